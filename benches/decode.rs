@@ -0,0 +1,78 @@
+use criterion::{Criterion, criterion_group, criterion_main};
+use itertools::Itertools;
+use plank::{PlankReader, PlankWriter};
+use std::path::PathBuf;
+
+const ROWS: usize = 100_000;
+
+fn write_fixture() -> PathBuf {
+    let dir = std::env::temp_dir().join("plank-bench");
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let csv_path = dir.join(format!("{}.csv", ROWS));
+    let plank_path = dir.join(format!("{}.plank", ROWS));
+
+    let mut w = csv::Writer::from_path(&csv_path).unwrap();
+    w.write_record(["id", "name", "active", "profile", "scores"])
+        .unwrap();
+    for i in 0..ROWS {
+        w.write_record([
+            i.to_string(),
+            format!("user-{}", i),
+            (i % 2 == 0).to_string(),
+            format!(
+                r#"{{"age": {}, "address": {{"city": "city-{}", "zip": {}}}, "tags": [{}]}}"#,
+                i % 90,
+                i % 97,
+                i % 10_000,
+                (0..8).map(|t| format!(r#""tag-{}""#, t)).join(", ")
+            ),
+            format!("[{}]", (0..32).map(|n| (i + n).to_string()).join(", ")),
+        ])
+        .unwrap();
+    }
+    w.flush().unwrap();
+
+    let mut f = PlankWriter::new(&plank_path).unwrap();
+    f.write_from_csv(&csv_path).unwrap();
+
+    plank_path
+}
+
+fn bench_decode(c: &mut Criterion) {
+    let path = write_fixture();
+
+    let mut group = c.benchmark_group("decode");
+    group.sample_size(10);
+
+    group.bench_function("100k_rows", |b| {
+        b.iter(|| {
+            let mut f = PlankReader::open(&path).unwrap();
+            let mut rows = 0;
+            for rg in &mut f {
+                for row in rg.unwrap() {
+                    std::hint::black_box(row.unwrap());
+                    rows += 1;
+                }
+            }
+            assert_eq!(rows, ROWS);
+        })
+    });
+
+    group.bench_function("100k_rows_nested_columns", |b| {
+        b.iter(|| {
+            let mut f = PlankReader::open(&path).unwrap();
+            let mut id = 0;
+            while let Ok(batch) = f.read_row_group_columns(id, &["profile", "scores"]) {
+                std::hint::black_box(batch);
+                id += 1;
+            }
+            assert_eq!(id, f.footer().row_group_count() as usize);
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_decode);
+criterion_main!(benches);
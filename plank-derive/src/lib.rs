@@ -0,0 +1,100 @@
+//! `#[derive(PlankRecord)]` for the `plank` crate, see `plank::PlankRecord`
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::ext::IdentExt;
+use syn::{Data, DeriveInput, Fields, parse_macro_input, parse_quote};
+
+/// Derives `PlankRecord` and `PlankValue` for a struct with named fields. Each field is a
+/// column named after it, typed by the field's own `PlankValue`: nested structs deriving
+/// `PlankRecord` are struct columns, `Vec<T>` lists and `Option<T>` nullable columns. Type
+/// parameters are bounded by `PlankValue`.
+#[proc_macro_derive(PlankRecord)]
+pub fn derive_plank_record(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "PlankRecord needs a struct with named fields",
+                ));
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "PlankRecord can only be derived for structs",
+            ));
+        }
+    };
+
+    let name = &input.ident;
+    // Fields of a type parameter are columns too, so every one must be a `PlankValue`
+    let mut generics = input.generics.clone();
+    let params: Vec<_> = generics.type_params().map(|p| p.ident.clone()).collect();
+    let where_clause = generics.make_where_clause();
+    for param in params {
+        where_clause
+            .predicates
+            .push(parse_quote!(#param: ::plank::PlankValue));
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let idents: Vec<_> = fields.iter().filter_map(|f| f.ident.as_ref()).collect();
+    let names: Vec<_> = idents.iter().map(|i| i.unraw().to_string()).collect();
+    let types: Vec<_> = fields.iter().map(|f| &f.ty).collect();
+    let width = idents.len();
+
+    Ok(quote! {
+        impl #impl_generics ::plank::PlankRecord for #name #ty_generics #where_clause {
+            fn schema() -> ::std::vec::Vec<::plank::PlankField> {
+                ::std::vec![#(
+                    ::plank::PlankField::new(
+                        #names,
+                        <#types as ::plank::PlankValue>::plank_type(),
+                    )
+                ),*]
+            }
+
+            fn to_row(&self) -> ::std::vec::Vec<::plank::PlankData> {
+                ::std::vec![#(::plank::PlankValue::to_plank(&self.#idents)),*]
+            }
+
+            fn from_row(row: ::std::vec::Vec<::plank::PlankData>) -> ::std::io::Result<Self> {
+                let mut values = ::plank::__private::row_values(row, #width)?;
+                ::std::result::Result::Ok(Self {
+                    #(#idents: ::plank::__private::take_field(&mut values, #names)?),*
+                })
+            }
+        }
+
+        impl #impl_generics ::plank::PlankValue for #name #ty_generics #where_clause {
+            fn plank_type() -> ::plank::PlankType {
+                ::plank::PlankType::Struct(<Self as ::plank::PlankRecord>::schema())
+            }
+
+            fn to_plank(&self) -> ::plank::PlankData {
+                ::plank::PlankData::Struct(::plank::PlankRecord::to_row(self))
+            }
+
+            fn from_plank(data: ::plank::PlankData) -> ::std::io::Result<Self> {
+                match data {
+                    ::plank::PlankData::Struct(values) => {
+                        <Self as ::plank::PlankRecord>::from_row(values)
+                    }
+                    other => ::std::result::Result::Err(::plank::__private::mismatch(
+                        &<Self as ::plank::PlankValue>::plank_type(),
+                        &other,
+                    )),
+                }
+            }
+        }
+    })
+}
@@ -1,10 +1,10 @@
+use jni::JNIEnv;
 use jni::objects::{JClass, JObject, JObjectArray, JString};
 use jni::sys::{jboolean, jint, jlong, jobject};
-use jni::JNIEnv;
 
 use crate::file::reader::{PlankReader, RecordBatch};
-use crate::serde::Serialize;
-use crate::types::{data::PlankData, fields::PlankField, types::PlankType};
+use crate::file::stats::ColumnStats;
+use crate::types::{data::PlankData, types::PlankType};
 
 #[unsafe(no_mangle)]
 pub extern "system" fn Java_io_plank_PlankReader_openNative(
@@ -89,22 +89,16 @@ pub extern "system" fn Java_io_plank_PlankReader_getFooterNative(
     let plank_meta_class = env.find_class("io/plank/PlankMeta").unwrap();
     let obj = env.alloc_object(&plank_meta_class).unwrap();
 
-    env.set_field(&obj, "rowCount", "I", (footer.row_count as jint).into())
+    // Counts are 64-bit, so Java sees them as longs
+    env.set_field(&obj, "rowCount", "J", (footer.row_count as jlong).into())
         .unwrap();
     env.set_field(&obj, "columnCount", "I", (footer.col_count as jint).into())
         .unwrap();
     env.set_field(
         &obj,
         "rowGroupCount",
-        "I",
-        (footer.row_group_count as jint).into(),
-    )
-    .unwrap();
-    env.set_field(
-        &obj,
-        "rowGroupCount",
-        "I",
-        (footer.row_group_count as jint).into(),
+        "J",
+        (footer.row_group_count as jlong).into(),
     )
     .unwrap();
 
@@ -132,9 +126,115 @@ pub extern "system" fn Java_io_plank_PlankReader_getFooterNative(
     )
     .unwrap();
 
+    // One map per row group from leaf path to its column chunk statistics
+    let list_class = env.find_class("java/util/ArrayList").unwrap();
+    let stats_list = env.new_object(&list_class, "()V", &[]).unwrap();
+
+    for id in 0..footer.row_group_count as usize {
+        let row_group_map = env.new_object(&map_class, "()V", &[]).unwrap();
+        let stats = reader.row_group_stats(id).unwrap_or_default();
+
+        for (leaf, stats) in reader.leaves().iter().zip(stats) {
+            let stats_obj = column_stats_to_jobject(&mut env, stats, leaf.field().field_type());
+            let path = env.new_string(leaf.path()).unwrap();
+            env.call_method(
+                &row_group_map,
+                "put",
+                "(Ljava/lang/Object;Ljava/lang/Object;)Ljava/lang/Object;",
+                &[(&path).into(), (&stats_obj).into()],
+            )
+            .unwrap();
+        }
+        env.call_method(
+            &stats_list,
+            "add",
+            "(Ljava/lang/Object;)Z",
+            &[(&row_group_map).into()],
+        )
+        .unwrap();
+    }
+    env.set_field(
+        &obj,
+        "columnStats",
+        "Ljava/util/ArrayList;",
+        (&stats_list).into(),
+    )
+    .unwrap();
+
+    // Metadata values are raw bytes, keyed by name
+    let metadata_map = env.new_object(&map_class, "()V", &[]).unwrap();
+    for (key, value) in footer.metadata() {
+        let key = env.new_string(key).unwrap();
+        let value = env.byte_array_from_slice(value).unwrap();
+        env.call_method(
+            &metadata_map,
+            "put",
+            "(Ljava/lang/Object;Ljava/lang/Object;)Ljava/lang/Object;",
+            &[(&key).into(), (&value).into()],
+        )
+        .unwrap();
+    }
+    env.set_field(
+        &obj,
+        "metadata",
+        "Ljava/util/LinkedHashMap;",
+        (&metadata_map).into(),
+    )
+    .unwrap();
+
     obj.into_raw()
 }
 
+fn column_stats_to_jobject<'local>(
+    env: &mut JNIEnv<'local>,
+    stats: &ColumnStats,
+    leaf_type: &PlankType,
+) -> JObject<'local> {
+    let map_class = env.find_class("java/util/LinkedHashMap").unwrap();
+    let map = env.new_object(map_class, "()V", &[]).unwrap();
+
+    let long_class = env.find_class("java/lang/Long").unwrap();
+    let mut entries = vec![
+        (
+            "min",
+            stats.min.as_ref().map_or(JObject::null(), |v| {
+                plank_data_to_jobject(env, v, leaf_type)
+            }),
+        ),
+        (
+            "max",
+            stats.max.as_ref().map_or(JObject::null(), |v| {
+                plank_data_to_jobject(env, v, leaf_type)
+            }),
+        ),
+    ];
+    for (key, value) in [
+        ("nullCount", stats.null_count),
+        ("distinctCount", stats.distinct_count),
+        ("encodedSize", stats.encoded_size),
+        ("decodedSize", stats.decoded_size),
+    ] {
+        let value = env
+            .new_object(&long_class, "(J)V", &[(value as jlong).into()])
+            .unwrap();
+        entries.push((key, value));
+    }
+
+    for (key, value) in entries {
+        let key = env.new_string(key).unwrap();
+        env.call_method(
+            &map,
+            "put",
+            "(Ljava/lang/Object;Ljava/lang/Object;)Ljava/lang/Object;",
+            &[(&key).into(), (&value).into()],
+        )
+        .unwrap();
+    }
+
+    map
+}
+
+#[allow(dead_code)]
 fn plank_type_to_jclass<'local>(
     env: &mut JNIEnv<'local>,
     plank_type: &PlankType,
@@ -144,8 +244,9 @@ fn plank_type_to_jclass<'local>(
         PlankType::Int32 => env.find_class("java/lang/Integer").unwrap(),
         PlankType::Int64 => env.find_class("java/lang/Long").unwrap(),
         PlankType::Bool => env.find_class("java/lang/Boolean").unwrap(),
-        PlankType::Struct(_) => env.find_class("java/util/HashMap").unwrap(),
+        PlankType::Struct(_) | PlankType::Map(_) => env.find_class("java/util/HashMap").unwrap(),
         PlankType::List(_) => env.find_class("java/util/ArrayList").unwrap(),
+        PlankType::Nullable(inner) => plank_type_to_jclass(env, inner),
     }
 }
 
@@ -156,6 +257,9 @@ fn plank_type_to_string(plank_type: &PlankType) -> String {
         PlankType::Int64 => "Long".to_string(),
         PlankType::Bool => "Boolean".to_string(),
         PlankType::List(item) => format!("List<{}>", plank_type_to_string(item)),
+        PlankType::Map(value) => format!("Map<String, {}>", plank_type_to_string(value)),
+        // Java references are nullable anyway
+        PlankType::Nullable(inner) => plank_type_to_string(inner),
         PlankType::Struct(fields) => {
             let field_strs: Vec<String> = fields
                 .iter()
@@ -220,6 +324,29 @@ fn plank_data_to_jobject<'local>(
             }
             list
         }
+        (PlankType::Map(value_type), PlankData::List(entries)) => {
+            let class = env.find_class("java/util/HashMap").unwrap();
+            let map = env.new_object(class, "()V", &[]).unwrap();
+            for entry in entries {
+                let PlankData::Struct(kv) = entry else {
+                    continue;
+                };
+                let [PlankData::Str(key), value] = kv.as_slice() else {
+                    continue;
+                };
+                let key = env.new_string(key).unwrap();
+                let val = plank_data_to_jobject(env, value, value_type);
+                env.call_method(
+                    &map,
+                    "put",
+                    "(Ljava/lang/Object;Ljava/lang/Object;)Ljava/lang/Object;",
+                    &[(&key).into(), (&val).into()],
+                )
+                .unwrap();
+            }
+            map
+        }
+        (PlankType::Nullable(inner), data) => plank_data_to_jobject(env, data, inner),
         _ => JObject::null(),
     }
 }
@@ -237,10 +364,10 @@ fn record_batch_to_jobject(env: &mut JNIEnv, batch: RecordBatch) -> jobject {
 
     for (i, col) in batch.columns.iter().enumerate() {
         let col_obj_array = env
-            .new_object_array(col.records.len() as jint, &obj_class, JObject::null())
+            .new_object_array(col.len() as jint, &obj_class, JObject::null())
             .unwrap();
-        for (j, record) in col.records.iter().enumerate() {
-            let val = plank_data_to_jobject(env, record, batch.schema[i].field_type());
+        for (j, record) in col.iter().enumerate() {
+            let val = plank_data_to_jobject(env, &record, batch.schema[i].field_type());
             env.set_object_array_element(&col_obj_array, j as jint, val)
                 .unwrap();
         }
@@ -1,5 +1,14 @@
+pub mod bloom;
+pub mod codec;
+pub mod config;
 pub(crate) mod footer;
+pub mod format;
+pub mod ingest;
+pub mod page_index;
+pub mod predicate;
 pub mod reader;
 pub(crate) mod rowgroup;
+pub mod sort;
+pub mod stats;
+pub mod view;
 pub mod writer;
-
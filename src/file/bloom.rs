@@ -0,0 +1,183 @@
+use crate::file::rowgroup::column::Column;
+use crate::serde::{Deserialize, Serialize};
+use crate::types::{data::PlankData, types::PlankType};
+
+/// The false positive rate used when none is given
+pub const DEFAULT_FPP: f64 = 0.01;
+
+const SALT: [u32; 8] = [
+    0x47b6137b, 0x44974d91, 0x8824ad5b, 0xa2b7289d, 0x705495c7, 0x2df1424b, 0x9efc4947, 0x5c6bfb31,
+];
+
+const MIN_BYTES: usize = 32;
+const MAX_BYTES: usize = 128 * 1024 * 1024;
+
+/// A split-block bloom filter over the values of one leaf column chunk.
+///
+/// Every value sets one bit in each of the eight 32-bit words of a single 256-bit block, so a
+/// lookup touches one cache line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BloomFilter {
+    blocks: Vec<[u32; 8]>,
+}
+
+impl BloomFilter {
+    /// Creates a filter sized for `distinct` values at a false positive rate of `fpp`
+    pub fn new(distinct: u64, fpp: f64) -> Self {
+        let fpp = fpp.clamp(f64::MIN_POSITIVE, 0.5);
+        let bits = -8.0 * distinct.max(1) as f64 / (1.0 - fpp.powf(1.0 / 8.0)).ln();
+        let bytes = ((bits / 8.0).ceil() as usize)
+            .clamp(MIN_BYTES, MAX_BYTES)
+            .next_power_of_two();
+
+        BloomFilter {
+            blocks: vec![[0; 8]; bytes / 32],
+        }
+    }
+
+    /// Builds a filter holding every value of a primitive column
+    pub(crate) fn from_column(values: &Column, distinct: u64, fpp: f64) -> Self {
+        let mut filter = BloomFilter::new(distinct, fpp);
+        let mut buf = Vec::new();
+        for i in 0..values.len() {
+            buf.clear();
+            values.encode_value(i, &mut buf);
+            filter.insert_hash(hash(&buf));
+        }
+        filter
+    }
+
+    pub fn insert(&mut self, value: &PlankData) -> std::io::Result<()> {
+        self.insert_hash(hash(&value.to_bytes()?));
+        Ok(())
+    }
+
+    /// Whether the value may have been inserted. False positives happen at about the rate the
+    /// filter was sized for, false negatives never.
+    pub fn might_contain(&self, value: &PlankData) -> std::io::Result<bool> {
+        let hash = hash(&value.to_bytes()?);
+        let block = &self.blocks[self.block_index(hash)];
+        Ok(mask(hash as u32)
+            .iter()
+            .zip(block)
+            .all(|(bit, word)| word & bit != 0))
+    }
+
+    fn insert_hash(&mut self, hash: u64) {
+        let index = self.block_index(hash);
+        for (word, bit) in self.blocks[index].iter_mut().zip(mask(hash as u32)) {
+            *word |= bit;
+        }
+    }
+
+    fn block_index(&self, hash: u64) -> usize {
+        (((hash >> 32) * self.blocks.len() as u64) >> 32) as usize
+    }
+}
+
+impl Serialize for BloomFilter {
+    fn to_bytes(&self) -> std::io::Result<Vec<u8>> {
+        Ok(self
+            .blocks
+            .iter()
+            .flatten()
+            .flat_map(|word| word.to_le_bytes())
+            .collect())
+    }
+}
+
+impl<'a> Deserialize<'a> for BloomFilter {
+    type Schema = ();
+    /// The filter takes up all of `bytes`
+    fn from_bytes(bytes: &[u8], _: &'a Self::Schema) -> std::io::Result<(Self, usize)> {
+        if bytes.is_empty() || !bytes.len().is_multiple_of(32) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "bloom filter is not made of whole blocks",
+            ));
+        }
+
+        let blocks = bytes
+            .chunks_exact(32)
+            .map(|block| {
+                let mut words = [0u32; 8];
+                for (word, bytes) in words.iter_mut().zip(block.chunks_exact(4)) {
+                    *word = u32::from_le_bytes(bytes.try_into().unwrap_or_default());
+                }
+                words
+            })
+            .collect();
+
+        Ok((BloomFilter { blocks }, bytes.len()))
+    }
+}
+
+/// Converts a lookup value to the type of the leaf it is looked up in, so integers hash the
+/// same way they were stored. Returns `None` when no stored value can be equal to it.
+pub(crate) fn coerce(value: &PlankData, leaf_type: &PlankType) -> Option<PlankData> {
+    match (value, leaf_type) {
+        (PlankData::Int32(n), PlankType::Int64) => Some(PlankData::Int64(*n as i64)),
+        (PlankData::Int64(n), PlankType::Int32) => i32::try_from(*n).ok().map(PlankData::Int32),
+        (PlankData::Int32(_), PlankType::Int32)
+        | (PlankData::Int64(_), PlankType::Int64)
+        | (PlankData::Str(_), PlankType::Str)
+        | (PlankData::Bool(_), PlankType::Bool) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+/// The bits a value sets in each word of its block
+fn mask(key: u32) -> [u32; 8] {
+    let mut mask = [0u32; 8];
+    for (bit, salt) in mask.iter_mut().zip(SALT) {
+        *bit = 1 << (key.wrapping_mul(salt) >> 27);
+    }
+    mask
+}
+
+/// FNV-1a followed by a 64-bit finalizer. Filters are persisted, so the hash must never
+/// change between versions.
+fn hash(bytes: &[u8]) -> u64 {
+    let mut h: u64 = 0xcbf29ce484222325;
+    for &b in bytes {
+        h ^= b as u64;
+        h = h.wrapping_mul(0x100000001b3);
+    }
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51afd7ed558ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ceb9fe1a85ec53);
+    h ^ (h >> 33)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bloom_filter_has_no_false_negatives() {
+        let values = Column::from_values(
+            &PlankType::Str,
+            (0..5000).map(|i| PlankData::Str(format!("user-{}", i))),
+        )
+        .unwrap();
+        let filter = BloomFilter::from_column(&values, 5000, 0.01);
+        let (filter, _) = BloomFilter::from_bytes(&filter.to_bytes().unwrap(), &()).unwrap();
+
+        for i in 0..5000 {
+            assert!(
+                filter
+                    .might_contain(&PlankData::Str(format!("user-{}", i)))
+                    .unwrap()
+            );
+        }
+        let false_positives = (5000..15000)
+            .filter(|i| {
+                filter
+                    .might_contain(&PlankData::Str(format!("user-{}", i)))
+                    .unwrap()
+            })
+            .count();
+        assert!(false_positives < 300, "{} false positives", false_positives);
+    }
+}
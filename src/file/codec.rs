@@ -0,0 +1,60 @@
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use std::borrow::Cow;
+use std::io::{Read, Write};
+
+/// Compression applied to an encoded column chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Codec {
+    /// Stored as-is, which lets memory-mapped readers borrow values straight from the file
+    Uncompressed,
+    #[default]
+    Zlib,
+}
+
+impl Codec {
+    pub(crate) fn id(&self) -> u8 {
+        match self {
+            Codec::Uncompressed => 0,
+            Codec::Zlib => 1,
+        }
+    }
+
+    pub(crate) fn from_id(id: u8) -> std::io::Result<Self> {
+        match id {
+            0 => Ok(Codec::Uncompressed),
+            1 => Ok(Codec::Zlib),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("unknown codec id {}", id),
+            )),
+        }
+    }
+
+    /// Appends `bytes` to `out` compressed with this codec
+    pub(crate) fn compress_into(&self, bytes: &[u8], out: &mut Vec<u8>) -> std::io::Result<()> {
+        match self {
+            Codec::Uncompressed => out.extend_from_slice(bytes),
+            Codec::Zlib => {
+                let mut c = ZlibEncoder::new(out, Compression::default());
+                c.write_all(bytes)?;
+                c.finish()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Uncompressed input is handed back borrowed
+    pub(crate) fn decompress<'a>(&self, bytes: &'a [u8]) -> std::io::Result<Cow<'a, [u8]>> {
+        match self {
+            Codec::Uncompressed => Ok(Cow::Borrowed(bytes)),
+            Codec::Zlib => {
+                let mut c = ZlibDecoder::new(bytes);
+                let mut buf = Vec::new();
+                c.read_to_end(&mut buf)?;
+                Ok(Cow::Owned(buf))
+            }
+        }
+    }
+}
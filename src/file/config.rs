@@ -0,0 +1,222 @@
+use crate::file::codec::Codec;
+use crate::file::rowgroup::column::{Column, page::Encoding};
+use crate::serde::{Deserialize, Serialize, read_u32, read_u64};
+
+/// Identifies the library that wrote a file
+pub const CREATED_BY: &str = concat!("plank ", env!("CARGO_PKG_VERSION"));
+
+/// Rows a row group holds at most by default. Row groups this large keep the footer small and
+/// give scans long runs of every column to read.
+pub const DEFAULT_ROW_GROUP_ROWS: usize = 1024 * 1024;
+
+/// Uncompressed bytes a row group holds at most by default
+pub const DEFAULT_ROW_GROUP_BYTES: usize = 128 * 1024 * 1024;
+
+/// Memory the rows buffered for a row group take at most by default
+pub const DEFAULT_MAX_ROW_GROUP_MEMORY: usize = 512 * 1024 * 1024;
+
+/// How the writer cuts rows into row groups. A row group is written as soon as it reaches
+/// any of its limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriterOptions {
+    pub(crate) row_group_rows: usize,
+    pub(crate) row_group_bytes: usize,
+    pub(crate) max_memory: usize,
+}
+
+/// The settings a file was written with, kept in its footer
+#[derive(Debug, Clone, PartialEq)]
+pub struct WriterConfig {
+    /// Rows a row group holds before the next one is started
+    pub row_group_rows: u64,
+    /// Uncompressed bytes a row group holds before the next one is started, `None` when
+    /// only the row count limits it
+    pub row_group_bytes: Option<u64>,
+    pub page_size: u64,
+    pub codec: Codec,
+    /// The encodings pages were written with
+    pub encodings: Vec<Encoding>,
+    pub created_by: String,
+    /// Memory the rows buffered for a row group took at most, `None` when not recorded
+    pub max_memory: Option<u64>,
+    /// The leaf columns bloom filters were built for, with their false positive rate
+    pub bloom_filters: Vec<(String, f64)>,
+}
+
+impl Default for WriterOptions {
+    fn default() -> Self {
+        WriterOptions {
+            row_group_rows: DEFAULT_ROW_GROUP_ROWS,
+            row_group_bytes: DEFAULT_ROW_GROUP_BYTES,
+            max_memory: DEFAULT_MAX_ROW_GROUP_MEMORY,
+        }
+    }
+}
+
+impl WriterOptions {
+    /// Sets how many rows a row group holds at most, at least one
+    pub fn with_row_group_rows(mut self, rows: usize) -> Self {
+        self.row_group_rows = rows.max(1);
+        self
+    }
+
+    /// Sets how many bytes the values of a row group take at most once encoded, before
+    /// compression
+    pub fn with_row_group_bytes(mut self, bytes: usize) -> Self {
+        self.row_group_bytes = bytes;
+        self
+    }
+
+    /// Sets how much memory the rows buffered for a row group take at most
+    pub fn with_max_memory(mut self, bytes: usize) -> Self {
+        self.max_memory = bytes;
+        self
+    }
+
+    /// Whether `rows` rows buffered in `columns` reach any of the limits
+    pub(crate) fn is_full(&self, columns: &[Column], rows: usize) -> bool {
+        rows >= self.row_group_rows
+            || columns.iter().map(Column::encoded_size).sum::<usize>() >= self.row_group_bytes
+            || columns.iter().map(Column::memory_size).sum::<usize>() >= self.max_memory
+    }
+}
+
+impl Serialize for WriterConfig {
+    fn to_bytes(&self) -> std::io::Result<Vec<u8>> {
+        let mut v = Vec::new();
+        v.extend_from_slice(&self.row_group_rows.to_le_bytes());
+        v.extend_from_slice(&self.row_group_bytes.unwrap_or(0).to_le_bytes());
+        v.extend_from_slice(&self.page_size.to_le_bytes());
+        v.push(self.codec.id());
+        v.extend_from_slice(&(self.encodings.len() as u32).to_le_bytes());
+        v.extend(self.encodings.iter().map(Encoding::id));
+        v.extend_from_slice(&(self.created_by.len() as u32).to_le_bytes());
+        v.extend_from_slice(self.created_by.as_bytes());
+        v.extend_from_slice(&self.max_memory.unwrap_or(0).to_le_bytes());
+        v.extend_from_slice(&(self.bloom_filters.len() as u32).to_le_bytes());
+        for (column, fpp) in &self.bloom_filters {
+            v.extend_from_slice(&(column.len() as u32).to_le_bytes());
+            v.extend_from_slice(column.as_bytes());
+            v.extend_from_slice(&fpp.to_le_bytes());
+        }
+        Ok(v)
+    }
+}
+
+impl<'a> Deserialize<'a> for WriterConfig {
+    type Schema = ();
+    fn from_bytes(bytes: &[u8], _: &'a Self::Schema) -> std::io::Result<(Self, usize)> {
+        let truncated = || {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "writer config is truncated",
+            )
+        };
+
+        let row_group_rows = read_u64(bytes, 0)?;
+        let row_group_bytes = Some(read_u64(bytes, 8)?).filter(|&b| b > 0);
+        let page_size = read_u64(bytes, 16)?;
+        let codec = Codec::from_id(*bytes.get(24).ok_or_else(truncated)?)?;
+
+        let count = read_u32(bytes, 25)? as usize;
+        let mut pos = 29;
+        let encodings = bytes
+            .get(pos..pos + count)
+            .ok_or_else(truncated)?
+            .iter()
+            .map(|&id| Encoding::from_id(id))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        pos += count;
+
+        let size = read_u32(bytes, pos)? as usize;
+        pos += 4;
+        let created_by = bytes
+            .get(pos..pos + size)
+            .and_then(|b| std::str::from_utf8(b).ok())
+            .ok_or_else(truncated)?
+            .to_string();
+        pos += size;
+
+        let max_memory = Some(read_u64(bytes, pos)?).filter(|&m| m > 0);
+        let count = read_u32(bytes, pos + 8)?;
+        pos += 12;
+        let mut bloom_filters = Vec::new();
+        for _ in 0..count {
+            let size = read_u32(bytes, pos)? as usize;
+            pos += 4;
+            let column = bytes
+                .get(pos..pos + size)
+                .and_then(|b| std::str::from_utf8(b).ok())
+                .ok_or_else(truncated)?
+                .to_string();
+            pos += size;
+            let fpp = f64::from_bits(read_u64(bytes, pos)?);
+            pos += 8;
+            bloom_filters.push((column, fpp));
+        }
+
+        Ok((
+            WriterConfig {
+                row_group_rows,
+                row_group_bytes,
+                page_size,
+                codec,
+                encodings,
+                created_by,
+                max_memory,
+                bloom_filters,
+            },
+            pos,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{data::PlankData, types::PlankType};
+
+    #[test]
+    fn test_options_cut_at_the_first_limit() {
+        let ids = Column::from_values(&PlankType::Int64, (0..10).map(PlankData::Int64)).unwrap();
+        let names = Column::from_values(
+            &PlankType::Str,
+            (0..10).map(|i| PlankData::Str(format!("name-{}", i))),
+        )
+        .unwrap();
+        let columns = [ids, names];
+        // 10 ids of 8 bytes, 10 names of 6 bytes behind a 4-byte length
+        assert_eq!(columns.iter().map(Column::encoded_size).sum::<usize>(), 180);
+
+        let options = WriterOptions::default();
+        assert!(!options.is_full(&columns, 10));
+        assert!(options.with_row_group_rows(10).is_full(&columns, 10));
+        assert!(options.with_row_group_bytes(180).is_full(&columns, 10));
+        assert!(!options.with_row_group_bytes(181).is_full(&columns, 10));
+        assert!(options.with_max_memory(180).is_full(&columns, 10));
+    }
+
+    #[test]
+    fn test_writer_config_round_trip() {
+        let config = WriterConfig {
+            row_group_rows: 10,
+            row_group_bytes: None,
+            page_size: 1024,
+            codec: Codec::Uncompressed,
+            encodings: vec![Encoding::Plain],
+            created_by: CREATED_BY.to_string(),
+            max_memory: Some(4096),
+            bloom_filters: vec![("user.name".to_string(), 0.001)],
+        };
+        let bytes = config.to_bytes().unwrap();
+        assert_eq!(
+            WriterConfig::from_bytes(&bytes, &()).unwrap(),
+            (config.clone(), bytes.len())
+        );
+
+        // A section cut anywhere is refused
+        for len in [0, 29, bytes.len() - 8, bytes.len() - 1] {
+            assert!(WriterConfig::from_bytes(&bytes[..len], &()).is_err());
+        }
+    }
+}
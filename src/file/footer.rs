@@ -1,122 +1,367 @@
-use crate::serde::{Deserialize, Serialize};
-use crate::types::{fields::PlankField, types::PlankType};
+use crate::file::config::WriterConfig;
+use crate::file::format::{FORMAT_VERSION, FeatureFlags, FormatVersion};
+use crate::file::rowgroup::shred;
+use crate::file::sort::SortOrder;
+use crate::file::stats::ColumnStats;
+use crate::serde::{Deserialize, Serialize, read_u32, read_u64};
+use crate::types::fields::PlankField;
 use sha2::{Digest, Sha256};
-use std::fmt::Write;
-use std::io::{BufRead, BufReader, Cursor, Read, Seek, SeekFrom};
-
-const PLANK_VERSION: &str = env!("CARGO_PKG_VERSION");
+use std::collections::BTreeMap;
 
 #[derive(Debug, Default)]
-pub(crate) struct Footer {
+pub struct Footer {
+    /// The format version of the file, from its trailer
+    pub(crate) version: FormatVersion,
+    pub(crate) features: FeatureFlags,
     pub(crate) schema: Vec<PlankField>,
-    pub(crate) offsets: Vec<u32>,
-    pub(crate) row_count: u32,
+    pub(crate) offsets: Vec<u64>,
+    pub(crate) row_count: u64,
     pub(crate) col_count: u32,
-    pub(crate) row_group_count: u32,
+    pub(crate) row_group_count: u64,
+    /// Statistics of every leaf column chunk, by row group
+    pub(crate) stats: Vec<Vec<ColumnStats>>,
+    /// Where the bloom filters written after the row groups are
+    pub(crate) bloom_filters: Vec<BlobLocation>,
+    /// Where the page index of every leaf column chunk is
+    pub(crate) page_indexes: Vec<BlobLocation>,
+    /// The columns rows were written sorted by, empty when unsorted
+    pub(crate) sort_order: SortOrder,
+    /// Key/value pairs set by the application that wrote the file
+    pub(crate) metadata: BTreeMap<String, Vec<u8>>,
+    /// The settings the file was written with
+    pub(crate) writer_config: Option<WriterConfig>,
+    /// The number of the first row of every row group
+    pub(crate) first_rows: Vec<u64>,
+    /// Where every leaf column chunk is, after its length
+    pub(crate) column_chunks: Vec<BlobLocation>,
+    /// The tags of sections of a newer writer, skipped when read
+    pub(crate) unknown_sections: Vec<u32>,
+}
+
+/// Where a blob kept for one leaf column chunk, such as its bloom filter or page index, or
+/// the chunk itself, is stored in the file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct BlobLocation {
+    pub(crate) row_group: u32,
+    pub(crate) leaf: u32,
+    pub(crate) offset: u64,
+    pub(crate) size: u64,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum FooterFieldType {
     Schema,
     Offsets,
     RowCount,
     ColCount,
     RowGroupCount,
+    Stats,
+    BloomFilters,
+    PageIndexes,
+    SortOrder,
+    Metadata,
+    WriterConfig,
+    FirstRows,
+    ColumnChunks,
+}
+
+impl FooterFieldType {
+    /// The id a section is tagged with. Ids are never reused.
+    fn tag(&self) -> u32 {
+        match self {
+            FooterFieldType::Schema => 1,
+            FooterFieldType::RowCount => 2,
+            FooterFieldType::ColCount => 3,
+            FooterFieldType::RowGroupCount => 4,
+            FooterFieldType::Offsets => 5,
+            FooterFieldType::Stats => 6,
+            FooterFieldType::BloomFilters => 7,
+            FooterFieldType::PageIndexes => 8,
+            FooterFieldType::SortOrder => 9,
+            FooterFieldType::Metadata => 10,
+            FooterFieldType::WriterConfig => 11,
+            FooterFieldType::FirstRows => 12,
+            FooterFieldType::ColumnChunks => 13,
+        }
+    }
+
+    fn from_tag(tag: u32) -> Option<Self> {
+        Footer::get_footer_layout()
+            .into_iter()
+            .find(|field| field.tag() == tag)
+    }
+
+    /// Whether a footer is unreadable without the section. Others default to empty.
+    fn is_required(&self) -> bool {
+        matches!(
+            self,
+            FooterFieldType::Schema
+                | FooterFieldType::RowCount
+                | FooterFieldType::ColCount
+                | FooterFieldType::RowGroupCount
+                | FooterFieldType::Offsets
+        )
+    }
 }
 
 impl Footer {
     pub fn new(
         schema: Vec<PlankField>,
-        offsets: Vec<u32>,
-        row_count: u32,
+        offsets: Vec<u64>,
+        row_count: u64,
         col_count: u32,
-        row_group_count: u32,
+        row_group_count: u64,
     ) -> Self {
         Footer {
+            version: FORMAT_VERSION,
+            features: FeatureFlags::empty(),
             schema,
             offsets,
             row_count,
             col_count,
             row_group_count,
+            stats: Vec::new(),
+            bloom_filters: Vec::new(),
+            page_indexes: Vec::new(),
+            sort_order: SortOrder::default(),
+            metadata: BTreeMap::new(),
+            writer_config: None,
+            first_rows: Vec::new(),
+            column_chunks: Vec::new(),
+            unknown_sections: Vec::new(),
         }
     }
 
-    fn get_footer_layout() -> Vec<FooterFieldType> {
-        // Use the plank_version if there are layout changes
-        match PLANK_VERSION {
-            _ => vec![
-                FooterFieldType::Schema,
-                FooterFieldType::RowCount,
-                FooterFieldType::ColCount,
-                FooterFieldType::RowGroupCount,
-                FooterFieldType::Offsets,
-            ],
-        }
+    /// Sets the per row group statistics of the leaf column chunks
+    pub fn with_stats(mut self, stats: Vec<Vec<ColumnStats>>) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    pub(crate) fn with_bloom_filters(mut self, bloom_filters: Vec<BlobLocation>) -> Self {
+        self.bloom_filters = bloom_filters;
+        self
+    }
+
+    /// Records the order rows were written in, with the key range of every row group
+    pub fn with_sort_order(mut self, sort_order: SortOrder) -> Self {
+        self.sort_order = sort_order;
+        self
     }
 
-    fn parse_field(reader: &mut BufReader<Cursor<&[u8]>>) -> std::io::Result<Vec<u8>> {
-        let mut buf = [0u8; 4];
-        reader.read_exact(&mut buf)?;
+    /// Sets the key/value pairs kept for the application
+    pub fn with_metadata(mut self, metadata: BTreeMap<String, Vec<u8>>) -> Self {
+        self.metadata = metadata;
+        self
+    }
 
-        let size = u32::from_le_bytes(buf);
+    /// The key/value pairs the writer stored in the file
+    pub fn metadata(&self) -> &BTreeMap<String, Vec<u8>> {
+        &self.metadata
+    }
 
-        let mut buf = vec![0u8; size as usize];
-        reader.read_exact(&mut buf)?;
+    /// Records the settings the file was written with
+    pub fn with_writer_config(mut self, writer_config: WriterConfig) -> Self {
+        self.writer_config = Some(writer_config);
+        self
+    }
 
-        Ok(buf)
+    pub fn writer_config(&self) -> Option<&WriterConfig> {
+        self.writer_config.as_ref()
     }
 
-    fn parse_count(bytes: &[u8]) -> std::io::Result<u32> {
-        Ok(u32::from_le_bytes(bytes[..4].try_into().map_err(|_| {
-            std::io::Error::new(std::io::ErrorKind::InvalidData, "expected bytes to be u32")
-        })?))
+    /// Sets the number of the first row of every row group
+    pub fn with_first_rows(mut self, first_rows: Vec<u64>) -> Self {
+        self.first_rows = first_rows;
+        self
     }
 
-    pub fn parse_schema(bytes: &[u8]) -> std::io::Result<Vec<PlankField>> {
-        let mut pos = 0;
-        let mut v: Vec<PlankField> = Vec::new();
+    pub fn schema(&self) -> &[PlankField] {
+        &self.schema
+    }
 
-        while pos + 4 < bytes.len() {
-            let size = u32::from_le_bytes(bytes[pos..pos + 4].try_into().map_err(|_| {
-                std::io::Error::new(std::io::ErrorKind::InvalidData, "expected bytes to be u32")
-            })?);
+    pub fn row_count(&self) -> u64 {
+        self.row_count
+    }
 
-            pos += 4;
+    pub fn row_group_count(&self) -> u64 {
+        self.row_group_count
+    }
+
+    /// The format version of the file the footer was read from, or is written in
+    pub fn version(&self) -> FormatVersion {
+        self.version
+    }
 
-            let field_name =
-                std::str::from_utf8(&bytes[pos..pos + size as usize]).map_err(|_| {
-                    std::io::Error::new(std::io::ErrorKind::InvalidData, "expected a field name")
-                })?;
+    /// The features the file relies on
+    pub fn features(&self) -> FeatureFlags {
+        self.features
+    }
 
-            pos += size as usize;
+    pub(crate) fn with_features(mut self, features: FeatureFlags) -> Self {
+        self.features = features;
+        self
+    }
 
-            let field_type = PlankType::from_bytes(&bytes[pos..], &())?;
+    pub(crate) fn with_column_chunks(mut self, column_chunks: Vec<BlobLocation>) -> Self {
+        self.column_chunks = column_chunks;
+        self
+    }
 
-            pos += field_type.encoded_size();
+    pub(crate) fn with_page_indexes(mut self, page_indexes: Vec<BlobLocation>) -> Self {
+        self.page_indexes = page_indexes;
+        self
+    }
 
-            v.push(PlankField::new(field_name, field_type))
+    /// The sections of a footer in the order they are written and parsed
+    fn get_footer_layout() -> Vec<FooterFieldType> {
+        vec![
+            FooterFieldType::Schema,
+            FooterFieldType::RowCount,
+            FooterFieldType::ColCount,
+            FooterFieldType::RowGroupCount,
+            FooterFieldType::Offsets,
+            FooterFieldType::Stats,
+            FooterFieldType::BloomFilters,
+            FooterFieldType::PageIndexes,
+            FooterFieldType::SortOrder,
+            FooterFieldType::Metadata,
+            FooterFieldType::WriterConfig,
+            FooterFieldType::FirstRows,
+            FooterFieldType::ColumnChunks,
+        ]
+    }
+
+    /// Reads a length-prefixed section payload at `pos` and moves past it
+    fn parse_field<'b>(bytes: &'b [u8], pos: &mut usize) -> std::io::Result<&'b [u8]> {
+        let size = read_u32(bytes, *pos)? as usize;
+        let payload = bytes.get(*pos + 4..*pos + 4 + size).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "footer section is truncated",
+            )
+        })?;
+        *pos += 4 + size;
+        Ok(payload)
+    }
+
+    /// Reads length-prefixed keys, each followed by a length-prefixed value
+    fn parse_metadata(bytes: &[u8]) -> std::io::Result<BTreeMap<String, Vec<u8>>> {
+        let count = read_u32(bytes, 0)?;
+        let mut pos = 4;
+        let mut metadata = BTreeMap::new();
+        for _ in 0..count {
+            let key = std::str::from_utf8(Self::parse_field(bytes, &mut pos)?).map_err(|_| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "metadata key is not valid UTF-8",
+                )
+            })?;
+            let value = Self::parse_field(bytes, &mut pos)?;
+            metadata.insert(key.to_string(), value.to_vec());
+        }
+        Ok(metadata)
+    }
+
+    pub fn parse_schema(bytes: &[u8]) -> std::io::Result<Vec<PlankField>> {
+        let mut pos = 0;
+        let mut v: Vec<PlankField> = Vec::new();
+
+        while pos < bytes.len() {
+            let (field, size) = PlankField::from_bytes(&bytes[pos..], &())?;
+            pos += size;
+            v.push(field)
         }
 
         Ok(v)
     }
 
-    pub fn parse_offsets(bytes: &[u8]) -> std::io::Result<Vec<u32>> {
+    pub fn parse_offsets(bytes: &[u8]) -> std::io::Result<Vec<u64>> {
+        (0..bytes.len() / 8)
+            .map(|i| read_u64(bytes, i * 8))
+            .collect()
+    }
+
+    /// Reads the statistics of each leaf of `schema`, row group after row group
+    pub fn parse_stats(
+        bytes: &[u8],
+        schema: &[PlankField],
+    ) -> std::io::Result<Vec<Vec<ColumnStats>>> {
+        let leaves = shred::leaves(schema)?;
+        let mut pos = 0;
         let mut v = Vec::new();
-        for chunk in bytes.chunks_exact(4) {
-            v.push(u32::from_le_bytes(chunk.try_into().map_err(|_| {
-                std::io::Error::new(std::io::ErrorKind::InvalidData, "expected u32")
-            })?));
+
+        while pos < bytes.len() && !leaves.is_empty() {
+            let mut row_group = Vec::with_capacity(leaves.len());
+            for leaf in &leaves {
+                let (stats, size) =
+                    ColumnStats::from_bytes(&bytes[pos..], leaf.field().field_type())?;
+                pos += size;
+                row_group.push(stats);
+            }
+            v.push(row_group);
         }
+
         Ok(v)
     }
 }
 
+impl BlobLocation {
+    /// Encoded size
+    const SIZE: usize = 24;
+
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.row_group.to_le_bytes());
+        out.extend_from_slice(&self.leaf.to_le_bytes());
+        out.extend_from_slice(&self.offset.to_le_bytes());
+        out.extend_from_slice(&self.size.to_le_bytes());
+    }
+
+    fn read(bytes: &[u8]) -> std::io::Result<Self> {
+        Ok(BlobLocation {
+            row_group: read_u32(bytes, 0)?,
+            leaf: read_u32(bytes, 4)?,
+            offset: read_u64(bytes, 8)?,
+            size: read_u64(bytes, 16)?,
+        })
+    }
+
+    /// Writes a footer section made of locations
+    fn write_all(locations: &[Self]) -> Vec<u8> {
+        let mut v = Vec::with_capacity(locations.len() * Self::SIZE);
+        for location in locations {
+            location.write(&mut v);
+        }
+        v
+    }
+
+    /// Reads a footer section made of locations, sorted by row group and leaf for `find`
+    fn parse_all(bytes: &[u8]) -> std::io::Result<Vec<Self>> {
+        let mut locations = bytes
+            .chunks(Self::SIZE)
+            .map(Self::read)
+            .collect::<std::io::Result<Vec<_>>>()?;
+        locations.sort_by_key(Self::key);
+        Ok(locations)
+    }
+
+    fn key(&self) -> (u32, u32) {
+        (self.row_group, self.leaf)
+    }
+
+    /// Finds the blob of a leaf column chunk in locations sorted by row group and leaf
+    pub(crate) fn find(locations: &[Self], row_group: usize, leaf: usize) -> Option<&Self> {
+        let key = (u32::try_from(row_group).ok()?, u32::try_from(leaf).ok()?);
+        locations
+            .binary_search_by_key(&key, Self::key)
+            .ok()
+            .map(|i| &locations[i])
+    }
+}
+
 impl Serialize for Footer {
     fn to_bytes(&self) -> std::io::Result<Vec<u8>> {
-        let mut s = Vec::new();
-
-        // s.extend_from_slice(format!("!PLANK_VERSION={}", PLANK_VERSION).as_bytes());
-        // s.push(b'\n');
+        let mut sections = Vec::new();
 
         for field in Self::get_footer_layout() {
             let bytes: Vec<u8> = match field {
@@ -132,69 +377,196 @@ impl Serialize for Footer {
                 FooterFieldType::Offsets => {
                     self.offsets.iter().flat_map(|f| f.to_le_bytes()).collect()
                 }
+                FooterFieldType::Stats => {
+                    let mut v = Vec::new();
+                    for stats in self.stats.iter().flatten() {
+                        v.extend_from_slice(&stats.to_bytes()?);
+                    }
+                    v
+                }
+                FooterFieldType::BloomFilters => BlobLocation::write_all(&self.bloom_filters),
+                FooterFieldType::PageIndexes => BlobLocation::write_all(&self.page_indexes),
+                FooterFieldType::ColumnChunks => BlobLocation::write_all(&self.column_chunks),
+                FooterFieldType::SortOrder if self.sort_order.is_empty() => Vec::new(),
+                FooterFieldType::SortOrder => self.sort_order.to_bytes()?,
+                FooterFieldType::Metadata if self.metadata.is_empty() => Vec::new(),
+                FooterFieldType::Metadata => {
+                    let mut v = Vec::new();
+                    v.extend_from_slice(&(self.metadata.len() as u32).to_le_bytes());
+                    for (key, value) in &self.metadata {
+                        v.extend_from_slice(&(key.len() as u32).to_le_bytes());
+                        v.extend_from_slice(key.as_bytes());
+                        v.extend_from_slice(&(value.len() as u32).to_le_bytes());
+                        v.extend_from_slice(value);
+                    }
+                    v
+                }
+                FooterFieldType::WriterConfig => match &self.writer_config {
+                    Some(config) => config.to_bytes()?,
+                    None => Vec::new(),
+                },
+                FooterFieldType::FirstRows => self
+                    .first_rows
+                    .iter()
+                    .flat_map(|r| r.to_le_bytes())
+                    .collect(),
             };
 
+            // Readers treat a missing optional section as an empty one
+            if bytes.is_empty() && !field.is_required() {
+                continue;
+            }
+            sections.push((field, bytes));
+        }
+
+        let mut s = Vec::new();
+        s.extend_from_slice(&(sections.len() as u32).to_le_bytes());
+        for (field, bytes) in sections {
+            s.extend_from_slice(&field.tag().to_le_bytes());
             s.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
             s.extend_from_slice(&bytes);
         }
 
         let checksum = Sha256::digest(&s);
-        s.extend_from_slice(&checksum.to_vec());
+        s.extend_from_slice(&checksum);
 
         Ok(s)
     }
 }
 
 impl<'a> Deserialize<'a> for Footer {
-    type Schema = ();
-    fn from_bytes(bytes: &[u8], _: &'a Self::Schema) -> std::io::Result<Self> {
-        let mut br = BufReader::new(Cursor::new(bytes));
-        let before = br.stream_position()?;
+    /// The format version of the file the footer was read from
+    type Schema = FormatVersion;
+    fn from_bytes(bytes: &[u8], version: &'a Self::Schema) -> std::io::Result<(Self, usize)> {
+        let version = *version;
+        let mut pos = 0;
+        let mut sections: Vec<(FooterFieldType, &[u8])> = Vec::new();
+        let mut unknown_sections = Vec::new();
 
-        let mut footer = Footer::default();
+        let count = read_u32(bytes, 0)?;
+        pos += 4;
+        for _ in 0..count {
+            let tag = read_u32(bytes, pos)?;
+            pos += 4;
+            let payload = Self::parse_field(bytes, &mut pos)?;
 
-        for field in Self::get_footer_layout() {
-            match field {
-                FooterFieldType::Schema => {
-                    footer.schema = Self::parse_schema(&Self::parse_field(&mut br)?)?
-                }
-                FooterFieldType::RowCount => {
-                    footer.row_count = Self::parse_count(&Self::parse_field(&mut br)?)?
-                }
-                FooterFieldType::ColCount => {
-                    footer.col_count = Self::parse_count(&Self::parse_field(&mut br)?)?
-                }
-                FooterFieldType::RowGroupCount => {
-                    footer.row_group_count = Self::parse_count(&Self::parse_field(&mut br)?)?
-                }
-                FooterFieldType::Offsets => {
-                    footer.offsets = Self::parse_offsets(&Self::parse_field(&mut br)?)?
-                }
+            // Sections added by newer writers are skipped
+            let Some(field) = FooterFieldType::from_tag(tag) else {
+                unknown_sections.push(tag);
+                continue;
+            };
+            if sections.iter().any(|(f, _)| *f == field) {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("footer holds more than one {:?} section", field),
+                ));
             }
+            sections.push((field, payload));
         }
 
-        let after = br.stream_position()?;
-
         // Sha256 is 32 bytes
-        let mut provided = [0u8; 32];
-        br.read_exact(&mut provided);
-
-        let buf = &bytes[before as usize..after as usize];
-
-        if provided != Sha256::digest(buf)[..] {
+        let provided = bytes.get(pos..pos + 32).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "footer checksum is missing",
+            )
+        })?;
+        if provided != &Sha256::digest(&bytes[..pos])[..] {
             return Err(std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
                 "incorrect checksum found",
             ));
         }
 
-        Ok(footer)
+        let mut footer = Footer {
+            version,
+            unknown_sections,
+            ..Default::default()
+        };
+
+        // The schema is parsed first, later sections depend on it
+        for field in Self::get_footer_layout() {
+            let Some(&(_, payload)) = sections.iter().find(|(f, _)| *f == field) else {
+                if field.is_required() {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("footer is missing its {:?} section", field),
+                    ));
+                }
+                continue;
+            };
+
+            match field {
+                FooterFieldType::Schema => footer.schema = Self::parse_schema(payload)?,
+                FooterFieldType::RowCount => footer.row_count = read_u64(payload, 0)?,
+                FooterFieldType::ColCount => footer.col_count = read_u32(payload, 0)?,
+                FooterFieldType::RowGroupCount => footer.row_group_count = read_u64(payload, 0)?,
+                FooterFieldType::Offsets => footer.offsets = Self::parse_offsets(payload)?,
+                FooterFieldType::Stats => {
+                    footer.stats = Self::parse_stats(payload, &footer.schema)?
+                }
+                FooterFieldType::BloomFilters => {
+                    footer.bloom_filters = BlobLocation::parse_all(payload)?
+                }
+                FooterFieldType::PageIndexes => {
+                    footer.page_indexes = BlobLocation::parse_all(payload)?
+                }
+                FooterFieldType::SortOrder => {
+                    footer.sort_order = SortOrder::from_bytes(payload, &footer.schema)?.0
+                }
+                FooterFieldType::Metadata => footer.metadata = Self::parse_metadata(payload)?,
+                FooterFieldType::WriterConfig => {
+                    footer.writer_config = Some(WriterConfig::from_bytes(payload, &())?.0)
+                }
+                FooterFieldType::FirstRows => {
+                    footer.first_rows = (0..payload.len() / 8)
+                        .map(|i| read_u64(payload, i * 8))
+                        .collect::<std::io::Result<_>>()?
+                }
+                FooterFieldType::ColumnChunks => {
+                    footer.column_chunks = BlobLocation::parse_all(payload)?
+                }
+            }
+        }
+
+        Ok((footer, pos + provided.len()))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::file::codec::Codec;
+    use crate::file::config::CREATED_BY;
+    use crate::file::rowgroup::column::page::Encoding;
+    use crate::types::{data::PlankData, types::PlankType};
+
+    /// The bytes of a footer made of `sections`, each a tag and its payload
+    fn tagged(sections: &[(u32, Vec<u8>)]) -> Vec<u8> {
+        let mut v = (sections.len() as u32).to_le_bytes().to_vec();
+        for (tag, payload) in sections {
+            v.extend_from_slice(&tag.to_le_bytes());
+            v.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+            v.extend_from_slice(payload);
+        }
+        let checksum = Sha256::digest(&v);
+        v.extend_from_slice(&checksum);
+        v
+    }
+
+    /// The required sections of a footer with one Int64 column and one row group
+    fn required_sections() -> Vec<(u32, Vec<u8>)> {
+        vec![
+            (
+                1,
+                PlankField::new("id", PlankType::Int64).to_bytes().unwrap(),
+            ),
+            (2, 7u64.to_le_bytes().to_vec()),
+            (3, 1u32.to_le_bytes().to_vec()),
+            (4, 1u64.to_le_bytes().to_vec()),
+            (5, 4u64.to_le_bytes().to_vec()),
+        ]
+    }
 
     #[test]
     fn test_roundtrip_footer() {
@@ -207,16 +579,171 @@ mod tests {
             10,
             2,
             5,
-        );
+        )
+        .with_stats(vec![vec![
+            ColumnStats {
+                min: Some(PlankData::Int32(-4)),
+                max: Some(PlankData::Int32(7)),
+                distinct_count: 10,
+                ..Default::default()
+            },
+            ColumnStats::default(),
+        ]])
+        .with_metadata(BTreeMap::from([
+            ("origin".to_string(), b"etl".to_vec()),
+            ("empty".to_string(), Vec::new()),
+        ]))
+        .with_writer_config(WriterConfig {
+            row_group_rows: 10,
+            row_group_bytes: None,
+            page_size: 1024,
+            codec: Codec::Zlib,
+            encodings: vec![Encoding::Plain],
+            created_by: CREATED_BY.to_string(),
+            max_memory: Some(1 << 20),
+            bloom_filters: vec![("id".to_string(), 0.05)],
+        })
+        .with_first_rows(vec![0, 10, 20, 30, 40]);
 
         let bytes = footer.to_bytes().unwrap();
-        let deserialized = Footer::from_bytes(&bytes, &()).unwrap();
+        let (deserialized, size) = Footer::from_bytes(&bytes, &FORMAT_VERSION).unwrap();
+
+        assert_eq!(size, bytes.len());
 
         assert_eq!(footer.row_count, deserialized.row_count);
         assert_eq!(footer.col_count, deserialized.col_count);
         assert_eq!(footer.row_group_count, deserialized.row_group_count);
         assert_eq!(footer.offsets, deserialized.offsets);
+        assert_eq!(footer.stats, deserialized.stats);
+        assert_eq!(footer.metadata, deserialized.metadata);
+        assert_eq!(footer.writer_config, deserialized.writer_config);
+        assert_eq!(footer.first_rows, deserialized.first_rows);
 
         assert_eq!(bytes, deserialized.to_bytes().unwrap());
+
+        // Row and row group counts are 64-bit
+        let mut sections = required_sections();
+        sections[1].1 = (5u64 << 32).to_le_bytes().to_vec();
+        let (read, _) = Footer::from_bytes(&tagged(&sections), &FORMAT_VERSION).unwrap();
+        assert_eq!(read.row_count(), 5 << 32);
+    }
+
+    #[test]
+    fn test_unknown_sections_are_skipped() {
+        let known = required_sections();
+        let (expected, _) = Footer::from_bytes(&tagged(&known), &FORMAT_VERSION).unwrap();
+
+        // Sections of a newer writer, first, between known ones and last
+        let mut sections = known.clone();
+        sections.insert(0, (1000, b"newer".to_vec()));
+        sections.insert(3, (1001, Vec::new()));
+        sections.push((u32::MAX, vec![0xff; 64]));
+        let bytes = tagged(&sections);
+        let (footer, size) = Footer::from_bytes(&bytes, &FORMAT_VERSION).unwrap();
+        assert_eq!(size, bytes.len());
+        assert_eq!(footer.schema, [PlankField::new("id", PlankType::Int64)]);
+        assert_eq!(footer.row_count, 7);
+        assert_eq!(footer.offsets, [4]);
+        assert_eq!(footer.unknown_sections, [1000, 1001, u32::MAX]);
+        assert_eq!(footer.to_bytes().unwrap(), expected.to_bytes().unwrap());
+
+        // A known section is not skipped when repeated or missing
+        let mut twice = known.clone();
+        twice.push((2, 8u64.to_le_bytes().to_vec()));
+        assert!(Footer::from_bytes(&tagged(&twice), &FORMAT_VERSION).is_err());
+        assert!(Footer::from_bytes(&tagged(&known[1..]), &FORMAT_VERSION).is_err());
+
+        // So is one too short for its value
+        for section in 1..4 {
+            let mut short = known.clone();
+            short[section].1.truncate(3);
+            assert!(Footer::from_bytes(&tagged(&short), &FORMAT_VERSION).is_err());
+        }
+    }
+
+    #[test]
+    fn test_metadata_round_trips_binary_values() {
+        let metadata = BTreeMap::from([
+            ("git_sha".to_string(), b"4c1a8ab".to_vec()),
+            ("registry.id".to_string(), 42u64.to_le_bytes().to_vec()),
+            ("raw".to_string(), vec![0, 0xff, 0xfe, 0x80, 0, 1]),
+            ("empty".to_string(), Vec::new()),
+            ("schlüssel".to_string(), "wert".as_bytes().to_vec()),
+        ]);
+        let footer = Footer::new(
+            vec![PlankField::new("id", PlankType::Int64)],
+            vec![4],
+            7,
+            1,
+            1,
+        )
+        .with_metadata(metadata.clone());
+        let bytes = footer.to_bytes().unwrap();
+        let (read, _) = Footer::from_bytes(&bytes, &FORMAT_VERSION).unwrap();
+        assert_eq!(read.metadata(), &metadata);
+
+        // A footer without the section has no pairs
+        let mut sections = required_sections();
+        let (read, _) = Footer::from_bytes(&tagged(&sections), &FORMAT_VERSION).unwrap();
+        assert!(read.metadata().is_empty());
+
+        // Keys must be UTF-8, values need not be
+        let mut section = 1u32.to_le_bytes().to_vec();
+        for field in [&[0xffu8, 0xfe][..], b"value"] {
+            section.extend_from_slice(&(field.len() as u32).to_le_bytes());
+            section.extend_from_slice(field);
+        }
+        sections.push((10, section));
+        assert!(Footer::from_bytes(&tagged(&sections), &FORMAT_VERSION).is_err());
+    }
+
+    #[test]
+    fn test_writer_config_and_first_rows() {
+        let (footer, _) =
+            Footer::from_bytes(&tagged(&required_sections()), &FORMAT_VERSION).unwrap();
+        assert!(footer.writer_config().is_none());
+        assert!(footer.first_rows.is_empty());
+
+        let config = WriterConfig {
+            row_group_rows: 5,
+            row_group_bytes: Some(1 << 20),
+            page_size: 4096,
+            codec: Codec::Uncompressed,
+            encodings: vec![Encoding::Plain],
+            created_by: CREATED_BY.to_string(),
+            max_memory: None,
+            bloom_filters: Vec::new(),
+        };
+        let mut sections = required_sections();
+        sections.push((11, config.to_bytes().unwrap()));
+        sections.push((12, 0u64.to_le_bytes().to_vec()));
+        let (footer, _) = Footer::from_bytes(&tagged(&sections), &FORMAT_VERSION).unwrap();
+        assert_eq!(footer.writer_config(), Some(&config));
+        assert_eq!(footer.first_rows, [0]);
+    }
+
+    #[test]
+    fn test_find_blob_location() {
+        let location = |row_group, leaf| BlobLocation {
+            row_group,
+            leaf,
+            offset: 100 * row_group as u64 + leaf as u64,
+            size: 1,
+        };
+        // Locations are found whatever order they were written in
+        let written: Vec<_> = (0..50)
+            .flat_map(|rg| [location(rg, 3), location(rg, 0), location(rg, 1)])
+            .collect();
+        let locations = BlobLocation::parse_all(&BlobLocation::write_all(&written)).unwrap();
+
+        for rg in 0..50 {
+            for leaf in [0, 1, 3] {
+                let found = BlobLocation::find(&locations, rg as usize, leaf as usize);
+                assert_eq!(found, Some(&location(rg, leaf)));
+            }
+            assert_eq!(BlobLocation::find(&locations, rg as usize, 2), None);
+        }
+        assert_eq!(BlobLocation::find(&locations, 50, 0), None);
+        assert_eq!(BlobLocation::find(&locations, usize::MAX, 0), None);
     }
 }
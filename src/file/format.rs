@@ -0,0 +1,283 @@
+use std::fmt;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+/// Written at the very start and the very end of every file
+pub const MAGIC: [u8; 4] = *b"PLNK";
+
+/// The format files are written in. Readers refuse a newer major version. A newer minor
+/// version only adds to the footer, so readers read what they know of it.
+///
+/// A change older readers would misread, such as a new page or row group layout, bumps the
+/// major version. A new footer section or type bumps the minor version. A structure only some files
+/// use also gets a `FeatureFlags` bit, so older readers refuse just those files.
+/// `test_format_is_pinned` fails on any change to the bytes written, until it is versioned.
+pub const FORMAT_VERSION: FormatVersion = FormatVersion { major: 1, minor: 1 };
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FormatVersion {
+    pub major: u16,
+    pub minor: u16,
+}
+
+/// Features a file relies on. A reader must understand every flag set to read the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub struct FeatureFlags(u32);
+
+/// The fixed-size end of a file: where the footer starts, the format version and features,
+/// then the magic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Trailer {
+    pub(crate) footer_offset: u64,
+    pub(crate) version: FormatVersion,
+    pub(crate) features: FeatureFlags,
+}
+
+impl Default for FormatVersion {
+    fn default() -> Self {
+        FORMAT_VERSION
+    }
+}
+
+impl fmt::Display for FormatVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+impl FeatureFlags {
+    /// Struct or list columns, stored as leaves with repetition and definition levels
+    pub const NESTED_COLUMNS: FeatureFlags = FeatureFlags(1);
+    pub const BLOOM_FILTERS: FeatureFlags = FeatureFlags(1 << 1);
+    pub const PAGE_INDEXES: FeatureFlags = FeatureFlags(1 << 2);
+    pub const SORT_ORDER: FeatureFlags = FeatureFlags(1 << 3);
+    /// Columns holding nulls, kept as definition levels
+    pub const NULLABLE_COLUMNS: FeatureFlags = FeatureFlags(1 << 4);
+    /// Map columns, type id 8, from format version 1.1
+    pub const MAP_COLUMNS: FeatureFlags = FeatureFlags(1 << 5);
+
+    const KNOWN: u32 = 0b111111;
+
+    pub fn empty() -> Self {
+        FeatureFlags(0)
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    pub fn contains(&self, other: FeatureFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub(crate) fn insert(&mut self, other: FeatureFlags) {
+        self.0 |= other.0;
+    }
+}
+
+impl Trailer {
+    pub(crate) const SIZE: u64 = 8 + 2 + 2 + 4 + 4;
+
+    pub(crate) fn write<W: Write>(&self, out: &mut W) -> std::io::Result<()> {
+        out.write_all(&self.footer_offset.to_le_bytes())?;
+        out.write_all(&self.version.major.to_le_bytes())?;
+        out.write_all(&self.version.minor.to_le_bytes())?;
+        out.write_all(&self.features.bits().to_le_bytes())?;
+        out.write_all(&MAGIC)
+    }
+
+    /// Reads the trailer at the end of a file and checks this reader can handle the file
+    pub(crate) fn read<R: Read + Seek>(f: &mut R) -> std::io::Result<Self> {
+        let len = f.seek(SeekFrom::End(0))?;
+        if len < 4 {
+            return Err(not_plank("file is too short"));
+        }
+
+        let mut head = [0u8; 4];
+        f.seek(SeekFrom::Start(0))?;
+        f.read_exact(&mut head)?;
+        let mut tail = [0u8; 4];
+        f.seek(SeekFrom::End(-4))?;
+        f.read_exact(&mut tail)?;
+
+        let trailer = if tail == MAGIC && len >= 4 + Self::SIZE {
+            let mut buf = [0u8; Self::SIZE as usize - 4];
+            f.seek(SeekFrom::End(-(Self::SIZE as i64)))?;
+            f.read_exact(&mut buf)?;
+            Trailer {
+                footer_offset: u64::from_le_bytes(buf[0..8].try_into().unwrap_or_default()),
+                version: FormatVersion {
+                    major: u16::from_le_bytes([buf[8], buf[9]]),
+                    minor: u16::from_le_bytes([buf[10], buf[11]]),
+                },
+                features: FeatureFlags(u32::from_le_bytes(
+                    buf[12..16].try_into().unwrap_or_default(),
+                )),
+            }
+        } else if head == MAGIC {
+            return Err(not_plank(
+                "file is truncated, its trailing magic is missing",
+            ));
+        } else {
+            return Err(not_plank("missing magic"));
+        };
+
+        trailer.check(len)?;
+        Ok(trailer)
+    }
+
+    fn check(&self, len: u64) -> std::io::Result<()> {
+        if self.version.major > FORMAT_VERSION.major {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "file format version {} is newer than {}, the newest this reader supports",
+                    self.version, FORMAT_VERSION
+                ),
+            ));
+        }
+        let unknown = self.features.bits() & !FeatureFlags::KNOWN;
+        if unknown != 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "file uses features this reader does not know: {:#x}",
+                    unknown
+                ),
+            ));
+        }
+        if self.footer_offset >= len {
+            return Err(not_plank("footer offset points past the end of the file"));
+        }
+        Ok(())
+    }
+}
+
+fn not_plank(reason: &str) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("not a plank file: {}", reason),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::codec::Codec;
+    use crate::file::reader::PlankReader;
+    use crate::file::sort::SortColumn;
+    use crate::file::writer::PlankWriter;
+    use crate::serde::Serialize;
+    use crate::types::{data::PlankData, fields::PlankField, types::PlankType};
+    use sha2::{Digest, Sha256};
+    use std::io::Cursor;
+
+    /// The SHA-256 of the file written by `test_format_is_pinned`, its writer name left out
+    const PINNED_FORMAT: &str = "0b9d31efb188adca9dbfecee5a292fc2aa8025e3f90c52cd8d9ade7ca34a550e";
+
+    #[test]
+    fn test_format_is_pinned() {
+        let dir = std::env::temp_dir().join(format!("plank-pinned-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("pinned.plank");
+
+        let schema = vec![
+            PlankField::new("id", PlankType::Int32),
+            PlankField::new("name", PlankType::Str),
+            PlankField::new("active", PlankType::Bool),
+            PlankField::new("score", PlankType::Nullable(Box::new(PlankType::Int64))),
+            PlankField::new(
+                "user",
+                PlankType::Struct(vec![
+                    PlankField::new("city", PlankType::Str),
+                    PlankField::new("tags", PlankType::List(Box::new(PlankType::Str))),
+                ]),
+            ),
+        ];
+        let mut writer = PlankWriter::with_schema(&path, schema)
+            .unwrap()
+            .with_codec(Codec::Zlib)
+            .with_page_size(64)
+            .with_options(crate::file::config::WriterOptions::default().with_row_group_rows(4))
+            .with_bloom_filter("name", 0.01)
+            .with_sort_order(vec![SortColumn::asc("id")])
+            .with_metadata("origin", "pinned");
+        for i in 0..10 {
+            let tags = (0..i % 3)
+                .map(|t| PlankData::Str(format!("tag-{}", t)))
+                .collect();
+            writer
+                .write_row(&[
+                    PlankData::Int32(i),
+                    PlankData::Str(format!("name-{}", i)),
+                    PlankData::Bool(i % 2 == 0),
+                    if i % 4 == 0 {
+                        PlankData::Null
+                    } else {
+                        PlankData::Int64(i as i64 * 1000)
+                    },
+                    PlankData::Struct(vec![
+                        PlankData::Str(format!("city-{}", i % 2)),
+                        PlankData::List(tags),
+                    ]),
+                ])
+                .unwrap();
+        }
+        writer.finish().unwrap();
+
+        // The writer name holds the crate version, so it is replaced by a fixed one
+        let reader = PlankReader::open(&path).unwrap();
+        let mut footer = reader.into_footer();
+        if let Some(config) = footer.writer_config.as_mut() {
+            config.created_by = "plank".to_string();
+        }
+        let bytes = std::fs::read(&path).unwrap();
+        let trailer = Trailer::read(&mut Cursor::new(&bytes)).unwrap();
+        let mut hasher = Sha256::new();
+        hasher.update(&bytes[..trailer.footer_offset as usize]);
+        hasher.update(footer.to_bytes().unwrap());
+        hasher.update(&bytes[bytes.len() - Trailer::SIZE as usize..]);
+        let hash = format!("{:x}", hasher.finalize());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            hash, PINNED_FORMAT,
+            "the bytes written changed: bump FORMAT_VERSION or add a feature flag, then update \
+             PINNED_FORMAT"
+        );
+    }
+
+    #[test]
+    fn test_trailer_negotiates_versions() {
+        let trailer = Trailer {
+            footer_offset: 4,
+            version: FORMAT_VERSION,
+            features: FeatureFlags::BLOOM_FILTERS,
+        };
+        let mut file = MAGIC.to_vec();
+        file.extend_from_slice(&[0; 8]);
+        trailer.write(&mut file).unwrap();
+        assert_eq!(Trailer::read(&mut Cursor::new(&file)).unwrap(), trailer);
+
+        // A newer minor version is read, a newer major version is refused
+        let mut newer = trailer;
+        newer.version.minor += 1;
+        let mut file = MAGIC.to_vec();
+        file.extend_from_slice(&[0; 8]);
+        newer.write(&mut file).unwrap();
+        assert_eq!(Trailer::read(&mut Cursor::new(&file)).unwrap(), newer);
+
+        newer.version.major += 1;
+        let mut file = MAGIC.to_vec();
+        newer.write(&mut file).unwrap();
+        let err = Trailer::read(&mut Cursor::new(&file)).unwrap_err();
+        assert!(err.to_string().contains("2.2"), "{}", err);
+
+        // The leading magic without the trailing one means the file was cut short
+        let mut file = MAGIC.to_vec();
+        file.extend_from_slice(&[0; 30]);
+        assert!(Trailer::read(&mut Cursor::new(&file)).is_err());
+        // Neither magic, such as a file from before the magic bytes
+        let err = Trailer::read(&mut Cursor::new(&[1, 2, 3, 1, 0, 0, 0])).unwrap_err();
+        assert!(err.to_string().contains("missing magic"), "{}", err);
+    }
+}
@@ -0,0 +1,477 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::PathBuf;
+
+use flate2::read::MultiGzDecoder;
+
+use crate::types::{
+    data::PlankData,
+    fields::PlankField,
+    types::{JsonType, PlankType},
+};
+
+/// Records read at most to find a value of every column to infer its type from
+pub(crate) const INFER_RECORDS: usize = 1024;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const UTF8_BOM: [u8; 3] = [0xef, 0xbb, 0xbf];
+
+/// How the text of an input is encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextEncoding {
+    /// UTF-8, with or without a byte order mark
+    #[default]
+    Utf8,
+    /// ISO-8859-1, one byte per character
+    Latin1,
+}
+
+/// What becomes of records that cannot be written
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum BadRecordPolicy {
+    /// Stops the conversion with the `IngestError`
+    #[default]
+    Fail,
+    /// Leaves the record out
+    Skip,
+    /// Writes a null in place of each cell that cannot be read. Records whose cells cannot be
+    /// nulled, in columns that are not nullable or missing, still fail.
+    Null,
+    /// Leaves the record out and appends it to a file at the path, created on the first
+    /// rejected record: CSV records in the dialect they were read in, after the header row,
+    /// and NDJSON lines as they were
+    Quarantine(PathBuf),
+}
+
+/// Why a record of an input could not be written. Conversions fail with an `InvalidData`
+/// error holding it, which `std::io::Error::get_ref` gives back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IngestError {
+    /// A cell that cannot be read as the type of its column
+    Cell {
+        line: u64,
+        column: String,
+        value: String,
+        reason: String,
+    },
+    /// A record with another number of cells than the schema has columns
+    Width {
+        line: u64,
+        cells: usize,
+        columns: usize,
+    },
+    /// A record that cannot be read at all, such as an NDJSON line that is not an object
+    Record { line: u64, reason: String },
+}
+
+/// What a conversion wrote and left out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct IngestReport {
+    pub rows_written: u64,
+    pub rows_skipped: u64,
+    pub rows_quarantined: u64,
+    /// Cells written as nulls because they could not be read
+    pub cells_nulled: u64,
+}
+
+/// Applies a `BadRecordPolicy` while an input is converted
+pub(crate) struct BadRecords {
+    policy: BadRecordPolicy,
+    /// Written to the quarantine file ahead of the first record
+    header: Vec<u8>,
+    quarantine: Option<BufWriter<File>>,
+    pub(crate) report: IngestReport,
+}
+
+/// How a CSV is read: its dialect, the cells taken as nulls and its text encoding
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvOptions {
+    pub(crate) delimiter: u8,
+    pub(crate) quote: u8,
+    pub(crate) escape: Option<u8>,
+    pub(crate) has_headers: bool,
+    pub(crate) comment: Option<u8>,
+    pub(crate) trim: bool,
+    pub(crate) null_values: Vec<String>,
+    pub(crate) encoding: TextEncoding,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            delimiter: b',',
+            quote: b'"',
+            escape: None,
+            has_headers: true,
+            comment: None,
+            trim: false,
+            null_values: Vec::new(),
+            encoding: TextEncoding::Utf8,
+        }
+    }
+}
+
+impl CsvOptions {
+    /// Sets the byte separating cells, `b'\t'` for TSV
+    pub fn with_delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    pub fn with_quote(mut self, quote: u8) -> Self {
+        self.quote = quote;
+        self
+    }
+
+    /// Sets the byte escaping quotes inside quoted cells. Without one, quotes are escaped by
+    /// doubling them.
+    pub fn with_escape(mut self, escape: u8) -> Self {
+        self.escape = Some(escape);
+        self
+    }
+
+    /// Sets whether the first record names the columns. Without headers, columns are named
+    /// `column_1`, `column_2` and so on, unless the writer has a schema.
+    pub fn with_headers(mut self, has_headers: bool) -> Self {
+        self.has_headers = has_headers;
+        self
+    }
+
+    /// Skips lines starting with `comment`
+    pub fn with_comment(mut self, comment: u8) -> Self {
+        self.comment = Some(comment);
+        self
+    }
+
+    /// Sets whether whitespace around headers and cells is trimmed
+    pub fn with_trim(mut self, trim: bool) -> Self {
+        self.trim = trim;
+        self
+    }
+
+    /// Reads cells equal to any of `values`, such as `""` or `NA`, as nulls. Inferred columns
+    /// are then nullable, and a null in a column that is not is a bad record.
+    pub fn with_null_values<S: Into<String>>(
+        mut self,
+        values: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.null_values = values.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn with_encoding(mut self, encoding: TextEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    pub(crate) fn is_null(&self, cell: &str) -> bool {
+        self.null_values.iter().any(|null| null == cell)
+    }
+
+    /// Writes `record` back as a line of CSV in this dialect, so reading it with these options
+    /// gives the record again. Cells holding the delimiter, the quote, the escape or a line
+    /// break are quoted, with quotes and escapes inside escaped.
+    pub(crate) fn write_record(&self, record: &csv::StringRecord) -> Vec<u8> {
+        let mut line = Vec::new();
+        for (i, cell) in record.iter().enumerate() {
+            if i > 0 {
+                line.push(self.delimiter);
+            }
+            let cell = cell.as_bytes();
+            let special = |b: &u8| {
+                [self.delimiter, self.quote, b'\r', b'\n'].contains(b) || Some(*b) == self.escape
+            };
+            // A lone empty cell would be a blank line, and a leading comment byte a comment
+            let quoted = cell.iter().any(special)
+                || (record.len() == 1 && cell.is_empty())
+                || (i == 0 && self.comment.is_some() && cell.first() == self.comment.as_ref());
+            if !quoted {
+                line.extend_from_slice(cell);
+                continue;
+            }
+            line.push(self.quote);
+            for &b in cell {
+                if b == self.quote || Some(b) == self.escape {
+                    line.push(self.escape.unwrap_or(self.quote));
+                }
+                line.push(b);
+            }
+            line.push(self.quote);
+        }
+        line.push(b'\n');
+        line
+    }
+
+    /// Opens a CSV reader on `source`, decompressing it if it is gzipped
+    pub(crate) fn reader<'r, R: Read + 'r>(
+        &self,
+        source: R,
+    ) -> std::io::Result<csv::Reader<Box<dyn Read + 'r>>> {
+        Ok(csv::ReaderBuilder::new()
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .escape(self.escape)
+            .double_quote(self.escape.is_none())
+            .has_headers(self.has_headers)
+            .flexible(true)
+            .comment(self.comment)
+            .trim(if self.trim {
+                csv::Trim::All
+            } else {
+                csv::Trim::None
+            })
+            .from_reader(decode(source, self.encoding)?))
+    }
+
+    /// Reads the records the schema is inferred from: the first one, then more until every
+    /// column has held a value other than a null
+    pub(crate) fn sample<R: Read>(
+        &self,
+        records: &mut csv::StringRecordsIntoIter<R>,
+    ) -> std::io::Result<Vec<csv::StringRecord>> {
+        let mut sample = Vec::new();
+        let mut untyped = Vec::new();
+        while sample.len() < INFER_RECORDS {
+            let Some(record) = records.next().transpose()? else {
+                break;
+            };
+            if sample.is_empty() {
+                untyped = (0..record.len()).collect();
+            }
+            untyped.retain(|&i| record.get(i).is_none_or(|cell| self.is_null(cell)));
+            sample.push(record);
+            if untyped.is_empty() {
+                break;
+            }
+        }
+        Ok(sample)
+    }
+
+    /// Infers the schema from the headers, if any, and the first value of every column in
+    /// `sample`. Columns without one are strings. Columns are nullable when null values are
+    /// set or `nullable` is.
+    pub(crate) fn infer_schema(
+        &self,
+        headers: Option<&csv::StringRecord>,
+        sample: &[csv::StringRecord],
+        nullable: bool,
+    ) -> Vec<PlankField> {
+        let width = headers.or(sample.first()).map_or(0, |r| r.len());
+        (0..width)
+            .map(|i| {
+                let name = headers
+                    .and_then(|h| h.get(i))
+                    .map_or_else(|| format!("column_{}", i + 1), str::to_string);
+                let field_type = sample
+                    .iter()
+                    .filter_map(|r| r.get(i))
+                    .find(|cell| !self.is_null(cell))
+                    .map_or(PlankType::Str, PlankType::infer_type);
+                if self.null_values.is_empty() && !nullable {
+                    PlankField::new(&name, field_type)
+                } else {
+                    PlankField::new(&name, field_type.nullable())
+                }
+            })
+            .collect()
+    }
+}
+
+impl IngestError {
+    pub fn line(&self) -> u64 {
+        match self {
+            IngestError::Cell { line, .. }
+            | IngestError::Width { line, .. }
+            | IngestError::Record { line, .. } => *line,
+        }
+    }
+}
+
+impl fmt::Display for IngestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IngestError::Cell {
+                line,
+                column,
+                value,
+                reason,
+            } => write!(
+                f,
+                "line {}, column {}: cannot read {:?}: {}",
+                line, column, value, reason
+            ),
+            IngestError::Width {
+                line,
+                cells,
+                columns,
+            } => write!(
+                f,
+                "line {}: record has {} cells but the schema has {} columns",
+                line, cells, columns
+            ),
+            IngestError::Record { line, reason } => write!(f, "line {}: {}", line, reason),
+        }
+    }
+}
+
+impl std::error::Error for IngestError {}
+
+impl From<IngestError> for std::io::Error {
+    fn from(error: IngestError) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, error)
+    }
+}
+
+impl BadRecords {
+    /// `header` goes ahead of the quarantined records
+    pub(crate) fn new(policy: BadRecordPolicy, header: Vec<u8>) -> Self {
+        BadRecords {
+            policy,
+            header,
+            quarantine: None,
+            report: IngestReport::default(),
+        }
+    }
+
+    /// Decides what is written in place of a cell of `field` that cannot be read: a null, or
+    /// nothing when its `record` is left out
+    pub(crate) fn cell(
+        &mut self,
+        error: IngestError,
+        field: &PlankField,
+        record: &[u8],
+    ) -> std::io::Result<Option<PlankData>> {
+        if self.policy == BadRecordPolicy::Null
+            && matches!(field.field_type(), PlankType::Nullable(_))
+        {
+            self.report.cells_nulled += 1;
+            return Ok(Some(PlankData::Null));
+        }
+        self.reject(error, record)?;
+        Ok(None)
+    }
+
+    /// Leaves out a record, given as the bytes it is quarantined as, or fails with `error` if
+    /// the policy does not allow it
+    pub(crate) fn reject(&mut self, error: IngestError, record: &[u8]) -> std::io::Result<()> {
+        match &self.policy {
+            BadRecordPolicy::Fail | BadRecordPolicy::Null => Err(error.into()),
+            BadRecordPolicy::Skip => {
+                self.report.rows_skipped += 1;
+                Ok(())
+            }
+            BadRecordPolicy::Quarantine(path) => {
+                let writer = match &mut self.quarantine {
+                    Some(writer) => writer,
+                    None => {
+                        let mut writer = BufWriter::new(File::create(path)?);
+                        writer.write_all(&self.header)?;
+                        self.quarantine.insert(writer)
+                    }
+                };
+                writer.write_all(record)?;
+                self.report.rows_quarantined += 1;
+                Ok(())
+            }
+        }
+    }
+
+    /// Flushes the quarantined records and returns the report
+    pub(crate) fn finish(mut self) -> std::io::Result<IngestReport> {
+        if let Some(writer) = &mut self.quarantine {
+            writer.flush()?;
+        }
+        Ok(self.report)
+    }
+}
+
+/// Infers the schema of NDJSON records: a column per key of any of the objects, in the order
+/// keys are first seen. Objects are structs, or maps when they hold more than 64 distinct
+/// keys. Every column and struct field is nullable, since any key may be missing,
+/// and keys holding values of different types are strings. Unless the objects are `complete`,
+/// all the records there are, integers are `Int64` so that later ones fit.
+pub(crate) fn infer_ndjson_schema<'a>(
+    objects: impl IntoIterator<Item = &'a serde_json::Map<String, serde_json::Value>>,
+    complete: bool,
+) -> Vec<PlankField> {
+    let columns = objects.into_iter().fold(Vec::new(), |columns, object| {
+        JsonType::merge_fields(columns, JsonType::of_object(object))
+    });
+    ndjson_columns(columns, complete)
+}
+
+/// Infers the schema of all the NDJSON records of `source`, leaving out lines that are not
+/// JSON objects
+pub(crate) fn scan_ndjson_schema<R: Read>(source: R) -> std::io::Result<Vec<PlankField>> {
+    let mut columns = Vec::new();
+    for line in BufReader::new(decode(source, TextEncoding::Utf8)?).lines() {
+        if let Ok(serde_json::Value::Object(object)) = serde_json::from_str(&line?) {
+            columns = JsonType::merge_fields(columns, JsonType::of_object(&object));
+        }
+    }
+    Ok(ndjson_columns(columns, true))
+}
+
+fn ndjson_columns(columns: Vec<(String, JsonType)>, complete: bool) -> Vec<PlankField> {
+    columns
+        .into_iter()
+        .map(|(name, t)| {
+            let t = if complete { t } else { t.widen() };
+            PlankField::new(&name, t.into_type().nullable())
+        })
+        .collect()
+}
+
+/// Wraps `source` to yield UTF-8 text, decompressed if it starts like a gzip stream and
+/// without a byte order mark
+pub(crate) fn decode<'r, R: Read + 'r>(
+    source: R,
+    encoding: TextEncoding,
+) -> std::io::Result<Box<dyn Read + 'r>> {
+    let mut source = BufReader::new(source);
+    let mut source: Box<dyn BufRead + 'r> = if source.fill_buf()?.starts_with(&GZIP_MAGIC) {
+        Box::new(BufReader::new(MultiGzDecoder::new(source)))
+    } else {
+        Box::new(source)
+    };
+
+    Ok(match encoding {
+        TextEncoding::Utf8 => {
+            if source.fill_buf()?.starts_with(&UTF8_BOM) {
+                source.consume(UTF8_BOM.len());
+            }
+            source
+        }
+        TextEncoding::Latin1 => Box::new(Latin1 {
+            inner: source,
+            pending: Vec::new(),
+        }),
+    })
+}
+
+/// Transcodes ISO-8859-1 to UTF-8
+struct Latin1<R> {
+    inner: R,
+    /// Encoded bytes that did not fit the last read
+    pending: Vec<u8>,
+}
+
+impl<R: BufRead> Read for Latin1<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pending.is_empty() {
+            let bytes = self.inner.fill_buf()?;
+            let mut char_buf = [0; 2];
+            for &b in bytes {
+                let c = char::from(b).encode_utf8(&mut char_buf);
+                self.pending.extend_from_slice(c.as_bytes());
+            }
+            let consumed = bytes.len();
+            self.inner.consume(consumed);
+        }
+        let n = self.pending.len().min(buf.len());
+        buf[..n].copy_from_slice(&self.pending[..n]);
+        self.pending.drain(..n);
+        Ok(n)
+    }
+}
@@ -0,0 +1,210 @@
+use std::ops::Range;
+
+use crate::file::rowgroup::column::{Column, PageSpan};
+use crate::file::stats;
+use crate::serde::{Deserialize, Serialize, read_u32, read_u64};
+use crate::types::{data::PlankData, types::PlankType};
+
+/// The column index and offset index of one leaf column chunk: where every page is, which
+/// rows it holds and the bounds of its values
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PageIndex {
+    pub pages: Vec<PageIndexEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PageIndexEntry {
+    /// Offset of the page header in the file
+    pub offset: u64,
+    /// Size of the page, header included
+    pub size: u32,
+    pub first_row: u64,
+    pub row_count: u32,
+    pub null_count: u64,
+    /// Smallest value, `None` when the page holds no values
+    pub min: Option<PlankData>,
+    pub max: Option<PlankData>,
+}
+
+impl PageIndex {
+    /// Indexes the pages of a chunk encoded from the primitive `values`, with page offsets
+    /// counted from `base`
+    pub(crate) fn new(values: &Column, spans: &[PageSpan], base: u64) -> std::io::Result<Self> {
+        let pages = spans
+            .iter()
+            .map(|span| {
+                let (min, max) = stats::bounds(values, span.values.clone())?;
+                Ok(PageIndexEntry {
+                    offset: base + span.bytes.start as u64,
+                    size: span.bytes.len() as u32,
+                    first_row: span.rows.start as u64,
+                    row_count: span.rows.len() as u32,
+                    null_count: (span.entries.len() - span.values.len()) as u64,
+                    min,
+                    max,
+                })
+            })
+            .collect::<std::io::Result<_>>()?;
+        Ok(PageIndex { pages })
+    }
+
+    /// Moves every page by `by` bytes, once the position of the chunk in the file is known
+    pub(crate) fn shift(&mut self, by: u64) {
+        self.pages.iter_mut().for_each(|page| page.offset += by);
+    }
+}
+
+impl PageIndexEntry {
+    pub fn rows(&self) -> Range<u64> {
+        self.first_row..self.first_row + self.row_count as u64
+    }
+}
+
+/// The rows in both sets of sorted, disjoint ranges
+pub(crate) fn intersect(a: &[Range<u64>], b: &[Range<u64>]) -> Vec<Range<u64>> {
+    let mut out = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        let start = a[i].start.max(b[j].start);
+        let end = a[i].end.min(b[j].end);
+        if start < end {
+            out.push(start..end);
+        }
+        if a[i].end < b[j].end {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    out
+}
+
+/// The rows in either set of ranges, sorted with touching ranges merged
+pub(crate) fn union(a: &[Range<u64>], b: &[Range<u64>]) -> Vec<Range<u64>> {
+    let mut all: Vec<_> = a
+        .iter()
+        .chain(b)
+        .filter(|r| !r.is_empty())
+        .cloned()
+        .collect();
+    all.sort_by_key(|r| r.start);
+    merge(all)
+}
+
+/// Merges sorted ranges that touch or overlap
+pub(crate) fn merge(ranges: impl IntoIterator<Item = Range<u64>>) -> Vec<Range<u64>> {
+    let mut out: Vec<Range<u64>> = Vec::new();
+    for range in ranges {
+        match out.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => out.push(range),
+        }
+    }
+    out
+}
+
+impl Serialize for PageIndex {
+    fn to_bytes(&self) -> std::io::Result<Vec<u8>> {
+        let mut v = Vec::new();
+        v.extend_from_slice(&(self.pages.len() as u32).to_le_bytes());
+        for page in &self.pages {
+            v.extend_from_slice(&page.offset.to_le_bytes());
+            v.extend_from_slice(&page.size.to_le_bytes());
+            v.extend_from_slice(&page.first_row.to_le_bytes());
+            v.extend_from_slice(&page.row_count.to_le_bytes());
+            v.extend_from_slice(&page.null_count.to_le_bytes());
+            match (&page.min, &page.max) {
+                (Some(min), Some(max)) => {
+                    v.push(1);
+                    v.extend_from_slice(&min.to_bytes()?);
+                    v.extend_from_slice(&max.to_bytes()?);
+                }
+                _ => v.push(0),
+            }
+        }
+        Ok(v)
+    }
+}
+
+impl<'a> Deserialize<'a> for PageIndex {
+    /// The leaf type the bounds are decoded as
+    type Schema = PlankType;
+    fn from_bytes(bytes: &[u8], schema: &'a Self::Schema) -> std::io::Result<(Self, usize)> {
+        let count = read_u32(bytes, 0)? as usize;
+        let mut pos = 4;
+        let mut pages = Vec::with_capacity(count.min(bytes.len() / 33));
+
+        for _ in 0..count {
+            let mut page = PageIndexEntry {
+                offset: read_u64(bytes, pos)?,
+                size: read_u32(bytes, pos + 8)?,
+                first_row: read_u64(bytes, pos + 12)?,
+                row_count: read_u32(bytes, pos + 20)?,
+                null_count: read_u64(bytes, pos + 24)?,
+                ..Default::default()
+            };
+            pos += 32;
+
+            match bytes.get(pos) {
+                Some(0) => pos += 1,
+                Some(1) => {
+                    pos += 1;
+                    let (min, size) = PlankData::from_bytes(&bytes[pos..], schema)?;
+                    pos += size;
+                    let (max, size) = PlankData::from_bytes(&bytes[pos..], schema)?;
+                    pos += size;
+                    page.min = Some(min);
+                    page.max = Some(max);
+                }
+                _ => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        "expected page index bounds",
+                    ));
+                }
+            }
+            pages.push(page);
+        }
+
+        Ok((PageIndex { pages }, pos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::codec::Codec;
+
+    #[test]
+    fn test_page_index_roundtrip_and_ranges() {
+        let values =
+            Column::from_values(&PlankType::Int64, (0..100).map(PlankData::Int64)).unwrap();
+        // 80 bytes per page, so ten values each
+        let (chunk, spans) = values.encode_leaf(&[], &[], 0, Codec::Zlib, 80).unwrap();
+        let index = PageIndex::new(&values, &spans, 100).unwrap();
+
+        assert_eq!(index.pages.len(), 10);
+        assert_eq!(index.pages[0].offset, 100);
+        assert_eq!(
+            index.pages.iter().map(|p| p.size as usize).sum::<usize>(),
+            chunk.len()
+        );
+        assert_eq!(index.pages[3].rows(), 30..40);
+        assert_eq!(index.pages[3].min, Some(PlankData::Int64(30)));
+        assert_eq!(index.pages[3].max, Some(PlankData::Int64(39)));
+
+        let bytes = index.to_bytes().unwrap();
+        let (decoded, size) = PageIndex::from_bytes(&bytes, &PlankType::Int64).unwrap();
+        assert_eq!(size, bytes.len());
+        assert_eq!(decoded, index);
+
+        assert_eq!(
+            intersect(&[0..10, 20..30], &[5..15, 18..25]),
+            vec![5..10, 20..25]
+        );
+        assert_eq!(
+            union(&[20..30, 40..50], &[0..10, 10..15]),
+            vec![0..15, 20..30, 40..50]
+        );
+    }
+}
@@ -0,0 +1,229 @@
+use std::cmp::Ordering;
+use std::ops::Range;
+
+use crate::file::page_index::{self, PageIndex};
+use crate::file::rowgroup::shred::Leaf;
+use crate::file::stats::ColumnStats;
+use crate::types::{data::PlankData, fields::PlankField, types::PlankType};
+
+/// A filter on top-level primitive columns, checked against the column chunk statistics to
+/// skip row groups that cannot hold a matching row, and against page indexes to skip pages
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Predicate {
+    Eq(String, PlankData),
+    Greater(String, PlankData),
+    GreaterEq(String, PlankData),
+    Less(String, PlankData),
+    LessEq(String, PlankData),
+    And(Vec<Predicate>),
+    Or(Vec<Predicate>),
+}
+
+impl Predicate {
+    /// Checks that every column exists, is a top-level primitive column and can be compared
+    /// with its value
+    pub(crate) fn validate(&self, schema: &[PlankField]) -> std::io::Result<()> {
+        let (column, value) = match self {
+            Predicate::And(predicates) | Predicate::Or(predicates) => {
+                return predicates.iter().try_for_each(|p| p.validate(schema));
+            }
+            Predicate::Eq(column, value)
+            | Predicate::Greater(column, value)
+            | Predicate::GreaterEq(column, value)
+            | Predicate::Less(column, value)
+            | Predicate::LessEq(column, value) => (column, value),
+        };
+
+        let field = schema
+            .iter()
+            .find(|f| f.field_name() == column)
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("column {} not found", column),
+                )
+            })?;
+
+        let comparable = matches!(
+            (field.field_type().non_null(), value),
+            (PlankType::Str, PlankData::Str(_))
+                | (PlankType::Bool, PlankData::Bool(_))
+                | (
+                    PlankType::Int32 | PlankType::Int64,
+                    PlankData::Int32(_) | PlankData::Int64(_)
+                )
+        );
+        if !comparable {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("column {} cannot be compared with {}", column, value),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether a row group with the given leaf statistics may hold a matching row. Only
+    /// returns false when the bounds rule every row out.
+    pub(crate) fn might_match(&self, leaves: &[Leaf], stats: &[ColumnStats]) -> bool {
+        let column = match self {
+            Predicate::And(predicates) => {
+                return predicates.iter().all(|p| p.might_match(leaves, stats));
+            }
+            Predicate::Or(predicates) => {
+                return predicates.iter().any(|p| p.might_match(leaves, stats));
+            }
+            Predicate::Eq(column, _)
+            | Predicate::Greater(column, _)
+            | Predicate::GreaterEq(column, _)
+            | Predicate::Less(column, _)
+            | Predicate::LessEq(column, _) => column,
+        };
+
+        match leaves
+            .iter()
+            .position(|leaf| leaf.path() == column)
+            .and_then(|i| stats.get(i))
+        {
+            Some(stats) => self.bounds_match(&stats.min, &stats.max),
+            None => true,
+        }
+    }
+
+    /// The rows out of `row_count` that may match, looking up the page index of a column
+    /// with `page_index`. Columns without an index keep every row.
+    pub(crate) fn row_ranges(
+        &self,
+        row_count: u64,
+        page_index: &mut dyn FnMut(&str) -> std::io::Result<Option<PageIndex>>,
+    ) -> std::io::Result<Vec<Range<u64>>> {
+        let column = match self {
+            Predicate::And(predicates) => {
+                let mut rows: Vec<_> = std::iter::once(0..row_count).collect();
+                for p in predicates {
+                    rows = page_index::intersect(&rows, &p.row_ranges(row_count, page_index)?);
+                }
+                return Ok(rows);
+            }
+            Predicate::Or(predicates) => {
+                let mut rows = Vec::new();
+                for p in predicates {
+                    rows = page_index::union(&rows, &p.row_ranges(row_count, page_index)?);
+                }
+                return Ok(rows);
+            }
+            Predicate::Eq(column, _)
+            | Predicate::Greater(column, _)
+            | Predicate::GreaterEq(column, _)
+            | Predicate::Less(column, _)
+            | Predicate::LessEq(column, _) => column,
+        };
+
+        Ok(match page_index(column)? {
+            Some(index) => page_index::merge(
+                index
+                    .pages
+                    .iter()
+                    .filter(|page| self.bounds_match(&page.min, &page.max))
+                    .map(|page| page.rows()),
+            ),
+            None => std::iter::once(0..row_count).collect(),
+        })
+    }
+
+    /// Whether a value between `min` and `max` may satisfy this comparison
+    fn bounds_match(&self, min: &Option<PlankData>, max: &Option<PlankData>) -> bool {
+        // Nothing to match without values
+        let (Some(min), Some(max)) = (min, max) else {
+            return false;
+        };
+        let value = match self {
+            Predicate::Eq(_, value)
+            | Predicate::Greater(_, value)
+            | Predicate::GreaterEq(_, value)
+            | Predicate::Less(_, value)
+            | Predicate::LessEq(_, value) => value,
+            Predicate::And(_) | Predicate::Or(_) => return true,
+        };
+        let (Some(min), Some(max)) = (compare(min, value), compare(max, value)) else {
+            return true;
+        };
+
+        match self {
+            Predicate::Eq(..) => min.is_le() && max.is_ge(),
+            Predicate::Greater(..) => max.is_gt(),
+            Predicate::GreaterEq(..) => max.is_ge(),
+            Predicate::Less(..) => min.is_lt(),
+            Predicate::LessEq(..) => min.is_le(),
+            Predicate::And(_) | Predicate::Or(_) => unreachable!(),
+        }
+    }
+}
+
+/// Orders two primitive values of the same kind, widening integers as needed
+pub(crate) fn compare(a: &PlankData, b: &PlankData) -> Option<Ordering> {
+    match (a, b) {
+        (PlankData::Str(a), PlankData::Str(b)) => Some(a.cmp(b)),
+        (PlankData::Bool(a), PlankData::Bool(b)) => Some(a.cmp(b)),
+        (PlankData::Int32(a), PlankData::Int32(b)) => Some(a.cmp(b)),
+        (PlankData::Int64(a), PlankData::Int64(b)) => Some(a.cmp(b)),
+        (PlankData::Int32(a), PlankData::Int64(b)) => Some((*a as i64).cmp(b)),
+        (PlankData::Int64(a), PlankData::Int32(b)) => Some(a.cmp(&(*b as i64))),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::rowgroup::shred;
+
+    #[test]
+    fn test_might_match_from_bounds() {
+        let schema = vec![
+            PlankField::new("ts", PlankType::Int64),
+            PlankField::new("country", PlankType::Str),
+        ];
+        let leaves = shred::leaves(&schema).unwrap();
+        let stats = vec![
+            ColumnStats {
+                min: Some(PlankData::Int64(100)),
+                max: Some(PlankData::Int64(200)),
+                ..Default::default()
+            },
+            ColumnStats {
+                min: Some(PlankData::Str("AT".to_string())),
+                max: Some(PlankData::Str("FR".to_string())),
+                ..Default::default()
+            },
+        ];
+        let de = Predicate::Eq("country".to_string(), PlankData::Str("DE".to_string()));
+        let us = Predicate::Eq("country".to_string(), PlankData::Str("US".to_string()));
+
+        assert!(de.might_match(&leaves, &stats));
+        assert!(!us.might_match(&leaves, &stats));
+        assert!(
+            Predicate::GreaterEq("ts".to_string(), PlankData::Int32(200))
+                .might_match(&leaves, &stats)
+        );
+        assert!(
+            !Predicate::Greater("ts".to_string(), PlankData::Int32(200))
+                .might_match(&leaves, &stats)
+        );
+        assert!(
+            !Predicate::Less("ts".to_string(), PlankData::Int64(100)).might_match(&leaves, &stats)
+        );
+        assert!(!Predicate::And(vec![de.clone(), us.clone()]).might_match(&leaves, &stats));
+        assert!(Predicate::Or(vec![us, de]).might_match(&leaves, &stats));
+
+        assert!(
+            Predicate::Eq("ts".to_string(), PlankData::Str("1".to_string()))
+                .validate(&schema)
+                .is_err()
+        );
+        assert!(
+            Predicate::Eq("id".to_string(), PlankData::Int32(1))
+                .validate(&schema)
+                .is_err()
+        );
+    }
+}
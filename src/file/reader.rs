@@ -1,18 +1,42 @@
-use itertools::Itertools;
-use std::collections::HashMap;
+use memmap2::Mmap;
+use serde::de::DeserializeOwned;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::fs::File;
-use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
+use std::marker::PhantomData;
+use std::ops::{Bound, Range};
 use std::path::Path;
 
-use crate::file::footer::{self, Footer};
-use crate::file::rowgroup::column::Column;
+use crate::file::bloom::{self, BloomFilter};
+use crate::file::config::WriterConfig;
+use crate::file::footer::{BlobLocation, Footer};
+use crate::file::format::{FormatVersion, Trailer};
+use crate::file::page_index::PageIndex;
+use crate::file::predicate::Predicate;
 use crate::file::rowgroup::RowGroup;
-use crate::serde::Deserialize;
-use crate::types::{data::PlankData, fields::PlankField, types::PlankType};
+use crate::file::rowgroup::column::Column;
+use crate::file::rowgroup::shred::{self, Leaf};
+use crate::file::sort::{self, SortColumn};
+use crate::file::stats::ColumnStats;
+use crate::file::view::{ColumnView, RecordBatchView};
+use crate::serde::{Deserialize, read_u32, read_u64};
+use crate::types::json;
+use crate::types::record::{self, PlankRecord};
+use crate::types::{data::PlankData, fields::PlankField};
 
-pub struct PlankReader {
-    file: BufReader<File>,
+/// Reads a plank file. `PlankReader<T>`, opened with `open_records`, also reads rows as
+/// records of `T`.
+pub struct PlankReader<T = ()> {
+    source: Source,
     footer: Footer,
+    leaves: Vec<Leaf>,
+    record: PhantomData<fn() -> T>,
+}
+
+enum Source {
+    Buffered(BufReader<File>),
+    Mapped(Mmap),
 }
 
 #[derive(Debug)]
@@ -22,47 +46,105 @@ pub struct RecordBatch {
     pub row_count: u32,
 }
 
-pub struct RowGroupIterator<'a> {
-    reader: &'a mut PlankReader,
-    offsets: Vec<u32>,
+pub struct RowGroupIterator<'a, T = ()> {
+    reader: &'a mut PlankReader<T>,
     index: usize,
 }
 
+/// Iterates over the row groups a predicate may match, skipping the ones ruled out by the
+/// footer statistics
+pub struct MatchingRowGroups<'a, T = ()> {
+    reader: &'a mut PlankReader<T>,
+    ids: std::vec::IntoIter<usize>,
+    pruned: usize,
+}
+
+/// Iterates over the rows of a file as values of `R`, records of `T` unless they are
+/// deserialized with `deserialize_rows`
+pub struct Records<'a, T, R = T> {
+    reader: &'a mut PlankReader<T>,
+    index: usize,
+    rows: Option<RowIterator>,
+    convert: fn(&[PlankField], Vec<PlankData>) -> std::io::Result<R>,
+}
+
 pub struct RowIterator {
     row_group: Option<RowGroup>,
     row: usize,
 }
 
 impl PlankReader {
-    fn parse_schema(bytes: &[u8]) -> std::io::Result<Vec<PlankField>> {
-        Footer::parse_schema(bytes)
+    pub fn open<P: AsRef<Path>>(file_path: P) -> std::io::Result<Self> {
+        let mut br = BufReader::new(File::open(file_path)?);
+        let footer = Self::read_footer(&mut br)?;
+        Self::from_source(Source::Buffered(br), footer)
     }
 
-    fn parse_offsets(bytes: &[u8]) -> std::io::Result<Vec<u32>> {
-        Footer::parse_offsets(bytes)
+    /// Opens the file through a read-only memory map.
+    ///
+    /// `read_row_group_view` then borrows uncompressed column chunks straight from the mapping.
+    /// The file must not be truncated or modified by anyone while the reader is alive.
+    pub fn open_mmap<P: AsRef<Path>>(file_path: P) -> std::io::Result<Self> {
+        let f = File::open(file_path)?;
+        // SAFETY: the mapping is read-only and callers are told not to modify the file while
+        // the reader is alive
+        let map = unsafe { Mmap::map(&f)? };
+        let footer = Self::read_footer(&mut Cursor::new(&map[..]))?;
+        Self::from_source(Source::Mapped(map), footer)
     }
+}
 
-    pub fn open<P: AsRef<Path>>(file_path: P) -> std::io::Result<Self> {
-        let mut f = File::open(file_path)?;
-        // Footer offset f;or u32
-        f.seek(SeekFrom::End(-4))?;
+impl<T: PlankRecord> PlankReader<T> {
+    /// Opens a file to read its rows as records of `T`, checking the schema of the file is
+    /// the one `T` derives
+    pub fn open_records<P: AsRef<Path>>(file_path: P) -> std::io::Result<Self> {
+        let reader = PlankReader::open(file_path)?;
+        record::check_schema(&T::schema(), reader.schema())?;
+        Self::from_source(reader.source, reader.footer)
+    }
 
-        let mut footer_offset = [0u8; 4];
-        f.read_exact(&mut footer_offset)?;
+    /// Iterates over every row of the file as a record, one row group at a time
+    pub fn records(&mut self) -> Records<'_, T> {
+        Records {
+            reader: self,
+            index: 0,
+            rows: None,
+            convert: |_, row| T::from_row(row),
+        }
+    }
+}
 
-        let footer_offset = u32::from_le_bytes(footer_offset);
-        // println!("{} reader", footer_offset);
+impl<T> PlankReader<T> {
+    fn from_source(source: Source, footer: Footer) -> std::io::Result<Self> {
+        Ok(Self {
+            leaves: shred::leaves(&footer.schema)?,
+            source,
+            footer,
+            record: PhantomData,
+        })
+    }
 
-        f.seek(SeekFrom::Start(footer_offset as u64))?;
+    /// Iterates over every row of the file deserialized into `D`, columns matched to its
+    /// fields by name. Nulls deserialize as `None`, structs as nested values.
+    pub fn deserialize_rows<D: DeserializeOwned>(&mut self) -> Records<'_, T, D> {
+        Records {
+            reader: self,
+            index: 0,
+            rows: None,
+            convert: json::from_row,
+        }
+    }
 
-        let mut br = BufReader::new(f);
+    fn read_footer<R: Read + Seek>(f: &mut R) -> std::io::Result<Footer> {
+        let trailer = Trailer::read(f)?;
+        f.seek(SeekFrom::Start(trailer.footer_offset))?;
 
         let mut footer_buf = Vec::new();
-        br.read_to_end(&mut footer_buf)?;
+        f.read_to_end(&mut footer_buf)?;
 
-        let footer = Footer::from_bytes(&footer_buf, &())?;
+        let (footer, _) = Footer::from_bytes(&footer_buf, &trailer.version)?;
 
-        Ok(Self { file: br, footer })
+        Ok(footer.with_features(trailer.features))
     }
 
     pub fn schema(&self) -> &[PlankField] {
@@ -73,31 +155,364 @@ impl PlankReader {
         &self.footer
     }
 
-    fn read_row_group_raw(&mut self, id: usize) -> std::io::Result<RowGroup> {
+    pub(crate) fn into_footer(self) -> Footer {
+        self.footer
+    }
+
+    /// The format version the file was written in
+    pub fn format_version(&self) -> FormatVersion {
+        self.footer.version()
+    }
+
+    /// The key/value pairs the writer stored in the footer
+    pub fn metadata(&self) -> &BTreeMap<String, Vec<u8>> {
+        self.footer.metadata()
+    }
+
+    /// The settings the file was written with
+    pub fn writer_config(&self) -> Option<&WriterConfig> {
+        self.footer.writer_config()
+    }
+
+    /// The number of the first row of row group `id` in the file, `None` when there is no such
+    /// row group
+    pub fn row_group_first_row(&self, id: usize) -> Option<u64> {
+        self.footer.first_rows.get(id).copied()
+    }
+
+    /// The id of the row group holding row `row` of the file, found from the first row of
+    /// every row group without reading any of them
+    pub fn row_group_of_row(&self, row: u64) -> Option<usize> {
+        let first_rows = &self.footer.first_rows;
+        if first_rows.is_empty() || row >= self.footer.row_count {
+            return None;
+        }
+        Some(sort::partition_point(first_rows.len(), |i| first_rows[i] <= row) - 1)
+    }
+
+    /// The leaf columns the schema is stored as, in storage order
+    pub fn leaves(&self) -> &[Leaf] {
+        &self.leaves
+    }
+
+    /// The statistics of every leaf column chunk of a row group, in the order of `leaves`
+    pub fn row_group_stats(&self, id: usize) -> Option<&[ColumnStats]> {
+        self.footer.stats.get(id).map(Vec::as_slice)
+    }
+
+    /// The statistics of the leaf at a dotted path in a row group
+    pub fn column_stats(&self, id: usize, path: &str) -> Option<&ColumnStats> {
+        let index = self.leaves.iter().position(|leaf| leaf.path() == path)?;
+        self.row_group_stats(id)?.get(index)
+    }
+
+    /// The ids of the row groups that may hold rows matching `predicate`
+    pub fn prune(&self, predicate: &Predicate) -> std::io::Result<Vec<usize>> {
+        predicate.validate(&self.footer.schema)?;
+
+        Ok((0..self.footer.row_group_count as usize)
+            .filter(|&id| {
+                self.row_group_stats(id)
+                    .is_none_or(|stats| predicate.might_match(&self.leaves, stats))
+            })
+            .collect())
+    }
+
+    /// Iterates over the row groups that may hold rows matching `predicate`. Rows inside them
+    /// are not filtered.
+    pub fn row_groups_matching(
+        &mut self,
+        predicate: &Predicate,
+    ) -> std::io::Result<MatchingRowGroups<'_, T>> {
+        let ids = self.prune(predicate)?;
+        let pruned = self.footer.row_group_count as usize - ids.len();
+
+        Ok(MatchingRowGroups {
+            reader: self,
+            ids: ids.into_iter(),
+            pruned,
+        })
+    }
+
+    /// The bloom filter of a column chunk, if the writer built one. `column` is a top-level
+    /// primitive column or the dotted path of a leaf.
+    pub fn read_bloom_filter(
+        &mut self,
+        id: usize,
+        column: &str,
+    ) -> std::io::Result<Option<BloomFilter>> {
+        self.row_group_offset(id)?;
+        let leaf = self.leaf_index(column)?;
+        let Some(location) = BlobLocation::find(&self.footer.bloom_filters, id, leaf) else {
+            return Ok(None);
+        };
+
+        let bytes = self
+            .source
+            .read_at(location.offset, location.size as usize)?;
+        Ok(Some(BloomFilter::from_bytes(&bytes, &())?.0))
+    }
+
+    /// Whether row group `id` may hold `value` in `column`. Rules the group out from the
+    /// column's min/max statistics first, then from its bloom filter if it has one. Fails for
+    /// a row group the file does not have.
+    pub fn might_contain(
+        &mut self,
+        id: usize,
+        column: &str,
+        value: &PlankData,
+    ) -> std::io::Result<bool> {
+        self.row_group_offset(id)?;
+        let leaf = &self.leaves[self.leaf_index(column)?];
+        let Some(value) = bloom::coerce(value, leaf.field().field_type()) else {
+            return Ok(false);
+        };
+
+        let predicate = Predicate::Eq(column.to_string(), value.clone());
+        if let Some(stats) = self.row_group_stats(id)
+            && !predicate.might_match(&self.leaves, stats)
+        {
+            return Ok(false);
+        }
+
+        match self.read_bloom_filter(id, column)? {
+            Some(filter) => filter.might_contain(&value),
+            None => Ok(true),
+        }
+    }
+
+    /// The ids of the row groups that may hold `value` in `column`
+    pub fn row_groups_containing(
+        &mut self,
+        column: &str,
+        value: &PlankData,
+    ) -> std::io::Result<Vec<usize>> {
+        let mut ids = Vec::new();
+        for id in 0..self.footer.row_group_count as usize {
+            if self.might_contain(id, column, value)? {
+                ids.push(id);
+            }
+        }
+        Ok(ids)
+    }
+
+    /// The columns rows were written sorted by, empty when the writer declared no order
+    pub fn sort_order(&self) -> &[SortColumn] {
+        &self.footer.sort_order.columns
+    }
+
+    /// The first row whose sort key starts with `key`
+    pub fn find_row(&mut self, key: &[PlankData]) -> std::io::Result<Option<Vec<PlankData>>> {
+        let rows = self.find_rows(Bound::Included(key), Bound::Included(key))?;
+        Ok(rows.into_iter().next())
+    }
+
+    /// The rows whose sort key lies between `lower` and `upper`, in file order. A bound may
+    /// be a prefix of the sort key. Row groups are binary-searched by the key range the
+    /// footer keeps for each, then rows inside the few that overlap, so nothing else is read.
+    pub fn find_rows(
+        &mut self,
+        lower: Bound<&[PlankData]>,
+        upper: Bound<&[PlankData]>,
+    ) -> std::io::Result<Vec<Vec<PlankData>>> {
+        let order = self.footer.sort_order.clone();
+        if order.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "file was not written with a sort order",
+            ));
+        }
+        for bound in [lower, upper] {
+            if let Bound::Included(key) | Bound::Excluded(key) = bound {
+                order.validate_key(key, &self.footer.schema)?;
+            }
+        }
+        let key_indexes = order.key_indexes(&self.footer.schema)?;
+
+        let before = |key: &[PlankData]| match lower {
+            Bound::Included(bound) => order.compare(key, bound).is_lt(),
+            Bound::Excluded(bound) => order.compare(key, bound).is_le(),
+            Bound::Unbounded => false,
+        };
+        let after = |key: &[PlankData]| match upper {
+            Bound::Included(bound) => order.compare(key, bound).is_gt(),
+            Bound::Excluded(bound) => order.compare(key, bound).is_ge(),
+            Bound::Unbounded => false,
+        };
+
+        let start = order.row_groups.partition_point(|(_, last)| before(last));
+        let end = order.row_groups.partition_point(|(first, _)| !after(first));
+
+        let mut rows = Vec::new();
+        for id in start..end.max(start) {
+            let rg = self.read_row_group_raw(id)?;
+            let key = |row| sort::key(&rg.columns, &key_indexes, row);
+            let row_count = rg.row_count as usize;
+            let first = sort::partition_point(row_count, |row| before(&key(row)));
+            let last = sort::partition_point(row_count, |row| !after(&key(row)));
+
+            for row in rg.into_iter().skip(first).take(last.saturating_sub(first)) {
+                rows.push(row?);
+            }
+        }
+        Ok(rows)
+    }
+
+    /// The page index of a column chunk, if the file has one. `column` is a top-level
+    /// primitive column or the dotted path of a leaf.
+    pub fn read_page_index(
+        &mut self,
+        id: usize,
+        column: &str,
+    ) -> std::io::Result<Option<PageIndex>> {
+        let leaf = self.leaf_index(column)?;
+        let Some(location) = BlobLocation::find(&self.footer.page_indexes, id, leaf) else {
+            return Ok(None);
+        };
+
+        let bytes = self
+            .source
+            .read_at(location.offset, location.size as usize)?;
+        let leaf_type = self.leaves[leaf].field().field_type();
+        Ok(Some(PageIndex::from_bytes(&bytes, leaf_type)?.0))
+    }
+
+    /// The rows of row group `id` that may match `predicate`, as sorted ranges counted from
+    /// the start of the row group. Pages whose bounds rule every row out are left out, so the
+    /// ranges follow page boundaries.
+    pub fn matching_row_ranges(
+        &mut self,
+        id: usize,
+        predicate: &Predicate,
+    ) -> std::io::Result<Vec<Range<u64>>> {
+        predicate.validate(&self.footer.schema)?;
+        if let Some(stats) = self.row_group_stats(id)
+            && !predicate.might_match(&self.leaves, stats)
+        {
+            return Ok(Vec::new());
+        }
+
+        let offset = self.row_group_offset(id)?;
+        let row_count = read_u32(&self.source.read_at(offset + 8, 8)?, 4)? as u64;
+        predicate.row_ranges(row_count, &mut |column| self.read_page_index(id, column))
+    }
+
+    /// Reads `column` for the given rows of row group `id`, decoding only the pages that
+    /// overlap them. Every run of adjacent pages comes back with the rows it holds, which may
+    /// reach past the requested ranges. The column must hold one value per row.
+    pub fn read_row_ranges(
+        &mut self,
+        id: usize,
+        column: &str,
+        ranges: &[Range<u64>],
+    ) -> std::io::Result<Vec<(Range<u64>, Column)>> {
+        let leaf = self.leaves[self.leaf_index(column)?].clone();
+        if leaf.max_repetition_level() > 0 || leaf.max_definition_level() > 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("column {} does not hold one value per row", column),
+            ));
+        }
+        let index = self.read_page_index(id, column)?.ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("no page index for column {} in row group {}", column, id),
+            )
+        })?;
+
+        // Adjacent pages are read together, as rows and byte ranges
+        let mut runs: Vec<(Range<u64>, Range<u64>)> = Vec::new();
+        for page in &index.pages {
+            let rows = page.rows();
+            if !ranges
+                .iter()
+                .any(|r| r.start < rows.end && rows.start < r.end)
+            {
+                continue;
+            }
+            let bytes = page.offset..page.offset + page.size as u64;
+            match runs.last_mut() {
+                Some((run_rows, run_bytes)) if run_bytes.end == bytes.start => {
+                    run_rows.end = rows.end;
+                    run_bytes.end = bytes.end;
+                }
+                _ => runs.push((rows, bytes)),
+            }
+        }
+
+        runs.into_iter()
+            .map(|(rows, bytes)| {
+                let chunk = self
+                    .source
+                    .read_at(bytes.start, (bytes.end - bytes.start) as usize)?;
+                let (_, _, values) = Column::decode_leaf(&chunk, leaf.field().field_type(), 0, 0)?;
+                Ok((rows, values))
+            })
+            .collect()
+    }
+
+    fn leaf_index(&self, path: &str) -> std::io::Result<usize> {
+        self.leaves
+            .iter()
+            .position(|leaf| leaf.path() == path)
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("leaf column {} not found", path),
+                )
+            })
+    }
+
+    fn row_group_offset(&self, id: usize) -> std::io::Result<u64> {
         let footer = &self.footer;
-        let rg_offsets = &footer.offsets;
 
-        if id as u32 >= footer.row_group_count {
+        if id as u64 >= footer.row_group_count {
             return Err(std::io::Error::new(
                 std::io::ErrorKind::NotFound,
                 "requested row group does not exist",
             ));
         }
 
-        let br = &mut self.file;
+        Ok(footer.offsets[id])
+    }
 
-        // Go to the beginning of the row group
-        br.seek(SeekFrom::Start(rg_offsets[id] as u64))?;
+    fn read_row_group_raw(&mut self, id: usize) -> std::io::Result<RowGroup> {
+        let offset = self.row_group_offset(id)?;
+        let buf = self.source.read_row_group(offset)?;
 
-        let mut buf = [0u8; 4];
-        br.read_exact(&mut buf)?;
+        let (rg, _) = RowGroup::from_bytes(&buf, &self.footer.schema)?;
+        Ok(rg)
+    }
 
-        let row_group_size = u32::from_le_bytes(buf);
+    /// Reads a row group without decoding its columns.
+    ///
+    /// On a reader from `open_mmap`, uncompressed column chunks and their string values are
+    /// borrowed from the mapping. Otherwise the chunks are copied out of the file first.
+    pub fn read_row_group_view(&mut self, id: usize) -> std::io::Result<RecordBatchView<'_>> {
+        let offset = self.row_group_offset(id)?;
+        let bytes = self.source.read_row_group(offset)?;
+        let schema = &self.footer.schema;
 
-        let mut buf = vec![0u8; row_group_size as usize];
-        br.read_exact(&mut buf)?;
+        let layout = RowGroup::layout(&bytes, self.leaves.len())?;
+
+        let columns = self
+            .leaves
+            .iter()
+            .zip(layout.columns)
+            .map(|(leaf, range)| {
+                let chunk = match &bytes {
+                    Cow::Borrowed(b) => Cow::Borrowed(&b[range]),
+                    Cow::Owned(b) => Cow::Owned(b[range].to_vec()),
+                };
+                ColumnView::new(leaf, chunk)
+            })
+            .collect();
 
-        RowGroup::from_bytes(&buf, &self.footer.schema)
+        Ok(RecordBatchView {
+            schema,
+            columns,
+            row_count: layout.row_count,
+        })
     }
 
     pub fn read_row_group(&mut self, id: usize) -> std::io::Result<RecordBatch> {
@@ -110,60 +525,179 @@ impl PlankReader {
         })
     }
 
+    /// Reads the named columns of a row group.
+    ///
+    /// A name is either a top-level field or a dotted path to a sub-field, like `user.id`. Only
+    /// the leaves below the named field are read and decoded. A sub-field under lists comes
+    /// back wrapped in those lists.
     pub fn read_row_group_columns(
         &mut self,
         id: usize,
         column_names: &[&str],
     ) -> std::io::Result<RecordBatch> {
-        let rg = self.read_row_group_raw(id)?;
-        let mut columns = rg.columns;
+        let mut taken = vec![false; self.leaves.len()];
+        let mut projected = Vec::with_capacity(column_names.len());
 
-        // let mut column_map = HashMap::new();
+        for &name in column_names {
+            let (field, range) = shred::project(&self.footer.schema, name)?;
+            if taken[range.clone()].iter().any(|&t| t) {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("data column {} requested more than once", name),
+                ));
+            }
+            taken[range.clone()].fill(true);
+            projected.push((field, range));
+        }
 
-        let mut column_by_name = self
-            .schema()
+        let wanted: Vec<usize> = projected
             .iter()
-            .enumerate()
-            .map(|(i, col)| (col.field_name().as_str(), i))
-            .collect::<HashMap<&str, usize>>();
+            .flat_map(|(_, range)| range.clone())
+            .collect();
+        let offset = self.row_group_offset(id)?;
+        let locations = self.column_chunk_locations(id, &wanted)?;
+        let (row_count, chunks) = self.source.read_column_chunks(offset, &locations)?;
 
-        let mut schema_by_name = self
-            .schema()
-            .iter()
-            .map(|col| (col.field_name().as_str(), col))
-            .collect::<HashMap<&str, &PlankField>>();
+        let mut chunks = chunks.iter().map(|chunk| chunk.as_ref());
+        let mut schema = Vec::with_capacity(projected.len());
+        let mut columns = Vec::with_capacity(projected.len());
+        for (field, range) in projected {
+            let count = range.len();
+            columns.push(RowGroup::read_column(
+                chunks.by_ref().take(count),
+                &self.leaves[range],
+                &field,
+                row_count,
+            )?);
+            schema.push(field);
+        }
 
         Ok(RecordBatch {
-            schema: column_names
-                .iter()
-                .map(|&item| {
-                    let schema = schema_by_name.get(item).ok_or_else(|| {
+            schema,
+            columns,
+            row_count,
+        })
+    }
+
+    /// Where the chunks of the given leaves in row group `id` are
+    fn column_chunk_locations(
+        &self,
+        id: usize,
+        leaves: &[usize],
+    ) -> std::io::Result<Vec<Range<u64>>> {
+        leaves
+            .iter()
+            .map(|&leaf| {
+                BlobLocation::find(&self.footer.column_chunks, id, leaf)
+                    .map(|chunk| chunk.offset..chunk.offset + chunk.size)
+                    .ok_or_else(|| {
                         std::io::Error::new(
-                            std::io::ErrorKind::NotFound,
-                            format!("schema column {} not found", item),
+                            std::io::ErrorKind::InvalidData,
+                            format!(
+                                "footer has no location for leaf {} of row group {}",
+                                leaf, id
+                            ),
                         )
-                    })?.to_owned();
-                    Ok(schema.clone())
+                    })
+            })
+            .collect()
+    }
+}
+
+impl Source {
+    fn read_at(&mut self, offset: u64, size: usize) -> std::io::Result<Cow<'_, [u8]>> {
+        match self {
+            Source::Buffered(br) => {
+                br.seek(SeekFrom::Start(offset))?;
+                let mut buf = vec![0u8; size];
+                br.read_exact(&mut buf)?;
+                Ok(Cow::Owned(buf))
+            }
+            Source::Mapped(map) => map
+                .get(offset as usize..offset as usize + size)
+                .map(Cow::Borrowed)
+                .ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        "read runs past the end of the file",
+                    )
+                }),
+        }
+    }
+
+    /// Reads the chunks at `locations` of the row group at `offset`, along with its row count
+    fn read_column_chunks(
+        &mut self,
+        offset: u64,
+        locations: &[Range<u64>],
+    ) -> std::io::Result<(u32, Vec<Cow<'_, [u8]>>)> {
+        let row_count = read_u32(&self.read_at(offset + 8, 8)?, 4)?;
+        Ok((row_count, self.read_ranges(locations)?))
+    }
+
+    /// Returns the bytes of every range, in order
+    fn read_ranges(&mut self, ranges: &[Range<u64>]) -> std::io::Result<Vec<Cow<'_, [u8]>>> {
+        match self {
+            Source::Buffered(br) => ranges
+                .iter()
+                .map(|range| {
+                    br.seek(SeekFrom::Start(range.start))?;
+                    let mut buf = vec![0u8; (range.end - range.start) as usize];
+                    br.read_exact(&mut buf)?;
+                    Ok(Cow::Owned(buf))
                 })
-                .collect::<std::io::Result<_>>()?,
-            columns: column_names
+                .collect(),
+            Source::Mapped(map) => ranges
                 .iter()
-                .map(|&name| {
-                    let id = column_by_name.get(name).ok_or_else(|| {
+                .map(|range| {
+                    map.get(range.start as usize..range.end as usize)
+                        .map(Cow::Borrowed)
+                        .ok_or_else(|| {
+                            std::io::Error::new(
+                                std::io::ErrorKind::UnexpectedEof,
+                                "read runs past the end of the file",
+                            )
+                        })
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns the bytes of the length-prefixed row group starting at `offset`
+    fn read_row_group(&mut self, offset: u64) -> std::io::Result<Cow<'_, [u8]>> {
+        match self {
+            Source::Buffered(br) => {
+                // Go to the beginning of the row group
+                br.seek(SeekFrom::Start(offset))?;
+
+                let mut buf = [0u8; 8];
+                br.read_exact(&mut buf)?;
+
+                let row_group_size = u64::from_le_bytes(buf);
+
+                let mut buf = vec![0u8; row_group_size as usize];
+                br.read_exact(&mut buf)?;
+
+                Ok(Cow::Owned(buf))
+            }
+            Source::Mapped(map) => {
+                let start = offset as usize;
+                let size = read_u64(map, start)? as usize;
+
+                map.get(start + 8..start + 8 + size)
+                    .map(Cow::Borrowed)
+                    .ok_or_else(|| {
                         std::io::Error::new(
-                            std::io::ErrorKind::NotFound,
-                            format!("data column {} not found", name),
+                            std::io::ErrorKind::UnexpectedEof,
+                            "row group runs past the end of the file",
                         )
-                    })?;
-                    Ok(std::mem::replace(&mut columns[*id], Column::default()))
-                })
-                .collect::<std::io::Result<_>>()?,
-            row_count: rg.row_count,
-        })
+                    })
+            }
+        }
     }
 }
 
-impl<'a> Iterator for RowGroupIterator<'a> {
+impl<T> Iterator for RowGroupIterator<'_, T> {
     type Item = std::io::Result<RowGroup>;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -173,35 +707,78 @@ impl<'a> Iterator for RowGroupIterator<'a> {
     }
 }
 
+impl<T> MatchingRowGroups<'_, T> {
+    /// The number of row groups skipped because they cannot match
+    pub fn pruned(&self) -> usize {
+        self.pruned
+    }
+}
+
+impl<T> Iterator for MatchingRowGroups<'_, T> {
+    type Item = std::io::Result<RowGroup>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.ids.next()?;
+        Some(self.reader.read_row_group_raw(id))
+    }
+}
+
+impl<T, R> Iterator for Records<'_, T, R> {
+    type Item = std::io::Result<R>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(row) = self.rows.as_mut().and_then(Iterator::next) {
+                return Some(row.and_then(|row| (self.convert)(&self.reader.footer.schema, row)));
+            }
+            if self.index >= self.reader.footer.row_group_count as usize {
+                return None;
+            }
+            let row_group = self.reader.read_row_group_raw(self.index);
+            self.index += 1;
+            match row_group {
+                Ok(row_group) => self.rows = Some(row_group.into_iter()),
+                Err(e) => {
+                    self.index = usize::MAX;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
 impl Iterator for RowIterator {
     type Item = std::io::Result<Vec<PlankData>>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let columns = &self.row_group.as_ref()?.columns;
+        let row_group = self.row_group.as_ref()?;
 
-        if self.row >= columns[0].records.len() {
+        if self.row >= row_group.row_count as usize {
             return None;
         }
 
-        let row: Vec<PlankData> = columns
+        let row = row_group
+            .columns
             .iter()
-            .map(|col| col.records[self.row].clone())
-            .collect();
+            .map(|col| {
+                col.get(self.row).ok_or_else(|| {
+                    std::io::Error::new(std::io::ErrorKind::InvalidData, "column is too short")
+                })
+            })
+            .collect::<std::io::Result<Vec<PlankData>>>();
 
         self.row += 1;
-        Some(Ok(row))
+        Some(row)
     }
 }
 
-impl<'a> IntoIterator for &'a mut PlankReader {
+impl<'a, T> IntoIterator for &'a mut PlankReader<T> {
     type Item = std::io::Result<RowGroup>;
-    type IntoIter = RowGroupIterator<'a>;
+    type IntoIter = RowGroupIterator<'a, T>;
 
     fn into_iter(self) -> Self::IntoIter {
-        let offsets = self.footer.offsets.clone();
         RowGroupIterator {
             reader: self,
-            offsets,
             index: 0,
         }
     }
@@ -218,3 +795,366 @@ impl IntoIterator for RowGroup {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::codec::Codec;
+    use crate::file::config::WriterOptions;
+    use crate::file::format::{FORMAT_VERSION, FeatureFlags};
+    use crate::file::view::ValueRef;
+    use crate::file::writer::PlankWriter;
+    use std::path::PathBuf;
+
+    /// Writes a file of 25 rows in row groups of 10 to a directory of its own, which the test
+    /// removes when done
+    fn write_csv_fixture(name: &str, codec: Codec) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("plank-reader-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let csv_path = dir.join(format!("{}.csv", name));
+        let plank_path = dir.join(format!("{}.plank", name));
+
+        let mut csv = String::from("id,name\n");
+        for i in 0..25 {
+            csv.push_str(&format!("{},name-{}\n", i, i));
+        }
+        std::fs::write(&csv_path, csv).unwrap();
+
+        let mut f = PlankWriter::new(&plank_path)
+            .unwrap()
+            .with_codec(codec)
+            .with_options(WriterOptions::default().with_row_group_rows(10));
+        f.write_from_csv(&csv_path).unwrap();
+
+        plank_path
+    }
+
+    #[test]
+    fn test_mmap_view_borrows_uncompressed_strings() {
+        let path = write_csv_fixture("mmap_uncompressed", Codec::Uncompressed);
+        let mut reader = PlankReader::open_mmap(&path).unwrap();
+
+        let map_range = match &reader.source {
+            Source::Mapped(map) => map.as_ptr_range(),
+            Source::Buffered(_) => unreachable!(),
+        };
+
+        let view = reader.read_row_group_view(1).unwrap();
+        let names = view.column("name").unwrap();
+
+        assert!(names.is_borrowed().unwrap());
+        let values = names
+            .values()
+            .unwrap()
+            .collect::<std::io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(values.len(), 10);
+
+        for (i, value) in values.iter().enumerate() {
+            match value {
+                ValueRef::Str(s) => {
+                    assert_eq!(*s, format!("name-{}", 10 + i));
+                    assert!(map_range.contains(&s.as_ptr()));
+                }
+                _ => panic!("expected a string"),
+            }
+        }
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_view_matches_decoded_row_group() {
+        let path = write_csv_fixture("view_compressed", Codec::Zlib);
+
+        let mut mapped = PlankReader::open_mmap(&path).unwrap();
+        let mut buffered = PlankReader::open(&path).unwrap();
+
+        let batch = buffered.read_row_group(2).unwrap();
+        let view = mapped.read_row_group_view(2).unwrap();
+
+        assert_eq!(view.row_count, batch.row_count);
+        for (column, expected) in view.columns.iter().zip(&batch.columns) {
+            assert!(!column.is_borrowed().unwrap());
+            let values = column
+                .values()
+                .unwrap()
+                .map(|v| v.unwrap().to_data())
+                .collect::<Vec<_>>();
+            assert_eq!(values, expected.iter().collect::<Vec<_>>());
+        }
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_column_stats_from_footer() {
+        let path = write_csv_fixture("stats", Codec::Zlib);
+        let reader = PlankReader::open(&path).unwrap();
+
+        let ids = reader.column_stats(1, "id").unwrap();
+        assert_eq!(ids.min, Some(PlankData::Int32(10)));
+        assert_eq!(ids.max, Some(PlankData::Int32(19)));
+        assert_eq!(ids.null_count, 0);
+        assert_eq!(ids.distinct_count, 10);
+        assert_eq!(ids.decoded_size, 40);
+
+        let names = reader.column_stats(2, "name").unwrap();
+        assert_eq!(names.min, Some(PlankData::Str("name-20".to_string())));
+        assert_eq!(names.max, Some(PlankData::Str("name-24".to_string())));
+        assert!(reader.column_stats(3, "id").is_none());
+
+        let config = reader.writer_config().unwrap();
+        assert_eq!(config.row_group_rows, 10);
+        assert_eq!(config.codec, Codec::Zlib);
+        assert_eq!(reader.row_group_first_row(2), Some(20));
+        assert_eq!(reader.row_group_of_row(0), Some(0));
+        assert_eq!(reader.row_group_of_row(19), Some(1));
+        assert_eq!(reader.row_group_of_row(24), Some(2));
+        assert_eq!(reader.row_group_of_row(25), None);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_row_groups_matching_prunes_by_stats() {
+        let path = write_csv_fixture("prune", Codec::Zlib);
+        let mut reader = PlankReader::open(&path).unwrap();
+
+        let predicate = Predicate::And(vec![
+            Predicate::GreaterEq("id".to_string(), PlankData::Int32(12)),
+            Predicate::Less("id".to_string(), PlankData::Int64(20)),
+        ]);
+        let mut groups = reader.row_groups_matching(&predicate).unwrap();
+
+        assert_eq!(groups.pruned(), 2);
+        let rg = groups.next().unwrap().unwrap();
+        assert_eq!(rg.id, 1);
+        assert!(groups.next().is_none());
+
+        let predicate = Predicate::Eq("name".to_string(), PlankData::Str("zzz".to_string()));
+        assert_eq!(reader.prune(&predicate).unwrap(), Vec::<usize>::new());
+        assert!(
+            reader
+                .prune(&Predicate::Eq("missing".to_string(), PlankData::Int32(1)))
+                .is_err()
+        );
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_bloom_filter_lookup() {
+        let path = write_csv_fixture("bloom", Codec::Zlib);
+        PlankWriter::new(&path)
+            .unwrap()
+            .with_options(WriterOptions::default().with_row_group_rows(10))
+            .with_bloom_filter("name", bloom::DEFAULT_FPP)
+            .with_metadata("origin", "fixture")
+            .write_from_csv(path.with_extension("csv"))
+            .unwrap();
+        let mut reader = PlankReader::open(&path).unwrap();
+
+        assert_eq!(reader.format_version(), FORMAT_VERSION);
+        let features = reader.footer().features();
+        assert!(features.contains(FeatureFlags::BLOOM_FILTERS));
+        assert!(!features.contains(FeatureFlags::SORT_ORDER));
+        assert_eq!(reader.metadata()["origin"], b"fixture");
+        // Bloom filters are written right after their row group
+        let footer = reader.footer();
+        assert!(footer.bloom_filters[0].offset < footer.offsets[1]);
+        // The CSV it was written from has no magic bytes or footer
+        assert!(PlankReader::open(path.with_extension("csv")).is_err());
+
+        assert!(reader.read_bloom_filter(0, "name").unwrap().is_some());
+        assert!(reader.read_bloom_filter(0, "id").unwrap().is_none());
+
+        let name = PlankData::Str("name-13".to_string());
+        assert_eq!(reader.row_groups_containing("name", &name).unwrap(), [1]);
+        // Inside the bounds of the first two row groups, so only the filter rules them out
+        let missing = PlankData::Str("name-13x".to_string());
+        assert!(!reader.might_contain(0, "name", &missing).unwrap());
+        assert!(!reader.might_contain(1, "name", &missing).unwrap());
+
+        // Without a filter the statistics still narrow the lookup down
+        assert_eq!(
+            reader
+                .row_groups_containing("id", &PlankData::Int64(21))
+                .unwrap(),
+            [2]
+        );
+        assert!(reader.might_contain(0, "missing", &name).is_err());
+
+        // Row groups past the last one are not found rather than taken to match
+        let count = reader.footer().row_group_count() as usize;
+        for id in [count, usize::MAX] {
+            let err = reader.might_contain(id, "name", &name).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+            assert!(reader.read_bloom_filter(id, "name").is_err());
+        }
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_read_matching_pages_only() {
+        let path = write_csv_fixture("pages", Codec::Zlib);
+        // Two ids to a page
+        PlankWriter::new(&path)
+            .unwrap()
+            .with_options(WriterOptions::default().with_row_group_rows(10))
+            .with_page_size(8)
+            .write_from_csv(path.with_extension("csv"))
+            .unwrap();
+        let mut reader = PlankReader::open_mmap(&path).unwrap();
+
+        let index = reader.read_page_index(1, "id").unwrap().unwrap();
+        assert_eq!(index.pages.len(), 5);
+        assert_eq!(index.pages[1].rows(), 2..4);
+        assert_eq!(index.pages[1].min, Some(PlankData::Int32(12)));
+
+        let predicate = Predicate::Or(vec![
+            Predicate::And(vec![
+                Predicate::GreaterEq("id".to_string(), PlankData::Int32(13)),
+                Predicate::Less("id".to_string(), PlankData::Int32(16)),
+            ]),
+            Predicate::Eq("name".to_string(), PlankData::Str("name-18".to_string())),
+        ]);
+        let ranges = reader.matching_row_ranges(1, &predicate).unwrap();
+        assert_eq!(ranges, vec![2..6, 8..9]);
+        assert!(
+            reader
+                .matching_row_ranges(0, &predicate)
+                .unwrap()
+                .is_empty()
+        );
+
+        let runs = reader.read_row_ranges(1, "id", &ranges).unwrap();
+        let ids: Vec<_> = runs
+            .iter()
+            .map(|(rows, values)| (rows.clone(), values.iter().collect::<Vec<_>>()))
+            .collect();
+        assert_eq!(
+            ids,
+            vec![
+                (2..6, (12..16).map(PlankData::Int32).collect()),
+                (8..10, vec![PlankData::Int32(18), PlankData::Int32(19)]),
+            ]
+        );
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_find_rows_by_sort_key() {
+        let path = write_csv_fixture("sorted", Codec::Zlib);
+        let csv = path.with_extension("csv");
+        PlankWriter::new(&path)
+            .unwrap()
+            .with_options(WriterOptions::default().with_row_group_rows(10))
+            .with_sort_order(vec![SortColumn::asc("id")])
+            .write_from_csv(&csv)
+            .unwrap();
+        let mut reader = PlankReader::open(&path).unwrap();
+
+        assert_eq!(reader.sort_order(), [SortColumn::asc("id")]);
+        assert_eq!(
+            reader.find_row(&[PlankData::Int64(13)]).unwrap(),
+            Some(vec![
+                PlankData::Int32(13),
+                PlankData::Str("name-13".to_string())
+            ])
+        );
+        assert_eq!(reader.find_row(&[PlankData::Int32(99)]).unwrap(), None);
+
+        let lower = [PlankData::Int32(8)];
+        let upper = [PlankData::Int32(21)];
+        let ids: Vec<_> = reader
+            .find_rows(Bound::Excluded(&lower), Bound::Included(&upper))
+            .unwrap()
+            .into_iter()
+            .map(|row| row[0].clone())
+            .collect();
+        assert_eq!(ids, (9..22).map(PlankData::Int32).collect::<Vec<_>>());
+
+        // Names are not in lexicographic order past name-9
+        let err = PlankWriter::new(&path)
+            .unwrap()
+            .with_options(WriterOptions::default().with_row_group_rows(10))
+            .with_sort_order(vec![SortColumn::asc("name")])
+            .write_from_csv(&csv)
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_projection_reads_only_requested_chunks() {
+        let path = write_csv_fixture("projection", Codec::Zlib);
+        let expected = PlankReader::open(&path).unwrap().read_row_group(1).unwrap();
+
+        // Break the page header of the id chunk, which a projection of name must not touch
+        let id_chunk = PlankReader::open(&path).unwrap().footer.column_chunks[2];
+        assert_eq!((id_chunk.row_group, id_chunk.leaf), (1, 0));
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[id_chunk.offset as usize + 4] = 0xff;
+        std::fs::write(&path, bytes).unwrap();
+
+        for mut reader in [
+            PlankReader::open(&path).unwrap(),
+            PlankReader::open_mmap(&path).unwrap(),
+        ] {
+            let batch = reader.read_row_group_columns(1, &["name"]).unwrap();
+            assert_eq!(batch.row_count, 10);
+            assert_eq!(batch.columns[0], expected.columns[1]);
+            assert!(reader.read_row_group(1).is_err());
+        }
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_read_nested_sub_field() {
+        let dir = std::env::temp_dir().join(format!("plank-reader-nested-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let csv_path = dir.join("nested.csv");
+        let plank_path = dir.join("nested.plank");
+
+        let mut csv = String::from("id,profile\n");
+        for i in 0..12 {
+            let tags = (0..(i + 1) % 3)
+                .map(|t| format!(r#"""t{}"""#, t))
+                .collect::<Vec<_>>();
+            csv.push_str(&format!(
+                r#"{},"{{""age"": {}, ""tags"": [{}]}}""#,
+                i,
+                i,
+                tags.join(", ")
+            ));
+            csv.push('\n');
+        }
+        std::fs::write(&csv_path, csv).unwrap();
+        PlankWriter::new(&plank_path)
+            .unwrap()
+            .with_options(WriterOptions::default().with_row_group_rows(10))
+            .write_from_csv(&csv_path)
+            .unwrap();
+
+        let mut reader = PlankReader::open(&plank_path).unwrap();
+        let paths = reader.leaves().iter().map(|l| l.path()).collect::<Vec<_>>();
+        assert_eq!(paths, ["id", "profile.age", "profile.tags"]);
+
+        let full = reader.read_row_group(1).unwrap();
+        let batch = reader
+            .read_row_group_columns(1, &["profile.tags", "id"])
+            .unwrap();
+
+        assert_eq!(batch.schema[0].field_name(), "profile.tags");
+        assert_eq!(batch.columns[1], full.columns[0]);
+        for (row, tags) in batch.columns[0].iter().enumerate() {
+            let profile = full.columns[1].get(row).unwrap();
+            assert_eq!(profile.get(1), Some(&tags));
+        }
+        assert!(
+            reader
+                .read_row_group_columns(1, &["profile", "profile.age"])
+                .is_err()
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,15 +1,44 @@
 pub mod column;
+pub mod shred;
 
-use crate::serde;
+use crate::file::bloom::BloomFilter;
+use crate::file::codec::Codec;
+use crate::file::page_index::PageIndex;
+use crate::file::stats::ColumnStats;
+use crate::serde::{self, read_u32, read_u64};
 use crate::types::fields::PlankField;
-use column::Column;
-use std::io::{BufRead, BufReader, Cursor, Read, Seek, SeekFrom};
+use column::{Column, PageSpan, page::DEFAULT_PAGE_SIZE};
+use shred::Leaf;
+use std::ops::Range;
 
 #[derive(Debug, Clone)]
 pub struct RowGroup {
     pub(crate) id: u32,
     pub(crate) columns: Vec<Column>,
     pub(crate) row_count: u32,
+    pub(crate) codec: Codec,
+    pub(crate) page_size: usize,
+    /// Leaves to build bloom filters for, with their false positive rate
+    pub(crate) bloom_filters: Vec<(usize, f64)>,
+}
+
+/// A row group encoded for writing, with what the footer keeps about it
+pub(crate) struct EncodedRowGroup {
+    pub(crate) bytes: Vec<u8>,
+    pub(crate) stats: Vec<ColumnStats>,
+    /// Filters by leaf index
+    pub(crate) bloom_filters: Vec<(usize, BloomFilter)>,
+    /// One per leaf, with page offsets counted from the start of `bytes`
+    pub(crate) page_indexes: Vec<PageIndex>,
+    /// Where the chunk of every leaf is inside `bytes`, after its length
+    pub(crate) chunks: Vec<Range<u64>>,
+}
+
+/// Where each column chunk sits inside an encoded row group
+pub(crate) struct RowGroupLayout {
+    pub(crate) id: u32,
+    pub(crate) row_count: u32,
+    pub(crate) columns: Vec<Range<usize>>,
 }
 
 impl RowGroup {
@@ -18,67 +47,176 @@ impl RowGroup {
             id,
             columns,
             row_count,
+            codec: Codec::default(),
+            page_size: DEFAULT_PAGE_SIZE,
+            bloom_filters: Vec::new(),
         }
     }
-}
-
-impl serde::Serialize for RowGroup {
-    fn to_bytes(&self) -> std::io::Result<Vec<u8>> {
-        let mut v = Vec::new();
-        v.extend_from_slice(&self.id.to_le_bytes());
-        v.extend_from_slice(&self.row_count.to_le_bytes());
 
-        for col in &self.columns {
-            let column_bytes = col.to_bytes()?;
-            v.extend_from_slice(&(column_bytes.len() as u32).to_le_bytes());
-            v.extend_from_slice(&column_bytes);
-        }
-
-        Ok(v)
+    pub fn with_codec(mut self, codec: Codec) -> Self {
+        self.codec = codec;
+        self
     }
-}
 
-impl<'a> serde::Deserialize<'a> for RowGroup {
-    type Schema = Vec<PlankField>;
-    fn from_bytes(bytes: &[u8], schema: &'a Self::Schema) -> std::io::Result<Self> {
-        let mut br = BufReader::new(Cursor::new(bytes));
-        let mut columns = Vec::new();
+    /// Sets the target byte size of the pages column chunks are split into
+    pub fn with_page_size(mut self, page_size: usize) -> Self {
+        self.page_size = page_size;
+        self
+    }
 
-        let id =
-            u32::from_le_bytes(bytes[..4].try_into().map_err(|_| {
-                std::io::Error::new(std::io::ErrorKind::InvalidData, "expected u32")
-            })?);
+    /// Builds a bloom filter for each of the given leaves, by leaf index, with its false
+    /// positive rate
+    pub fn with_bloom_filters(mut self, bloom_filters: Vec<(usize, f64)>) -> Self {
+        self.bloom_filters = bloom_filters;
+        self
+    }
 
-        let row_count =
-            u32::from_le_bytes(bytes[4..8].try_into().map_err(|_| {
-                std::io::Error::new(std::io::ErrorKind::InvalidData, "expected u32")
-            })?);
+    /// Reads the row group header and the column chunk boundaries without decoding any column
+    pub(crate) fn layout(bytes: &[u8], column_count: usize) -> std::io::Result<RowGroupLayout> {
+        let id = read_u32(bytes, 0)?;
+        let row_count = read_u32(bytes, 4)?;
 
         let mut pos = 8;
-        let mut schema_id = 0;
-
-        while pos + 4 < bytes.len() {
-            let size = u32::from_le_bytes(bytes[pos..pos + 4].try_into().map_err(|_| {
-                std::io::Error::new(std::io::ErrorKind::InvalidData, "expected u32")
-            })?) as usize;
-
-            pos += 4;
+        let mut columns = Vec::with_capacity(column_count);
 
-            columns.push(Column::from_bytes(
-                &bytes[pos..pos + size],
-                &schema[schema_id],
-            )?);
-            schema_id += 1;
+        for i in 0..column_count {
+            let size = read_u64(bytes, pos)? as usize;
+            pos += 8;
 
+            if pos + size > bytes.len() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("column {} is truncated", i),
+                ));
+            }
+            columns.push(pos..pos + size);
             pos += size;
         }
 
-        Ok(RowGroup {
+        Ok(RowGroupLayout {
             id,
-            columns,
             row_count,
+            columns,
         })
     }
+
+    /// Encodes the row group along with the statistics and page index of every leaf column
+    /// chunk and the requested bloom filters
+    pub(crate) fn encode(&self) -> std::io::Result<EncodedRowGroup> {
+        let mut v = Vec::new();
+        v.extend_from_slice(&self.id.to_le_bytes());
+        v.extend_from_slice(&self.row_count.to_le_bytes());
+
+        let mut stats = Vec::new();
+        let mut bloom_filters = Vec::new();
+        let mut page_indexes = Vec::new();
+        let mut chunks = Vec::new();
+        let mut write_chunk =
+            |values: &Column, entries: usize, chunk: Vec<u8>, spans: Vec<PageSpan>| {
+                let leaf = stats.len();
+                let leaf_stats = ColumnStats::collect(values, entries, &chunk)?;
+                if let Some(&(_, fpp)) = self.bloom_filters.iter().find(|(i, _)| *i == leaf) {
+                    let filter = BloomFilter::from_column(values, leaf_stats.distinct_count, fpp);
+                    bloom_filters.push((leaf, filter));
+                }
+                stats.push(leaf_stats);
+                v.extend_from_slice(&(chunk.len() as u64).to_le_bytes());
+                page_indexes.push(PageIndex::new(values, &spans, v.len() as u64)?);
+                chunks.push(v.len() as u64..(v.len() + chunk.len()) as u64);
+                v.extend_from_slice(&chunk);
+                Ok::<_, std::io::Error>(())
+            };
+
+        for col in &self.columns {
+            match col {
+                Column::Struct { .. } | Column::List { .. } | Column::Nullable { .. } => {
+                    for leaf in shred::shred(col)? {
+                        let (chunk, spans) = leaf.values.encode_leaf(
+                            &leaf.rep,
+                            &leaf.def,
+                            leaf.max_def,
+                            self.codec,
+                            self.page_size,
+                        )?;
+                        let entries = leaf.def.len().max(leaf.values.len());
+                        write_chunk(&leaf.values, entries, chunk, spans)?;
+                    }
+                }
+                _ => {
+                    let (chunk, spans) =
+                        col.encode_leaf(&[], &[], 0, self.codec, self.page_size)?;
+                    write_chunk(col, col.len(), chunk, spans)?;
+                }
+            }
+        }
+
+        Ok(EncodedRowGroup {
+            bytes: v,
+            stats,
+            bloom_filters,
+            page_indexes,
+            chunks,
+        })
+    }
+
+    /// Decodes the chunks of `leaves`, the leaves below `field`, and assembles them into a
+    /// column of `field`'s type
+    pub(crate) fn read_column<'c>(
+        chunks: impl IntoIterator<Item = &'c [u8]>,
+        leaves: &[Leaf],
+        field: &PlankField,
+        row_count: u32,
+    ) -> std::io::Result<Column> {
+        let data = leaves
+            .iter()
+            .zip(chunks)
+            .map(|(leaf, chunk)| leaf.decode(chunk))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        shred::assemble(field.field_type(), data, row_count as usize)
+    }
+}
+
+impl serde::Serialize for RowGroup {
+    fn to_bytes(&self) -> std::io::Result<Vec<u8>> {
+        Ok(self.encode()?.bytes)
+    }
+}
+
+impl<'a> serde::Deserialize<'a> for RowGroup {
+    type Schema = Vec<PlankField>;
+    fn from_bytes(bytes: &[u8], schema: &'a Self::Schema) -> std::io::Result<(Self, usize)> {
+        let leaves = shred::leaves(schema)?;
+        let layout = Self::layout(bytes, leaves.len())?;
+        let consumed = layout.columns.last().map_or(8, |range| range.end);
+
+        let mut start = 0;
+        let columns = schema
+            .iter()
+            .map(|field| {
+                let end = start + shred::leaf_count(field.field_type());
+                let column = Self::read_column(
+                    layout.columns[start..end].iter().map(|r| &bytes[r.clone()]),
+                    &leaves[start..end],
+                    field,
+                    layout.row_count,
+                );
+                start = end;
+                column
+            })
+            .collect::<std::io::Result<Vec<_>>>()?;
+
+        Ok((
+            RowGroup {
+                id: layout.id,
+                columns,
+                row_count: layout.row_count,
+                codec: Codec::default(),
+                page_size: DEFAULT_PAGE_SIZE,
+                bloom_filters: Vec::new(),
+            },
+            consumed,
+        ))
+    }
 }
 
 #[cfg(test)]
@@ -92,17 +230,25 @@ mod tests {
         let rowgroup = RowGroup::new(
             0,
             vec![
-                Column::new(vec![PlankData::Int32(1), PlankData::Int32(2)]),
-                Column::new(vec![
-                    PlankData::Str("a".to_string()),
-                    PlankData::Str("b".to_string()),
-                ]),
+                Column::from_values(
+                    &PlankType::Int32,
+                    vec![PlankData::Int32(1), PlankData::Int32(2)],
+                )
+                .unwrap(),
+                Column::from_values(
+                    &PlankType::Str,
+                    vec![
+                        PlankData::Str("a".to_string()),
+                        PlankData::Str("b".to_string()),
+                    ],
+                )
+                .unwrap(),
             ],
             2,
         );
 
         let serialized = rowgroup.to_bytes().unwrap();
-        let deserialized = RowGroup::from_bytes(
+        let (deserialized, size) = RowGroup::from_bytes(
             &serialized,
             &vec![
                 PlankField::new("col1", PlankType::Int32),
@@ -111,11 +257,9 @@ mod tests {
         )
         .unwrap();
 
+        assert_eq!(size, serialized.len());
         assert_eq!(rowgroup.row_count, deserialized.row_count);
         assert_eq!(rowgroup.id, deserialized.id);
-        assert_eq!(
-            rowgroup.columns[0].records[0],
-            deserialized.columns[0].records[0]
-        );
+        assert_eq!(rowgroup.columns, deserialized.columns);
     }
 }
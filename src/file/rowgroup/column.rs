@@ -1,71 +1,647 @@
-use crate::serde;
-use crate::serde::{Deserialize, Serialize};
+pub mod bitmap;
+pub mod page;
+
+use crate::file::codec::Codec;
+use crate::serde::{self, read_u32, read_u64};
 use crate::types::{data::PlankData, fields::PlankField, types::PlankType};
-use flate2::read::ZlibDecoder;
-use flate2::write::ZlibEncoder;
-use flate2::Compression;
-use std::io::prelude::*;
-
-#[derive(Debug, Clone, Default)]
-pub(crate) struct Column {
-    // id: u32,
-    pub(crate) records: Vec<PlankData>,
+use bitmap::Bitmap;
+use page::{DEFAULT_PAGE_SIZE, Page, Pages};
+use std::ops::Range;
+
+/// The values of one column held in typed buffers.
+///
+/// Nested types keep their children as columns of their own. `get` and `iter` give a row-wise
+/// `PlankData` view on top of the buffers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Column {
+    Int32(Vec<i32>),
+    Int64(Vec<i64>),
+    Bool(Bitmap),
+    /// Value `i` is `data[offsets[i]..offsets[i + 1]]`
+    Str {
+        offsets: Vec<usize>,
+        data: Vec<u8>,
+    },
+    Struct {
+        len: usize,
+        fields: Vec<Column>,
+    },
+    /// List `i` holds `items[offsets[i]..offsets[i + 1]]`
+    List {
+        offsets: Vec<usize>,
+        items: Box<Column>,
+    },
+    /// Value `i` is null where `validity` is unset, `values` then holds a placeholder
+    Nullable {
+        validity: Bitmap,
+        values: Box<Column>,
+    },
 }
 
 impl Column {
-    pub fn new(records: Vec<PlankData>) -> Self {
-        Column { records }
+    /// Creates an empty column able to hold values of `field_type`
+    pub fn new(field_type: &PlankType) -> Self {
+        match field_type {
+            PlankType::Int32 => Column::Int32(Vec::new()),
+            PlankType::Int64 => Column::Int64(Vec::new()),
+            PlankType::Bool => Column::Bool(Bitmap::new()),
+            PlankType::Str => Column::Str {
+                offsets: vec![0],
+                data: Vec::new(),
+            },
+            PlankType::Struct(fields) => Column::Struct {
+                len: 0,
+                fields: fields.iter().map(|f| Column::new(f.field_type())).collect(),
+            },
+            PlankType::List(item_type) => Column::List {
+                offsets: vec![0],
+                items: Box::new(Column::new(item_type)),
+            },
+            PlankType::Nullable(inner) => Column::Nullable {
+                validity: Bitmap::new(),
+                values: Box::new(Column::new(inner)),
+            },
+            PlankType::Map(value) => Column::new(&PlankType::map_entries(value)),
+        }
+    }
+
+    pub fn from_values<I: IntoIterator<Item = PlankData>>(
+        field_type: &PlankType,
+        values: I,
+    ) -> std::io::Result<Self> {
+        let mut column = Column::new(field_type);
+        for value in values {
+            column.push(value)?;
+        }
+        Ok(column)
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Column::Int32(v) => v.len(),
+            Column::Int64(v) => v.len(),
+            Column::Bool(v) => v.len(),
+            Column::Str { offsets, .. } => offsets.len() - 1,
+            Column::Struct { len, .. } => *len,
+            Column::List { offsets, .. } => offsets.len() - 1,
+            Column::Nullable { validity, .. } => validity.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Appends a value, failing without modifying the column if it does not match the column
+    /// type or holds a string or list too long for its `u32` length
+    pub fn push(&mut self, value: PlankData) -> std::io::Result<()> {
+        if !self.accepts(&value) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("value {} does not match the column type", value),
+            ));
+        }
+        self.push_unchecked(value);
+        Ok(())
+    }
+
+    pub(crate) fn accepts(&self, value: &PlankData) -> bool {
+        match (self, value) {
+            (Column::Int32(_), PlankData::Int32(_))
+            | (Column::Int64(_), PlankData::Int64(_))
+            | (Column::Bool(_), PlankData::Bool(_)) => true,
+            (Column::Str { .. }, PlankData::Str(s)) => u32::try_from(s.len()).is_ok(),
+            (Column::Struct { fields, .. }, PlankData::Struct(values)) => {
+                fields.len() == values.len() && fields.iter().zip(values).all(|(f, v)| f.accepts(v))
+            }
+            (Column::List { items, .. }, PlankData::List(values)) => {
+                u32::try_from(values.len()).is_ok() && values.iter().all(|v| items.accepts(v))
+            }
+            (Column::Nullable { values, .. }, value) => {
+                matches!(value, PlankData::Null) || values.accepts(value)
+            }
+            _ => false,
+        }
+    }
+
+    fn push_unchecked(&mut self, value: PlankData) {
+        match (self, value) {
+            (Column::Int32(v), PlankData::Int32(n)) => v.push(n),
+            (Column::Int64(v), PlankData::Int64(n)) => v.push(n),
+            (Column::Bool(v), PlankData::Bool(b)) => v.push(b),
+            (Column::Str { offsets, data }, PlankData::Str(s)) => {
+                data.extend_from_slice(s.as_bytes());
+                offsets.push(data.len());
+            }
+            (Column::Struct { len, fields }, PlankData::Struct(values)) => {
+                for (field, value) in fields.iter_mut().zip(values) {
+                    field.push_unchecked(value);
+                }
+                *len += 1;
+            }
+            (Column::List { offsets, items }, PlankData::List(values)) => {
+                for value in values {
+                    items.push_unchecked(value);
+                }
+                offsets.push(items.len());
+            }
+            (Column::Nullable { validity, values }, PlankData::Null) => {
+                validity.push(false);
+                values.push_default();
+            }
+            (Column::Nullable { validity, values }, value) => {
+                validity.push(true);
+                values.push_unchecked(value);
+            }
+            _ => unreachable!("value was checked against the column type"),
+        }
+    }
+
+    /// Appends the placeholder held under a null
+    pub(crate) fn push_default(&mut self) {
+        match self {
+            Column::Int32(v) => v.push(0),
+            Column::Int64(v) => v.push(0),
+            Column::Bool(v) => v.push(false),
+            Column::Str { offsets, data } => offsets.push(data.len()),
+            Column::Struct { len, fields } => {
+                fields.iter_mut().for_each(Column::push_default);
+                *len += 1;
+            }
+            Column::List { offsets, items } => offsets.push(items.len()),
+            Column::Nullable { validity, values } => {
+                validity.push(false);
+                values.push_default();
+            }
+        }
+    }
+
+    /// Rebuilds the value at `index` as `PlankData`
+    pub fn get(&self, index: usize) -> Option<PlankData> {
+        if index >= self.len() {
+            return None;
+        }
+        match self {
+            Column::Int32(v) => Some(PlankData::Int32(v[index])),
+            Column::Int64(v) => Some(PlankData::Int64(v[index])),
+            Column::Bool(v) => v.get(index).map(PlankData::Bool),
+            Column::Str { .. } => self.str_value(index).map(|s| PlankData::Str(s.to_string())),
+            Column::Struct { fields, .. } => fields
+                .iter()
+                .map(|f| f.get(index))
+                .collect::<Option<Vec<_>>>()
+                .map(PlankData::Struct),
+            Column::List { offsets, items } => (offsets[index]..offsets[index + 1])
+                .map(|i| items.get(i))
+                .collect::<Option<Vec<_>>>()
+                .map(PlankData::List),
+            Column::Nullable { validity, values } => match validity.get(index)? {
+                true => values.get(index),
+                false => Some(PlankData::Null),
+            },
+        }
+    }
+
+    /// Borrows the string at `index` without copying it
+    pub fn str_value(&self, index: usize) -> Option<&str> {
+        match self {
+            Column::Str { offsets, data } if index + 1 < offsets.len() => {
+                std::str::from_utf8(&data[offsets[index]..offsets[index + 1]]).ok()
+            }
+            _ => None,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = PlankData> + '_ {
+        (0..self.len()).filter_map(|i| self.get(i))
+    }
+
+    /// The size of the values once encoded, before compression
+    pub(crate) fn encoded_size(&self) -> usize {
+        match self {
+            Column::Int32(v) => v.len() * 4,
+            Column::Int64(v) => v.len() * 8,
+            Column::Bool(v) => v.len(),
+            Column::Str { data, .. } => self.len() * 4 + data.len(),
+            Column::Struct { len, fields } => {
+                len * 4 + fields.iter().map(Column::encoded_size).sum::<usize>()
+            }
+            Column::List { items, .. } => self.len() * 4 + items.encoded_size(),
+            Column::Nullable { values, .. } => self.len() + values.encoded_size(),
+        }
+    }
+
+    /// The heap memory held by the buffers
+    pub(crate) fn memory_size(&self) -> usize {
+        let offsets_size = |offsets: &Vec<usize>| offsets.capacity() * size_of::<usize>();
+        match self {
+            Column::Int32(v) => v.capacity() * size_of::<i32>(),
+            Column::Int64(v) => v.capacity() * size_of::<i64>(),
+            Column::Bool(v) => v.as_bytes().len(),
+            Column::Str { offsets, data } => offsets_size(offsets) + data.capacity(),
+            Column::Struct { fields, .. } => fields.iter().map(Column::memory_size).sum(),
+            Column::List { offsets, items } => offsets_size(offsets) + items.memory_size(),
+            Column::Nullable { validity, values } => {
+                validity.as_bytes().len() + values.memory_size()
+            }
+        }
+    }
+
+    pub(crate) fn encode_value(&self, index: usize, buf: &mut Vec<u8>) {
+        match self {
+            Column::Int32(v) => buf.extend_from_slice(&v[index].to_le_bytes()),
+            Column::Int64(v) => buf.extend_from_slice(&v[index].to_le_bytes()),
+            Column::Bool(v) => buf.push(v.get(index).unwrap_or_default() as u8),
+            Column::Str { offsets, data } => {
+                let bytes = &data[offsets[index]..offsets[index + 1]];
+                buf.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+                buf.extend_from_slice(bytes);
+            }
+            Column::Struct { fields, .. } => {
+                buf.extend_from_slice(&(fields.len() as u32).to_le_bytes());
+                for field in fields {
+                    field.encode_value(index, buf);
+                }
+            }
+            Column::List { offsets, items } => {
+                let (start, end) = (offsets[index], offsets[index + 1]);
+                buf.extend_from_slice(&((end - start) as u32).to_le_bytes());
+                for i in start..end {
+                    items.encode_value(i, buf);
+                }
+            }
+            // A presence byte, then the value if there is one
+            Column::Nullable { validity, values } => {
+                let present = validity.get(index).unwrap_or_default();
+                buf.push(present as u8);
+                if present {
+                    values.encode_value(index, buf);
+                }
+            }
+        }
+    }
+
+    /// Decodes one value from the start of `bytes` straight into the buffers and returns the
+    /// number of bytes it occupied
+    fn decode_value(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Column::Int32(v) => {
+                v.push(read_u32(bytes, 0)? as i32);
+                Ok(4)
+            }
+            Column::Int64(v) => {
+                v.push(read_u64(bytes, 0)? as i64);
+                Ok(8)
+            }
+            Column::Bool(v) => {
+                match bytes.first() {
+                    Some(0) => v.push(false),
+                    Some(1) => v.push(true),
+                    _ => {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::InvalidData,
+                            "expected bool",
+                        ));
+                    }
+                }
+                Ok(1)
+            }
+            Column::Str { offsets, data } => {
+                let size = read_u32(bytes, 0)? as usize;
+                let value = bytes
+                    .get(4..4 + size)
+                    .filter(|b| std::str::from_utf8(b).is_ok())
+                    .ok_or_else(|| {
+                        std::io::Error::new(
+                            std::io::ErrorKind::InvalidData,
+                            format!("expected to read {} bytes", size),
+                        )
+                    })?;
+                data.extend_from_slice(value);
+                offsets.push(data.len());
+                Ok(4 + size)
+            }
+            Column::Struct { len, fields } => {
+                let size = read_u32(bytes, 0)? as usize;
+                if size != fields.len() {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("expected {} struct fields, found {}", fields.len(), size),
+                    ));
+                }
+                let mut pos = 4;
+                for field in fields {
+                    pos += field.decode_value(&bytes[pos..])?;
+                }
+                *len += 1;
+                Ok(pos)
+            }
+            Column::List { offsets, items } => {
+                let size = read_u32(bytes, 0)? as usize;
+                let mut pos = 4;
+                for _ in 0..size {
+                    pos += items.decode_value(&bytes[pos..])?;
+                }
+                offsets.push(items.len());
+                Ok(pos)
+            }
+            Column::Nullable { validity, values } => match bytes.first() {
+                Some(0) => {
+                    validity.push(false);
+                    values.push_default();
+                    Ok(1)
+                }
+                Some(1) => {
+                    let size = values.decode_value(&bytes[1..])?;
+                    validity.push(true);
+                    Ok(1 + size)
+                }
+                _ => Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "expected a presence byte",
+                )),
+            },
+        }
     }
 }
 
-impl serde::Serialize for Column {
-    fn to_bytes(&self) -> std::io::Result<Vec<u8>> {
+/// Where one page of an encoded chunk sits and what it holds
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PageSpan {
+    /// Byte range of the page within the chunk, header included
+    pub(crate) bytes: Range<usize>,
+    pub(crate) rows: Range<usize>,
+    /// Level entries of the page, one per row unless the leaf is repeated
+    pub(crate) entries: Range<usize>,
+    /// Indexes of the values stored in the page
+    pub(crate) values: Range<usize>,
+}
+
+impl Column {
+    /// Encodes the column as a chunk of pages. A page is cut as soon as its encoded values
+    /// reach `page_size` bytes, so a page never splits a value.
+    pub(crate) fn encode(&self, codec: Codec, page_size: usize) -> std::io::Result<Vec<u8>> {
+        Ok(self.encode_leaf(&[], &[], 0, codec, page_size)?.0)
+    }
+
+    /// Encodes the values of a shredded leaf with its levels as a chunk of pages.
+    ///
+    /// Each page holds its repetition levels, then its definition levels, one byte per entry,
+    /// then the values of the entries defined up to `max_def`. Empty level slices are left
+    /// out. Pages are only cut in front of an entry that starts a new row. The chunk comes
+    /// back with the span of every page.
+    pub(crate) fn encode_leaf(
+        &self,
+        rep: &[u8],
+        def: &[u8],
+        max_def: u8,
+        codec: Codec,
+        page_size: usize,
+    ) -> std::io::Result<(Vec<u8>, Vec<PageSpan>)> {
+        let entries = if def.is_empty() {
+            self.len()
+        } else {
+            def.len()
+        };
+        let mut chunk = Vec::new();
+        let mut spans = Vec::new();
         let mut buf = Vec::new();
+        let mut start = 0;
+        let mut value = 0;
+        let mut first_value = 0;
+        let mut row = 0;
+        let mut first_row = 0;
+
+        for i in 0..entries {
+            if rep.get(i).is_none_or(|&r| r == 0) {
+                row += 1;
+            }
+            if def.is_empty() || def[i] == max_def {
+                self.encode_value(value, &mut buf);
+                value += 1;
+            }
+
+            let row_ends = rep.get(i + 1).is_none_or(|&r| r == 0);
+            if (buf.len() >= page_size && row_ends) || i + 1 == entries {
+                let mut page = Vec::with_capacity(2 * (i + 1 - start) + buf.len());
+                if !rep.is_empty() {
+                    page.extend_from_slice(&rep[start..=i]);
+                }
+                if !def.is_empty() {
+                    page.extend_from_slice(&def[start..=i]);
+                }
+                page.extend_from_slice(&buf);
+
+                let offset = chunk.len();
+                Page::write(&page, (i + 1 - start) as u32, codec, &mut chunk)?;
+                spans.push(PageSpan {
+                    bytes: offset..chunk.len(),
+                    rows: first_row..row,
+                    entries: start..i + 1,
+                    values: first_value..value,
+                });
+                buf.clear();
+                start = i + 1;
+                first_value = value;
+                first_row = row;
+            }
+        }
+
+        Ok((chunk, spans))
+    }
+
+    /// Decodes a chunk written by `encode_leaf` into its repetition levels, definition levels
+    /// and values
+    pub(crate) fn decode_leaf(
+        bytes: &[u8],
+        leaf_type: &PlankType,
+        max_rep: u8,
+        max_def: u8,
+    ) -> std::io::Result<(Vec<u8>, Vec<u8>, Column)> {
+        let mut rep = Vec::new();
+        let mut def = Vec::new();
+        let mut column = Column::new(leaf_type);
+
+        for page in Pages::new(bytes) {
+            let page = page?;
+            let buf = page.decompress()?;
+            let entries = page.header.value_count as usize;
+
+            let mut pos = 0;
+            if max_rep > 0 {
+                rep.extend_from_slice(read_levels(&buf, &mut pos, entries)?);
+            }
+            let value_count = if max_def > 0 {
+                let page_def = read_levels(&buf, &mut pos, entries)?;
+                def.extend_from_slice(page_def);
+                page_def.iter().filter(|&&d| d == max_def).count()
+            } else {
+                entries
+            };
 
-        for record in &self.records {
-            buf.extend_from_slice(&record.to_bytes()?);
+            for _ in 0..value_count {
+                pos += column.decode_value(buf.get(pos..).unwrap_or_default())?;
+            }
+            if pos != buf.len() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "page holds more bytes than its values",
+                ));
+            }
         }
 
-        let mut c = ZlibEncoder::new(Vec::new(), Compression::default());
-        c.write_all(&buf)?;
+        Ok((rep, def, column))
+    }
+}
+
+fn read_levels<'b>(buf: &'b [u8], pos: &mut usize, entries: usize) -> std::io::Result<&'b [u8]> {
+    let levels = buf.get(*pos..*pos + entries).ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, "page levels are truncated")
+    })?;
+    *pos += entries;
+    Ok(levels)
+}
 
-        c.finish()
+impl serde::Serialize for Column {
+    fn to_bytes(&self) -> std::io::Result<Vec<u8>> {
+        self.encode(Codec::default(), DEFAULT_PAGE_SIZE)
     }
 }
 
 impl<'a> serde::Deserialize<'a> for Column {
     type Schema = PlankField;
-    fn from_bytes(bytes: &[u8], schema: &'a Self::Schema) -> std::io::Result<Self> {
-        let mut c = ZlibDecoder::new(bytes);
-        let mut bytes = Vec::new();
-        c.read_to_end(&mut bytes)?;
+    fn from_bytes(bytes: &[u8], schema: &'a Self::Schema) -> std::io::Result<(Self, usize)> {
+        let (_, _, column) = Column::decode_leaf(bytes, schema.field_type(), 0, 0)?;
 
-        let mut pos = 0;
-        let mut v = Vec::new();
-        while pos < bytes.len() {
-            let item = PlankData::from_bytes(&bytes[pos..], schema.field_type())?;
-            let size = item.to_bytes()?.len();
-            pos += size;
-            v.push(item);
-        }
-
-        Ok(Column { records: v })
+        // The column owns the whole chunk it was handed
+        Ok((column, bytes.len()))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::serde::{Deserialize, Serialize};
 
     #[test]
     fn test_roundtrip_column() {
-        let column = Column::new(vec![PlankData::Int32(1), PlankData::Int32(2)]);
+        let field = PlankField::new("test", PlankType::Int32);
+        let column = Column::from_values(
+            field.field_type(),
+            vec![PlankData::Int32(1), PlankData::Int32(2)],
+        )
+        .unwrap();
         let bytes = column.to_bytes().unwrap();
 
-        let expected = Column::from_bytes(&bytes, &PlankField::new("test", PlankType::Int32)).unwrap();
+        let (expected, _) = Column::from_bytes(&bytes, &field).unwrap();
 
-        for (x, y) in column.records.iter().zip(&expected.records) {
+        assert_eq!(expected, Column::Int32(vec![1, 2]));
+        for (x, y) in column.iter().zip(expected.iter()) {
             assert_eq!(x, y);
         }
     }
+
+    #[test]
+    fn test_roundtrip_nested_column() {
+        let field = PlankField::new(
+            "person",
+            PlankType::Struct(vec![
+                PlankField::new("name", PlankType::Str),
+                PlankField::new("tags", PlankType::List(Box::new(PlankType::Str))),
+            ]),
+        );
+        let values = vec![
+            PlankData::Struct(vec![
+                PlankData::Str("me".to_string()),
+                PlankData::List(vec![
+                    PlankData::Str("a".to_string()),
+                    PlankData::Str("b".to_string()),
+                ]),
+            ]),
+            PlankData::Struct(vec![
+                PlankData::Str("you".to_string()),
+                PlankData::List(vec![]),
+            ]),
+        ];
+        let column = Column::from_values(field.field_type(), values.clone()).unwrap();
+        let bytes = column.to_bytes().unwrap();
+
+        let (expected, _) = Column::from_bytes(&bytes, &field).unwrap();
+
+        assert_eq!(expected.len(), 2);
+        assert_eq!(expected.iter().collect::<Vec<_>>(), values);
+        match &expected {
+            Column::Struct { fields, .. } => assert_eq!(fields[0].str_value(1), Some("you")),
+            _ => panic!("expected a struct column"),
+        }
+    }
+
+    #[test]
+    fn test_nullable_column_holds_nulls() {
+        let field_type = PlankType::Struct(vec![PlankField::new("n", PlankType::Int32)]).nullable();
+        let values = vec![
+            PlankData::Struct(vec![PlankData::Int32(1)]),
+            PlankData::Null,
+            PlankData::Struct(vec![PlankData::Int32(3)]),
+        ];
+        let column = Column::from_values(&field_type, values.clone()).unwrap();
+
+        assert_eq!(column.iter().collect::<Vec<_>>(), values);
+        assert!(column.accepts(&PlankData::Null));
+        assert!(!Column::new(&PlankType::Int32).accepts(&PlankData::Null));
+    }
+
+    #[test]
+    fn test_push_rejects_mismatched_type() {
+        let mut column = Column::new(&PlankType::Int64);
+
+        assert!(column.push(PlankData::Str("1".to_string())).is_err());
+        assert!(column.is_empty());
+    }
+
+    #[test]
+    fn test_roundtrip_uncompressed_column() {
+        let field = PlankField::new("test", PlankType::Str);
+        let column = Column::from_values(
+            field.field_type(),
+            vec![
+                PlankData::Str("a".to_string()),
+                PlankData::Str("bc".to_string()),
+            ],
+        )
+        .unwrap();
+        let bytes = column
+            .encode(Codec::Uncompressed, DEFAULT_PAGE_SIZE)
+            .unwrap();
+
+        assert_eq!(
+            bytes[page::PageHeader::SIZE..],
+            [1, 0, 0, 0, b'a', 2, 0, 0, 0, b'b', b'c']
+        );
+
+        let (expected, size) = Column::from_bytes(&bytes, &field).unwrap();
+
+        assert_eq!(size, bytes.len());
+        assert_eq!(expected, column);
+    }
+
+    #[test]
+    fn test_column_split_into_pages() {
+        let field = PlankField::new("test", PlankType::Int64);
+        let column =
+            Column::from_values(field.field_type(), (0..100).map(PlankData::Int64)).unwrap();
+
+        // Eight values fill a 64 byte page
+        let bytes = column.encode(Codec::Zlib, 64).unwrap();
+        let pages = Pages::new(&bytes)
+            .collect::<std::io::Result<Vec<_>>>()
+            .unwrap();
+
+        assert_eq!(pages.len(), 13);
+        assert!(pages[..12].iter().all(|p| p.header.value_count == 8));
+        assert_eq!(pages[12].header.value_count, 4);
+
+        let (expected, _) = Column::from_bytes(&bytes, &field).unwrap();
+        assert_eq!(expected, column);
+    }
 }
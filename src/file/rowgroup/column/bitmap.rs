@@ -0,0 +1,75 @@
+/// A growable, bit-packed vector of booleans.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Bitmap {
+    bits: Vec<u8>,
+    len: usize,
+}
+
+impl Bitmap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push(&mut self, value: bool) {
+        if self.len.is_multiple_of(8) {
+            self.bits.push(0);
+        }
+        if value {
+            self.bits[self.len / 8] |= 1 << (self.len % 8);
+        }
+        self.len += 1;
+    }
+
+    pub fn get(&self, index: usize) -> Option<bool> {
+        if index >= self.len {
+            return None;
+        }
+        Some(self.bits[index / 8] & (1 << (index % 8)) != 0)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = bool> + '_ {
+        (0..self.len).map(|i| self.bits[i / 8] & (1 << (i % 8)) != 0)
+    }
+
+    /// The packed bytes, least significant bit first
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bits
+    }
+}
+
+impl FromIterator<bool> for Bitmap {
+    fn from_iter<I: IntoIterator<Item = bool>>(iter: I) -> Self {
+        let mut bitmap = Bitmap::new();
+        for value in iter {
+            bitmap.push(value);
+        }
+        bitmap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bitmap_push_and_get() {
+        let values = [
+            true, false, true, true, false, false, false, true, true, false,
+        ];
+        let bitmap: Bitmap = values.iter().copied().collect();
+
+        assert_eq!(bitmap.len(), values.len());
+        assert_eq!(bitmap.as_bytes().len(), 2);
+        assert_eq!(bitmap.iter().collect::<Vec<_>>(), values);
+        assert_eq!(bitmap.get(8), Some(true));
+        assert_eq!(bitmap.get(10), None);
+    }
+}
@@ -0,0 +1,200 @@
+use crate::file::codec::Codec;
+use crate::serde::read_u32;
+use std::borrow::Cow;
+
+/// Pages are cut once their encoded values reach this many bytes
+pub const DEFAULT_PAGE_SIZE: usize = 1024 * 1024;
+
+/// How the values inside a page are laid out before compression
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    /// Values back to back in their `Serialize` form
+    #[default]
+    Plain,
+}
+
+/// The fixed-size header in front of every page of a column chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageHeader {
+    pub value_count: u32,
+    pub encoding: Encoding,
+    pub codec: Codec,
+    pub uncompressed_size: u32,
+    pub compressed_size: u32,
+}
+
+/// One page of a column chunk, still compressed
+#[derive(Debug, Clone, Copy)]
+pub struct Page<'a> {
+    pub header: PageHeader,
+    data: &'a [u8],
+}
+
+/// Walks the pages of a column chunk by their headers, so pages that are not needed are
+/// skipped without being decompressed
+pub struct Pages<'a> {
+    bytes: &'a [u8],
+}
+
+impl Encoding {
+    pub(crate) fn id(&self) -> u8 {
+        match self {
+            Encoding::Plain => 0,
+        }
+    }
+
+    pub(crate) fn from_id(id: u8) -> std::io::Result<Self> {
+        match id {
+            0 => Ok(Encoding::Plain),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("unknown encoding id {}", id),
+            )),
+        }
+    }
+}
+
+impl PageHeader {
+    pub const SIZE: usize = 4 + 1 + 1 + 4 + 4;
+
+    pub(crate) fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.value_count.to_le_bytes());
+        out.push(self.encoding.id());
+        out.push(self.codec.id());
+        out.extend_from_slice(&self.uncompressed_size.to_le_bytes());
+        out.extend_from_slice(&self.compressed_size.to_le_bytes());
+    }
+
+    pub(crate) fn read(bytes: &[u8]) -> std::io::Result<Self> {
+        if bytes.len() < Self::SIZE {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "page header is truncated",
+            ));
+        }
+        Ok(PageHeader {
+            value_count: read_u32(bytes, 0)?,
+            encoding: Encoding::from_id(bytes[4])?,
+            codec: Codec::from_id(bytes[5])?,
+            uncompressed_size: read_u32(bytes, 6)?,
+            compressed_size: read_u32(bytes, 10)?,
+        })
+    }
+}
+
+impl<'a> Page<'a> {
+    /// Compresses `values` (already encoded) into a page appended to `out`
+    pub(crate) fn write(
+        values: &[u8],
+        value_count: u32,
+        codec: Codec,
+        out: &mut Vec<u8>,
+    ) -> std::io::Result<()> {
+        let mut data = Vec::new();
+        codec.compress_into(values, &mut data)?;
+        let size = |len: usize| {
+            u32::try_from(len).map_err(|_| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "page is larger than 4 GiB",
+                )
+            })
+        };
+
+        PageHeader {
+            value_count,
+            encoding: Encoding::Plain,
+            codec,
+            uncompressed_size: size(values.len())?,
+            compressed_size: size(data.len())?,
+        }
+        .write(out);
+        out.extend_from_slice(&data);
+
+        Ok(())
+    }
+
+    /// The encoded values. Uncompressed pages are handed back borrowed.
+    pub fn decompress(&self) -> std::io::Result<Cow<'a, [u8]>> {
+        let bytes = self.header.codec.decompress(self.data)?;
+        if bytes.len() != self.header.uncompressed_size as usize {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "expected {} bytes in page, found {}",
+                    self.header.uncompressed_size,
+                    bytes.len()
+                ),
+            ));
+        }
+        Ok(bytes)
+    }
+}
+
+impl<'a> Pages<'a> {
+    pub fn new(chunk: &'a [u8]) -> Self {
+        Pages { bytes: chunk }
+    }
+}
+
+impl<'a> Iterator for Pages<'a> {
+    type Item = std::io::Result<Page<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.bytes.is_empty() {
+            return None;
+        }
+
+        let header = match PageHeader::read(self.bytes) {
+            Ok(header) => header,
+            Err(e) => {
+                self.bytes = &[];
+                return Some(Err(e));
+            }
+        };
+        let end = PageHeader::SIZE + header.compressed_size as usize;
+
+        match self.bytes.get(PageHeader::SIZE..end) {
+            Some(data) => {
+                self.bytes = &self.bytes[end..];
+                Some(Ok(Page { header, data }))
+            }
+            None => {
+                self.bytes = &[];
+                Some(Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "page is truncated",
+                )))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pages_skip_without_decompressing() {
+        let mut chunk = Vec::new();
+        Page::write(&[1, 0, 0, 0, 2, 0, 0, 0], 2, Codec::Zlib, &mut chunk).unwrap();
+        Page::write(&[3, 0, 0, 0], 1, Codec::Uncompressed, &mut chunk).unwrap();
+
+        let pages = Pages::new(&chunk)
+            .collect::<std::io::Result<Vec<_>>>()
+            .unwrap();
+
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[0].header.value_count, 2);
+        assert_eq!(pages[0].header.uncompressed_size, 8);
+        assert_eq!(pages[1].header.codec, Codec::Uncompressed);
+        assert!(matches!(
+            pages[1].decompress().unwrap(),
+            Cow::Borrowed(&[3, 0, 0, 0])
+        ));
+        assert_eq!(
+            pages[0].decompress().unwrap().as_ref(),
+            &[1, 0, 0, 0, 2, 0, 0, 0]
+        );
+    }
+}
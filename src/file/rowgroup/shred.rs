@@ -0,0 +1,621 @@
+use crate::file::rowgroup::column::{Column, bitmap::Bitmap};
+use crate::types::{fields::PlankField, types::PlankType};
+use std::ops::Range;
+
+/// A primitive column at the bottom of a field, stored as a column chunk of its own.
+///
+/// Struct fields are flattened into one leaf per primitive sub-field, named by its dotted path
+/// (`user.address.city`). Lists and nullable types do not add a path segment. A leaf under
+/// lists carries repetition and definition levels next to its values, and a leaf under
+/// nullable types definition levels, so the nested values and nulls can be rebuilt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Leaf {
+    field: PlankField,
+    max_rep: u8,
+    max_def: u8,
+}
+
+/// The values of one leaf shredded out of a nested column, with their levels.
+///
+/// There is one level entry per value, null or empty list. Levels are only kept when the leaf
+/// has lists or nullable types above it, otherwise every entry is a value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LeafData {
+    pub(crate) max_rep: u8,
+    pub(crate) max_def: u8,
+    pub(crate) rep: Vec<u8>,
+    pub(crate) def: Vec<u8>,
+    pub(crate) values: Column,
+}
+
+/// Reads a leaf back while a nested column is assembled
+struct Cursor {
+    data: LeafData,
+    entry: usize,
+    value: usize,
+}
+
+impl Leaf {
+    /// The leaf's dotted path and primitive type
+    pub fn field(&self) -> &PlankField {
+        &self.field
+    }
+
+    pub fn path(&self) -> &str {
+        self.field.field_name()
+    }
+
+    /// The number of lists above the leaf
+    pub fn max_repetition_level(&self) -> u8 {
+        self.max_rep
+    }
+
+    /// The number of lists and nullable types above the leaf
+    pub fn max_definition_level(&self) -> u8 {
+        self.max_def
+    }
+}
+
+/// The leaves of every field of `schema` in storage order
+pub(crate) fn leaves(schema: &[PlankField]) -> std::io::Result<Vec<Leaf>> {
+    let mut out = Vec::new();
+    for field in schema {
+        collect_leaves(field.field_name(), field.field_type(), 0, 0, &mut out)?;
+    }
+    Ok(out)
+}
+
+fn collect_leaves(
+    path: &str,
+    field_type: &PlankType,
+    lists: u8,
+    def: u8,
+    out: &mut Vec<Leaf>,
+) -> std::io::Result<()> {
+    let nested = || {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{} nests too many lists or nullable types", path),
+        )
+    };
+    match field_type {
+        PlankType::Struct(fields) => {
+            if fields.is_empty() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("struct {} has no fields to store", path),
+                ));
+            }
+            for field in fields {
+                let path = format!("{}.{}", path, field.field_name());
+                collect_leaves(&path, field.field_type(), lists, def, out)?;
+            }
+        }
+        PlankType::List(item_type) => {
+            let lists = lists.checked_add(1).ok_or_else(nested)?;
+            let def = def.checked_add(1).ok_or_else(nested)?;
+            collect_leaves(path, item_type, lists, def, out)?;
+        }
+        PlankType::Nullable(inner) => {
+            let def = def.checked_add(1).ok_or_else(nested)?;
+            collect_leaves(path, inner, lists, def, out)?;
+        }
+        PlankType::Map(value) => {
+            collect_leaves(path, &PlankType::map_entries(value), lists, def, out)?;
+        }
+        _ => out.push(Leaf {
+            field: PlankField::new(path, field_type.clone()),
+            max_rep: lists,
+            max_def: def,
+        }),
+    }
+    Ok(())
+}
+
+/// The number of leaves `field_type` is stored as
+pub(crate) fn leaf_count(field_type: &PlankType) -> usize {
+    match field_type {
+        PlankType::Struct(fields) => fields.iter().map(|f| leaf_count(f.field_type())).sum(),
+        PlankType::List(inner) | PlankType::Nullable(inner) => leaf_count(inner),
+        PlankType::Map(value) => leaf_count(&PlankType::map_entries(value)),
+        _ => 1,
+    }
+}
+
+/// Finds a top-level field or a dotted path into one.
+///
+/// Returns the field as it is read back, with the lists and nullable types crossed on the way
+/// wrapped around the sub-field's type, and the range of leaves it is stored in.
+pub(crate) fn project(
+    schema: &[PlankField],
+    name: &str,
+) -> std::io::Result<(PlankField, Range<usize>)> {
+    let mut start = 0;
+    for field in schema {
+        let count = leaf_count(field.field_type());
+        if field.field_name() == name {
+            return Ok((field.clone(), start..start + count));
+        }
+        start += count;
+    }
+
+    let mut start = 0;
+    for field in schema {
+        let rest = name
+            .strip_prefix(field.field_name().as_str())
+            .and_then(|rest| rest.strip_prefix('.'));
+        if let Some((field_type, range)) =
+            rest.and_then(|rest| project_path(field.field_type(), rest, start))
+        {
+            return Ok((PlankField::new(name, field_type), range));
+        }
+        start += leaf_count(field.field_type());
+    }
+
+    Err(std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        format!("column {} not found", name),
+    ))
+}
+
+fn project_path(
+    field_type: &PlankType,
+    path: &str,
+    start: usize,
+) -> Option<(PlankType, Range<usize>)> {
+    match field_type {
+        PlankType::List(item_type) => project_path(item_type, path, start)
+            .map(|(t, range)| (PlankType::List(Box::new(t)), range)),
+        // Kept even around a nullable sub-field, so the type matches the leaf levels
+        PlankType::Nullable(inner) => project_path(inner, path, start)
+            .map(|(t, range)| (PlankType::Nullable(Box::new(t)), range)),
+        // A part of a map is read as the list of entries it is part of
+        PlankType::Map(value) => project_path(&PlankType::map_entries(value), path, start),
+        PlankType::Struct(fields) => {
+            let mut start = start;
+            for field in fields {
+                let count = leaf_count(field.field_type());
+                if field.field_name() == path {
+                    return Some((field.field_type().clone(), start..start + count));
+                }
+                let found = path
+                    .strip_prefix(field.field_name().as_str())
+                    .and_then(|rest| rest.strip_prefix('.'))
+                    .and_then(|rest| project_path(field.field_type(), rest, start));
+                if found.is_some() {
+                    return found;
+                }
+                start += count;
+            }
+            None
+        }
+        _ => None,
+    }
+}
+
+/// Splits a column into its leaves in storage order
+pub(crate) fn shred(column: &Column) -> std::io::Result<Vec<LeafData>> {
+    let mut out = Vec::new();
+    empty_leaves(column, 0, 0, &mut out);
+    if out.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "struct has no fields to store",
+        ));
+    }
+
+    for row in 0..column.len() {
+        shred_value(column, row, 0, 0, 0, &mut out)?;
+    }
+    Ok(out)
+}
+
+fn empty_leaves(column: &Column, lists: u8, def: u8, out: &mut Vec<LeafData>) {
+    match column {
+        Column::Struct { fields, .. } => {
+            for field in fields {
+                empty_leaves(field, lists, def, out);
+            }
+        }
+        Column::List { items, .. } => empty_leaves(items, lists + 1, def + 1, out),
+        Column::Nullable { values, .. } => empty_leaves(values, lists, def + 1, out),
+        _ => out.push(LeafData {
+            max_rep: lists,
+            max_def: def,
+            rep: Vec::new(),
+            def: Vec::new(),
+            values: empty_like(column),
+        }),
+    }
+}
+
+fn shred_value(
+    column: &Column,
+    index: usize,
+    rep: u8,
+    def: u8,
+    lists: u8,
+    out: &mut [LeafData],
+) -> std::io::Result<()> {
+    match column {
+        Column::Struct { fields, .. } => {
+            let mut start = 0;
+            for field in fields {
+                let count = column_leaf_count(field);
+                shred_value(
+                    field,
+                    index,
+                    rep,
+                    def,
+                    lists,
+                    &mut out[start..start + count],
+                )?;
+                start += count;
+            }
+        }
+        Column::List { offsets, items } => {
+            let (start, end) = (offsets[index], offsets[index + 1]);
+            if start == end {
+                // An empty list leaves one entry without a value in every leaf below it
+                for leaf in out.iter_mut() {
+                    leaf.push_levels(rep, def);
+                }
+            }
+            for (i, item) in (start..end).enumerate() {
+                let rep = if i == 0 { rep } else { lists + 1 };
+                shred_value(items, item, rep, def + 1, lists + 1, out)?;
+            }
+        }
+        Column::Nullable { validity, values } => {
+            if validity.get(index) == Some(true) {
+                shred_value(values, index, rep, def + 1, lists, out)?;
+            } else {
+                // A null leaves one entry without a value in every leaf below it
+                for leaf in out.iter_mut() {
+                    leaf.push_levels(rep, def);
+                }
+            }
+        }
+        _ => {
+            let leaf = &mut out[0];
+            leaf.push_levels(rep, def);
+            copy_value(&mut leaf.values, column, index)?;
+        }
+    }
+    Ok(())
+}
+
+/// Rebuilds a column of `field_type` holding `row_count` values from its leaves
+pub(crate) fn assemble(
+    field_type: &PlankType,
+    leaves: Vec<LeafData>,
+    row_count: usize,
+) -> std::io::Result<Column> {
+    let mut column = Column::new(field_type);
+    if column_leaf_count(&column) != leaves.len() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("expected {} leaves", column_leaf_count(&column)),
+        ));
+    }
+
+    // A leaf that is the whole column is used as is
+    if !matches!(
+        column,
+        Column::Struct { .. } | Column::List { .. } | Column::Nullable { .. }
+    ) {
+        let mut leaves = leaves;
+        column = leaves.remove(0).values;
+        if column.len() != row_count {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("expected {} values, found {}", row_count, column.len()),
+            ));
+        }
+        return Ok(column);
+    }
+
+    let mut cursors = leaves
+        .into_iter()
+        .map(|data| Cursor {
+            data,
+            entry: 0,
+            value: 0,
+        })
+        .collect::<Vec<_>>();
+
+    for _ in 0..row_count {
+        assemble_value(&mut column, &mut cursors, 0, 0)?;
+    }
+
+    if cursors
+        .iter()
+        .any(|c| c.value != c.data.values.len() || c.entry < c.data.def.len())
+    {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "leaves hold more values than the row group",
+        ));
+    }
+
+    Ok(column)
+}
+
+fn assemble_value(
+    column: &mut Column,
+    cursors: &mut [Cursor],
+    lists: u8,
+    def: u8,
+) -> std::io::Result<()> {
+    match column {
+        Column::Struct { len, fields } => {
+            let mut start = 0;
+            for field in fields {
+                let count = column_leaf_count(field);
+                assemble_value(field, &mut cursors[start..start + count], lists, def)?;
+                start += count;
+            }
+            *len += 1;
+        }
+        Column::List { offsets, items } => {
+            if cursors[0].def()? <= def {
+                for cursor in cursors.iter_mut() {
+                    cursor.entry += 1;
+                }
+            } else {
+                loop {
+                    assemble_value(items, cursors, lists + 1, def + 1)?;
+                    if cursors[0].rep() != Some(lists + 1) {
+                        break;
+                    }
+                }
+            }
+            offsets.push(items.len());
+        }
+        Column::Nullable { validity, values } => {
+            if cursors[0].def()? <= def {
+                for cursor in cursors.iter_mut() {
+                    cursor.entry += 1;
+                }
+                validity.push(false);
+                values.push_default();
+            } else {
+                assemble_value(values, cursors, lists, def + 1)?;
+                validity.push(true);
+            }
+        }
+        _ => {
+            let cursor = &mut cursors[0];
+            if cursor.data.max_def > 0 && cursor.def()? != cursor.data.max_def {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "expected a value in leaf",
+                ));
+            }
+            copy_value(column, &cursor.data.values, cursor.value)?;
+            cursor.value += 1;
+            cursor.entry += 1;
+        }
+    }
+    Ok(())
+}
+
+impl Leaf {
+    pub(crate) fn decode(&self, chunk: &[u8]) -> std::io::Result<LeafData> {
+        let (rep, def, values) =
+            Column::decode_leaf(chunk, self.field.field_type(), self.max_rep, self.max_def)?;
+        Ok(LeafData {
+            max_rep: self.max_rep,
+            max_def: self.max_def,
+            rep,
+            def,
+            values,
+        })
+    }
+}
+
+impl LeafData {
+    fn push_levels(&mut self, rep: u8, def: u8) {
+        if self.max_rep > 0 {
+            self.rep.push(rep);
+        }
+        if self.max_def > 0 {
+            self.def.push(def);
+        }
+    }
+}
+
+impl Cursor {
+    fn def(&self) -> std::io::Result<u8> {
+        self.data.def.get(self.entry).copied().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "leaf has fewer levels than values",
+            )
+        })
+    }
+
+    fn rep(&self) -> Option<u8> {
+        self.data.rep.get(self.entry).copied()
+    }
+}
+
+fn column_leaf_count(column: &Column) -> usize {
+    match column {
+        Column::Struct { fields, .. } => fields.iter().map(column_leaf_count).sum(),
+        Column::List { items, .. } | Column::Nullable { values: items, .. } => {
+            column_leaf_count(items)
+        }
+        _ => 1,
+    }
+}
+
+fn empty_like(column: &Column) -> Column {
+    match column {
+        Column::Int32(_) => Column::Int32(Vec::new()),
+        Column::Int64(_) => Column::Int64(Vec::new()),
+        Column::Bool(_) => Column::Bool(Bitmap::new()),
+        _ => Column::Str {
+            offsets: vec![0],
+            data: Vec::new(),
+        },
+    }
+}
+
+/// Appends value `index` of the primitive column `src` to `dst`
+fn copy_value(dst: &mut Column, src: &Column, index: usize) -> std::io::Result<()> {
+    let missing = || {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("leaf has no value {}", index),
+        )
+    };
+    match (dst, src) {
+        (Column::Int32(dst), Column::Int32(src)) => dst.push(*src.get(index).ok_or_else(missing)?),
+        (Column::Int64(dst), Column::Int64(src)) => dst.push(*src.get(index).ok_or_else(missing)?),
+        (Column::Bool(dst), Column::Bool(src)) => dst.push(src.get(index).ok_or_else(missing)?),
+        (Column::Str { offsets, data }, src @ Column::Str { .. }) => {
+            let value = src.str_value(index).ok_or_else(missing)?;
+            data.extend_from_slice(value.as_bytes());
+            offsets.push(data.len());
+        }
+        _ => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "leaf type does not match the column",
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::data::PlankData;
+
+    fn orders_type() -> PlankType {
+        PlankType::Struct(vec![
+            PlankField::new("id", PlankType::Int32),
+            PlankField::new(
+                "orders",
+                PlankType::List(Box::new(PlankType::Struct(vec![
+                    PlankField::new("sku", PlankType::Str),
+                    PlankField::new("tags", PlankType::List(Box::new(PlankType::Str))),
+                ]))),
+            ),
+        ])
+    }
+
+    fn order(sku: &str, tags: &[&str]) -> PlankData {
+        PlankData::Struct(vec![
+            PlankData::Str(sku.to_string()),
+            PlankData::List(tags.iter().map(|t| PlankData::Str(t.to_string())).collect()),
+        ])
+    }
+
+    #[test]
+    fn test_shred_and_assemble_nested_column() {
+        let field_type = orders_type();
+        let values = vec![
+            PlankData::Struct(vec![
+                PlankData::Int32(1),
+                PlankData::List(vec![order("a", &["x", "y"]), order("b", &[])]),
+            ]),
+            PlankData::Struct(vec![PlankData::Int32(2), PlankData::List(vec![])]),
+            PlankData::Struct(vec![
+                PlankData::Int32(3),
+                PlankData::List(vec![order("c", &["z"])]),
+            ]),
+        ];
+        let column = Column::from_values(&field_type, values.clone()).unwrap();
+
+        let leaves = shred(&column).unwrap();
+
+        assert_eq!(leaves.len(), 3);
+        assert_eq!(leaves[0].values, Column::Int32(vec![1, 2, 3]));
+        assert!(leaves[0].rep.is_empty() && leaves[0].def.is_empty());
+        // orders.sku: a, b, <empty orders>, c
+        assert_eq!(leaves[1].rep, vec![0, 1, 0, 0]);
+        assert_eq!(leaves[1].def, vec![1, 1, 0, 1]);
+        // orders.tags: x, y, <empty tags>, <empty orders>, z
+        assert_eq!(leaves[2].rep, vec![0, 2, 1, 0, 0]);
+        assert_eq!(leaves[2].def, vec![2, 2, 1, 0, 2]);
+        assert_eq!(leaves[2].values.len(), 3);
+
+        let assembled = assemble(&field_type, leaves, values.len()).unwrap();
+        assert_eq!(assembled, column);
+        assert_eq!(assembled.iter().collect::<Vec<_>>(), values);
+    }
+
+    #[test]
+    fn test_shred_and_assemble_nullable_column() {
+        let field_type = PlankType::Struct(vec![
+            PlankField::new("id", PlankType::Int32),
+            PlankField::new(
+                "tags",
+                PlankType::List(Box::new(PlankType::Str.nullable())).nullable(),
+            ),
+        ])
+        .nullable();
+        let tags = |tags: &[Option<&str>]| {
+            PlankData::List(
+                tags.iter()
+                    .map(|t| t.map_or(PlankData::Null, |t| PlankData::Str(t.to_string())))
+                    .collect(),
+            )
+        };
+        let values = vec![
+            PlankData::Struct(vec![PlankData::Int32(1), tags(&[Some("x"), None])]),
+            PlankData::Null,
+            PlankData::Struct(vec![PlankData::Int32(3), PlankData::Null]),
+            PlankData::Struct(vec![PlankData::Int32(4), tags(&[])]),
+        ];
+        let column = Column::from_values(&field_type, values.clone()).unwrap();
+
+        let leaves = shred(&column).unwrap();
+
+        // id: 1, <null struct>, 3, 4
+        assert_eq!(leaves[0].def, vec![1, 0, 1, 1]);
+        assert_eq!(leaves[0].values, Column::Int32(vec![1, 3, 4]));
+        // tags: x, <null tag>, <null struct>, <null tags>, <empty tags>
+        assert_eq!(leaves[1].rep, vec![0, 1, 0, 0, 0]);
+        assert_eq!(leaves[1].def, vec![4, 3, 0, 1, 2]);
+        assert_eq!(leaves[1].values.len(), 1);
+
+        let assembled = assemble(&field_type, leaves, values.len()).unwrap();
+        assert_eq!(assembled.iter().collect::<Vec<_>>(), values);
+
+        let schema = [PlankField::new("user", field_type)];
+        let (field, _) = project(&schema, "user.id").unwrap();
+        assert_eq!(field.field_type(), &PlankType::Int32.nullable());
+    }
+
+    #[test]
+    fn test_project_dotted_path() {
+        let schema = vec![
+            PlankField::new("name", PlankType::Str),
+            PlankField::new("user", orders_type()),
+        ];
+
+        let leaf_paths = leaves(&schema)
+            .unwrap()
+            .iter()
+            .map(|l| l.path().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            leaf_paths,
+            ["name", "user.id", "user.orders.sku", "user.orders.tags"]
+        );
+
+        let (field, range) = project(&schema, "user.orders.tags").unwrap();
+        assert_eq!(range, 3..4);
+        assert_eq!(
+            field.field_type(),
+            &PlankType::List(Box::new(PlankType::List(Box::new(PlankType::Str))))
+        );
+
+        assert_eq!(project(&schema, "user").unwrap().1, 1..4);
+        assert!(project(&schema, "user.missing").is_err());
+    }
+}
@@ -0,0 +1,275 @@
+use std::cmp::Ordering;
+
+use crate::file::predicate::{self, Predicate};
+use crate::file::rowgroup::column::Column;
+use crate::serde::{Deserialize, Serialize, read_u32};
+use crate::types::{data::PlankData, fields::PlankField, types::PlankType};
+
+/// A top-level primitive column rows are sorted by
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortColumn {
+    pub column: String,
+    pub descending: bool,
+}
+
+/// The columns a file is sorted by, with the first and last key of every row group
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SortOrder {
+    pub columns: Vec<SortColumn>,
+    pub(crate) row_groups: Vec<(Vec<PlankData>, Vec<PlankData>)>,
+}
+
+impl SortColumn {
+    pub fn asc(column: &str) -> Self {
+        SortColumn {
+            column: column.to_string(),
+            descending: false,
+        }
+    }
+
+    pub fn desc(column: &str) -> Self {
+        SortColumn {
+            column: column.to_string(),
+            descending: true,
+        }
+    }
+}
+
+impl SortOrder {
+    pub fn new(columns: Vec<SortColumn>) -> Self {
+        SortOrder {
+            columns,
+            row_groups: Vec::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
+    /// The indexes in `schema` of the sort columns, checking that each one can be ordered
+    pub(crate) fn key_indexes(&self, schema: &[PlankField]) -> std::io::Result<Vec<usize>> {
+        self.columns
+            .iter()
+            .map(|sort| {
+                let index = schema
+                    .iter()
+                    .position(|f| f.field_name() == &sort.column)
+                    .ok_or_else(|| {
+                        std::io::Error::new(
+                            std::io::ErrorKind::NotFound,
+                            format!("sort column {} not found", sort.column),
+                        )
+                    })?;
+                if matches!(
+                    schema[index].field_type(),
+                    PlankType::Struct(_) | PlankType::List(_) | PlankType::Map(_)
+                ) {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!("cannot sort by nested column {}", sort.column),
+                    ));
+                }
+                if matches!(schema[index].field_type(), PlankType::Nullable(_)) {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!("cannot sort by nullable column {}", sort.column),
+                    ));
+                }
+                Ok(index)
+            })
+            .collect()
+    }
+
+    /// Checks that `key` holds at most one comparable value per sort column
+    pub(crate) fn validate_key(
+        &self,
+        key: &[PlankData],
+        schema: &[PlankField],
+    ) -> std::io::Result<()> {
+        if key.len() > self.columns.len() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "key has {} values but the file is sorted by {} columns",
+                    key.len(),
+                    self.columns.len()
+                ),
+            ));
+        }
+        self.columns.iter().zip(key).try_for_each(|(sort, value)| {
+            Predicate::Eq(sort.column.clone(), value.clone()).validate(schema)
+        })
+    }
+
+    /// Orders two keys by their common prefix, so a shorter key matches every key it starts
+    pub(crate) fn compare(&self, a: &[PlankData], b: &[PlankData]) -> Ordering {
+        self.columns
+            .iter()
+            .zip(a.iter().zip(b))
+            .map(|(sort, (a, b))| {
+                let ordering = predicate::compare(a, b).unwrap_or(Ordering::Equal);
+                if sort.descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+
+    /// Checks that the rows of a row group follow the order on from `previous`, the last key
+    /// written before them, and returns their first and last key. `first_row` only numbers
+    /// rows in the error.
+    pub(crate) fn check_row_group(
+        &self,
+        columns: &[Column],
+        key_indexes: &[usize],
+        row_count: usize,
+        previous: Option<&[PlankData]>,
+        first_row: u64,
+    ) -> std::io::Result<(Vec<PlankData>, Vec<PlankData>)> {
+        let mut last = previous.map(<[PlankData]>::to_vec);
+        let mut first = None;
+        for row in 0..row_count {
+            let current = key(columns, key_indexes, row);
+            if let Some(last) = &last
+                && self.compare(last, &current).is_gt()
+            {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!(
+                        "row {} is out of the declared sort order",
+                        first_row + row as u64
+                    ),
+                ));
+            }
+            first.get_or_insert_with(|| current.clone());
+            last = Some(current);
+        }
+        Ok((first.unwrap_or_default(), last.unwrap_or_default()))
+    }
+}
+
+/// The first index below `len` for which `pred` is false, given it holds for every index
+/// before that one and none after
+pub(crate) fn partition_point(len: usize, pred: impl Fn(usize) -> bool) -> usize {
+    let (mut low, mut high) = (0, len);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if pred(mid) {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    low
+}
+
+/// The sort key of a row, from the columns at `key_indexes`
+pub(crate) fn key(columns: &[Column], key_indexes: &[usize], row: usize) -> Vec<PlankData> {
+    key_indexes
+        .iter()
+        .filter_map(|&i| columns[i].get(row))
+        .collect()
+}
+
+impl Serialize for SortOrder {
+    fn to_bytes(&self) -> std::io::Result<Vec<u8>> {
+        let mut v = Vec::new();
+        v.extend_from_slice(&(self.columns.len() as u32).to_le_bytes());
+        for sort in &self.columns {
+            v.extend_from_slice(&(sort.column.len() as u32).to_le_bytes());
+            v.extend_from_slice(sort.column.as_bytes());
+            v.push(sort.descending as u8);
+        }
+        for (first, last) in &self.row_groups {
+            for value in first.iter().chain(last) {
+                v.extend_from_slice(&value.to_bytes()?);
+            }
+        }
+        Ok(v)
+    }
+}
+
+impl<'a> Deserialize<'a> for SortOrder {
+    /// The file schema the sort columns are looked up in
+    type Schema = Vec<PlankField>;
+    /// The row group keys take up the rest of `bytes`
+    fn from_bytes(bytes: &[u8], schema: &'a Self::Schema) -> std::io::Result<(Self, usize)> {
+        if bytes.is_empty() {
+            return Ok((SortOrder::default(), 0));
+        }
+
+        let count = read_u32(bytes, 0)? as usize;
+        let mut pos = 4;
+        let mut order = SortOrder::default();
+        for _ in 0..count {
+            let size = read_u32(bytes, pos)? as usize;
+            pos += 4;
+            let column = bytes
+                .get(pos..pos + size)
+                .and_then(|b| std::str::from_utf8(b).ok())
+                .ok_or_else(|| {
+                    std::io::Error::new(std::io::ErrorKind::InvalidData, "expected a sort column")
+                })?
+                .to_string();
+            pos += size;
+            let descending = bytes.get(pos).is_some_and(|&b| b == 1);
+            pos += 1;
+            order.columns.push(SortColumn { column, descending });
+        }
+
+        let types = order
+            .key_indexes(schema)?
+            .into_iter()
+            .map(|i| schema[i].field_type())
+            .collect::<Vec<_>>();
+        while pos < bytes.len() && !types.is_empty() {
+            let mut keys = [Vec::new(), Vec::new()];
+            for key in &mut keys {
+                for value_type in &types {
+                    let (value, size) = PlankData::from_bytes(&bytes[pos..], value_type)?;
+                    pos += size;
+                    key.push(value);
+                }
+            }
+            let [first, last] = keys;
+            order.row_groups.push((first, last));
+        }
+
+        Ok((order, pos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_and_roundtrip_sort_order() {
+        let schema = vec![
+            PlankField::new("country", PlankType::Str),
+            PlankField::new("ts", PlankType::Int64),
+        ];
+        let mut order = SortOrder::new(vec![SortColumn::asc("country"), SortColumn::desc("ts")]);
+        let key =
+            |country: &str, ts| vec![PlankData::Str(country.to_string()), PlankData::Int64(ts)];
+
+        assert!(order.compare(&key("AT", 5), &key("DE", 1)).is_lt());
+        assert!(order.compare(&key("DE", 5), &key("DE", 1)).is_lt());
+        assert!(
+            order
+                .compare(&key("DE", 5), &[PlankData::Str("DE".to_string())])
+                .is_eq()
+        );
+        assert!(order.validate_key(&[PlankData::Int32(1)], &schema).is_err());
+
+        order.row_groups = vec![(key("AT", 9), key("DE", 2)), (key("DE", 1), key("FR", 0))];
+        let bytes = order.to_bytes().unwrap();
+        let (decoded, size) = SortOrder::from_bytes(&bytes, &schema).unwrap();
+        assert_eq!(size, bytes.len());
+        assert_eq!(decoded, order);
+    }
+}
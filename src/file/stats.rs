@@ -0,0 +1,222 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::ops::Range;
+
+use crate::file::rowgroup::column::{Column, page::Pages};
+use crate::serde::{Deserialize, Serialize, read_u64};
+use crate::types::{data::PlankData, types::PlankType};
+
+/// Statistics of one leaf column chunk of a row group
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ColumnStats {
+    /// Smallest value, `None` when the chunk holds no values
+    pub min: Option<PlankData>,
+    pub max: Option<PlankData>,
+    /// Entries without a value, such as empty lists above the leaf
+    pub null_count: u64,
+    /// Estimated number of distinct values
+    pub distinct_count: u64,
+    /// Size of the chunk's pages as stored, headers included
+    pub encoded_size: u64,
+    /// Size of the chunk's pages once decompressed
+    pub decoded_size: u64,
+}
+
+/// A HyperLogLog sketch over 2^10 registers, good to a few percent
+struct DistinctCounter {
+    registers: [u8; 1024],
+}
+
+impl ColumnStats {
+    /// Gathers the statistics of a leaf with `entries` level entries, its primitive `values`
+    /// and the chunk they were encoded into
+    pub(crate) fn collect(values: &Column, entries: usize, chunk: &[u8]) -> std::io::Result<Self> {
+        let mut decoded_size = 0;
+        for page in Pages::new(chunk) {
+            decoded_size += page?.header.uncompressed_size as u64;
+        }
+
+        let (min, max) = bounds(values, 0..values.len())?;
+        let mut distinct = DistinctCounter::new();
+        match values {
+            Column::Int32(v) => v.iter().for_each(|n| distinct.insert(n)),
+            Column::Int64(v) => v.iter().for_each(|n| distinct.insert(n)),
+            Column::Bool(v) => v.iter().for_each(|b| distinct.insert(&b)),
+            Column::Str { .. } => (0..values.len())
+                .filter_map(|i| values.str_value(i))
+                .for_each(|s| distinct.insert(s)),
+            Column::Struct { .. } | Column::List { .. } | Column::Nullable { .. } => {}
+        }
+
+        Ok(ColumnStats {
+            min,
+            max,
+            null_count: entries.saturating_sub(values.len()) as u64,
+            distinct_count: distinct.estimate().min(values.len() as u64),
+            encoded_size: chunk.len() as u64,
+            decoded_size,
+        })
+    }
+}
+
+/// The smallest and largest of the primitive values in `range`
+pub(crate) fn bounds(
+    values: &Column,
+    range: Range<usize>,
+) -> std::io::Result<(Option<PlankData>, Option<PlankData>)> {
+    Ok(match values {
+        Column::Int32(v) => (
+            v[range.clone()].iter().min().map(|&n| PlankData::Int32(n)),
+            v[range].iter().max().map(|&n| PlankData::Int32(n)),
+        ),
+        Column::Int64(v) => (
+            v[range.clone()].iter().min().map(|&n| PlankData::Int64(n)),
+            v[range].iter().max().map(|&n| PlankData::Int64(n)),
+        ),
+        Column::Bool(v) => {
+            let bools = range.filter_map(|i| v.get(i));
+            (
+                bools.clone().min().map(PlankData::Bool),
+                bools.max().map(PlankData::Bool),
+            )
+        }
+        Column::Str { .. } => {
+            let strings = range.filter_map(|i| values.str_value(i));
+            (
+                strings.clone().min().map(|s| PlankData::Str(s.to_string())),
+                strings.max().map(|s| PlankData::Str(s.to_string())),
+            )
+        }
+        Column::Struct { .. } | Column::List { .. } | Column::Nullable { .. } => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "statistics are kept per leaf",
+            ));
+        }
+    })
+}
+
+impl DistinctCounter {
+    fn new() -> Self {
+        DistinctCounter {
+            registers: [0; 1024],
+        }
+    }
+
+    fn insert<T: Hash + ?Sized>(&mut self, value: &T) {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        // The top ten bits pick the register, the rest give the run of leading zeros
+        let register = &mut self.registers[(hash >> 54) as usize];
+        let rank = ((hash << 10).leading_zeros() + 1).min(55) as u8;
+        *register = (*register).max(rank);
+    }
+
+    fn estimate(&self) -> u64 {
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let estimate = alpha * m * m / sum;
+
+        // Linear counting is more accurate while many registers are still empty
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            (m * (m / zeros as f64).ln()).round() as u64
+        } else {
+            estimate.round() as u64
+        }
+    }
+}
+
+impl Serialize for ColumnStats {
+    fn to_bytes(&self) -> std::io::Result<Vec<u8>> {
+        let mut v = Vec::new();
+        v.extend_from_slice(&self.null_count.to_le_bytes());
+        v.extend_from_slice(&self.distinct_count.to_le_bytes());
+        v.extend_from_slice(&self.encoded_size.to_le_bytes());
+        v.extend_from_slice(&self.decoded_size.to_le_bytes());
+
+        match (&self.min, &self.max) {
+            (Some(min), Some(max)) => {
+                v.push(1);
+                v.extend_from_slice(&min.to_bytes()?);
+                v.extend_from_slice(&max.to_bytes()?);
+            }
+            _ => v.push(0),
+        }
+
+        Ok(v)
+    }
+}
+
+impl<'a> Deserialize<'a> for ColumnStats {
+    /// The leaf type the bounds are decoded as
+    type Schema = PlankType;
+    fn from_bytes(bytes: &[u8], schema: &'a Self::Schema) -> std::io::Result<(Self, usize)> {
+        let mut stats = ColumnStats {
+            null_count: read_u64(bytes, 0)?,
+            distinct_count: read_u64(bytes, 8)?,
+            encoded_size: read_u64(bytes, 16)?,
+            decoded_size: read_u64(bytes, 24)?,
+            ..Default::default()
+        };
+
+        let mut pos = 33;
+        match bytes.get(32) {
+            Some(0) => {}
+            Some(1) => {
+                let (min, size) = PlankData::from_bytes(&bytes[pos..], schema)?;
+                pos += size;
+                let (max, size) = PlankData::from_bytes(&bytes[pos..], schema)?;
+                pos += size;
+                stats.min = Some(min);
+                stats.max = Some(max);
+            }
+            _ => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "expected column statistics bounds",
+                ));
+            }
+        }
+
+        Ok((stats, pos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::codec::Codec;
+
+    #[test]
+    fn test_collect_and_roundtrip_stats() {
+        let values = Column::from_values(
+            &PlankType::Int64,
+            (0..1000).map(|i| PlankData::Int64(i % 300)),
+        )
+        .unwrap();
+        let chunk = values.encode(Codec::Zlib, 1024).unwrap();
+
+        let stats = ColumnStats::collect(&values, 1002, &chunk).unwrap();
+
+        assert_eq!(stats.min, Some(PlankData::Int64(0)));
+        assert_eq!(stats.max, Some(PlankData::Int64(299)));
+        assert_eq!(stats.null_count, 2);
+        assert!((285..=315).contains(&stats.distinct_count));
+        assert_eq!(stats.encoded_size, chunk.len() as u64);
+        assert_eq!(stats.decoded_size, 8000);
+
+        let bytes = stats.to_bytes().unwrap();
+        let (decoded, size) = ColumnStats::from_bytes(&bytes, &PlankType::Int64).unwrap();
+        assert_eq!(size, bytes.len());
+        assert_eq!(decoded, stats);
+
+        let empty = Column::new(&PlankType::Str);
+        let stats = ColumnStats::collect(&empty, 0, &[]).unwrap();
+        let (decoded, _) =
+            ColumnStats::from_bytes(&stats.to_bytes().unwrap(), &PlankType::Str).unwrap();
+        assert_eq!(decoded.min, None);
+    }
+}
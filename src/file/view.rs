@@ -0,0 +1,341 @@
+use std::borrow::Cow;
+use std::cell::OnceCell;
+
+use crate::file::rowgroup::column::page::Pages;
+use crate::file::rowgroup::shred::Leaf;
+use crate::serde::{read_u32, read_u64};
+use crate::types::{data::PlankData, fields::PlankField, types::PlankType};
+
+/// A row group whose leaf columns are left encoded and read in place.
+///
+/// With a memory-mapped reader, uncompressed column chunks are borrowed straight from the
+/// mapping, so scanning them allocates nothing.
+#[derive(Debug)]
+pub struct RecordBatchView<'a> {
+    pub schema: &'a [PlankField],
+    /// One view per leaf of the schema
+    pub columns: Vec<ColumnView<'a>>,
+    pub row_count: u32,
+}
+
+/// The encoded pages of one leaf column chunk
+#[derive(Debug)]
+pub struct ColumnView<'a> {
+    leaf: &'a Leaf,
+    chunk: Cow<'a, [u8]>,
+    /// Every page, filled in on the first scan
+    decoded: OnceCell<Vec<DecodedPage<'a>>>,
+}
+
+/// The number of values of a page and their plain encoding, with the levels cut off
+type DecodedPage<'a> = (usize, Cow<'a, [u8]>);
+
+/// A primitive value borrowed from the bytes of a column chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueRef<'a> {
+    Str(&'a str),
+    Int32(i32),
+    Int64(i64),
+    Bool(bool),
+}
+
+/// Iterates over the values of every page of a column chunk
+pub struct ColumnValues<'a> {
+    pages: std::slice::Iter<'a, DecodedPage<'a>>,
+    value_type: &'a PlankType,
+    current: Option<Values<'a>>,
+}
+
+/// Iterates over consecutive encoded values
+pub struct Values<'a> {
+    bytes: &'a [u8],
+    value_type: &'a PlankType,
+    remaining: usize,
+}
+
+impl<'a> RecordBatchView<'a> {
+    /// The view of the leaf at a dotted path such as `user.address.city`
+    pub fn column(&self, path: &str) -> Option<&ColumnView<'a>> {
+        self.columns.iter().find(|c| c.leaf.path() == path)
+    }
+}
+
+impl<'a> ColumnView<'a> {
+    pub(crate) fn new(leaf: &'a Leaf, chunk: Cow<'a, [u8]>) -> Self {
+        ColumnView {
+            leaf,
+            chunk,
+            decoded: OnceCell::new(),
+        }
+    }
+
+    pub fn leaf(&self) -> &'a Leaf {
+        self.leaf
+    }
+
+    /// The leaf's dotted path and primitive type
+    pub fn field(&self) -> &'a PlankField {
+        self.leaf.field()
+    }
+
+    /// The number of values in the chunk. Empty lists above the leaf hold none.
+    pub fn len(&self) -> std::io::Result<usize> {
+        Ok(self.decoded()?.iter().map(|(count, _)| count).sum())
+    }
+
+    pub fn is_empty(&self) -> std::io::Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// The pages of the chunk in order. Only the headers are read, so pages can be skipped
+    /// without decompressing them.
+    pub fn pages(&self) -> Pages<'_> {
+        Pages::new(&self.chunk)
+    }
+
+    fn decoded(&self) -> std::io::Result<&[DecodedPage<'a>]> {
+        if let Some(decoded) = self.decoded.get() {
+            return Ok(decoded);
+        }
+
+        // Uncompressed pages of a borrowed chunk stay borrowed
+        let decoded = match &self.chunk {
+            Cow::Borrowed(chunk) => Pages::new(chunk)
+                .map(|page| {
+                    let page = page?;
+                    match page.decompress()? {
+                        Cow::Borrowed(bytes) => {
+                            let (count, start) =
+                                self.skip_levels(bytes, page.header.value_count)?;
+                            let values = bytes.get(start..).ok_or_else(|| {
+                                std::io::Error::new(
+                                    std::io::ErrorKind::InvalidData,
+                                    "page levels are truncated",
+                                )
+                            })?;
+                            Ok((count, Cow::Borrowed(values)))
+                        }
+                        Cow::Owned(bytes) => self.owned_page(bytes, page.header.value_count),
+                    }
+                })
+                .collect::<std::io::Result<Vec<_>>>()?,
+            Cow::Owned(chunk) => Pages::new(chunk)
+                .map(|page| {
+                    let page = page?;
+                    self.owned_page(page.decompress()?.into_owned(), page.header.value_count)
+                })
+                .collect::<std::io::Result<Vec<_>>>()?,
+        };
+
+        Ok(self.decoded.get_or_init(|| decoded))
+    }
+
+    fn owned_page(&self, mut bytes: Vec<u8>, entries: u32) -> std::io::Result<DecodedPage<'a>> {
+        let (count, start) = self.skip_levels(&bytes, entries)?;
+        if start > bytes.len() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "page levels are truncated",
+            ));
+        }
+        bytes.drain(..start);
+        Ok((count, Cow::Owned(bytes)))
+    }
+
+    /// Returns the number of values in a page and where they start after its levels
+    fn skip_levels(&self, bytes: &[u8], entries: u32) -> std::io::Result<(usize, usize)> {
+        let entries = entries as usize;
+        let max_def = self.leaf.max_definition_level();
+        let mut start = 0;
+        if self.leaf.max_repetition_level() > 0 {
+            start += entries;
+        }
+        if max_def == 0 {
+            return Ok((entries, start));
+        }
+
+        let def = bytes.get(start..start + entries).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, "page levels are truncated")
+        })?;
+        Ok((
+            def.iter().filter(|&&d| d == max_def).count(),
+            start + entries,
+        ))
+    }
+
+    /// Whether the values point into the reader's source rather than a decompressed copy
+    pub fn is_borrowed(&self) -> std::io::Result<bool> {
+        Ok(self
+            .decoded()?
+            .iter()
+            .all(|(_, bytes)| matches!(bytes, Cow::Borrowed(_))))
+    }
+
+    /// Iterates over the values of all pages. Compressed pages are decompressed on the first
+    /// call and kept for later ones. Levels are skipped, so a leaf under lists yields its
+    /// values without the list boundaries.
+    pub fn values(&self) -> std::io::Result<ColumnValues<'_>> {
+        Ok(ColumnValues {
+            pages: self.decoded()?.iter(),
+            value_type: self.leaf.field().field_type(),
+            current: None,
+        })
+    }
+}
+
+impl<'a> ValueRef<'a> {
+    /// Reads one value from the start of `bytes` and returns it with the number of bytes it
+    /// occupied
+    pub(crate) fn decode(
+        bytes: &'a [u8],
+        value_type: &PlankType,
+    ) -> std::io::Result<(ValueRef<'a>, usize)> {
+        match value_type {
+            PlankType::Str => {
+                let size = read_u32(bytes, 0)? as usize;
+                let s = bytes
+                    .get(4..4 + size)
+                    .and_then(|b| std::str::from_utf8(b).ok())
+                    .ok_or_else(|| {
+                        std::io::Error::new(
+                            std::io::ErrorKind::InvalidData,
+                            format!("expected to read {} bytes", size),
+                        )
+                    })?;
+                Ok((ValueRef::Str(s), 4 + size))
+            }
+            PlankType::Int32 => Ok((ValueRef::Int32(read_u32(bytes, 0)? as i32), 4)),
+            PlankType::Int64 => Ok((ValueRef::Int64(read_u64(bytes, 0)? as i64), 8)),
+            PlankType::Bool => match bytes.first() {
+                Some(0) => Ok((ValueRef::Bool(false), 1)),
+                Some(1) => Ok((ValueRef::Bool(true), 1)),
+                _ => Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "expected bool",
+                )),
+            },
+            PlankType::Struct(_)
+            | PlankType::List(_)
+            | PlankType::Nullable(_)
+            | PlankType::Map(_) => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "nested values are stored as leaves",
+            )),
+        }
+    }
+
+    /// Copies the value into an owned `PlankData`
+    pub fn to_data(&self) -> PlankData {
+        match self {
+            ValueRef::Str(s) => PlankData::Str(s.to_string()),
+            ValueRef::Int32(n) => PlankData::Int32(*n),
+            ValueRef::Int64(n) => PlankData::Int64(*n),
+            ValueRef::Bool(b) => PlankData::Bool(*b),
+        }
+    }
+}
+
+impl<'a> Iterator for ColumnValues<'a> {
+    type Item = std::io::Result<ValueRef<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(value) = self.current.as_mut().and_then(|values| values.next()) {
+                return Some(value);
+            }
+            let (value_count, bytes) = self.pages.next()?;
+            self.current = Some(Values {
+                bytes,
+                value_type: self.value_type,
+                remaining: *value_count,
+            });
+        }
+    }
+}
+
+impl<'a> Iterator for Values<'a> {
+    type Item = std::io::Result<ValueRef<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        match ValueRef::decode(self.bytes, self.value_type) {
+            Ok((value, size)) => {
+                self.bytes = &self.bytes[size..];
+                Some(Ok(value))
+            }
+            Err(e) => {
+                self.remaining = 0;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::codec::Codec;
+    use crate::file::rowgroup::column::{Column, page::Page};
+    use crate::file::rowgroup::shred;
+
+    #[test]
+    fn test_column_view_values() {
+        let schema = vec![PlankField::new(
+            "person",
+            PlankType::Struct(vec![
+                PlankField::new("name", PlankType::Str),
+                PlankField::new("scores", PlankType::List(Box::new(PlankType::Int32))),
+            ]),
+        )];
+        let values = vec![
+            PlankData::Struct(vec![
+                PlankData::Str("me".to_string()),
+                PlankData::List(vec![PlankData::Int32(1), PlankData::Int32(2)]),
+            ]),
+            PlankData::Struct(vec![
+                PlankData::Str("you".to_string()),
+                PlankData::List(vec![]),
+            ]),
+        ];
+        let column = Column::from_values(schema[0].field_type(), values).unwrap();
+        let leaves = shred::leaves(&schema).unwrap();
+        let scores = &shred::shred(&column).unwrap()[1];
+
+        // A tiny page size puts every row on a page of its own
+        let (chunk, _) = scores
+            .values
+            .encode_leaf(
+                &scores.rep,
+                &scores.def,
+                scores.max_def,
+                Codec::Uncompressed,
+                1,
+            )
+            .unwrap();
+
+        let view = ColumnView::new(&leaves[1], Cow::Borrowed(&chunk));
+
+        assert_eq!(view.field().field_name(), "person.scores");
+        assert_eq!(view.pages().count(), 2);
+        assert!(view.is_borrowed().unwrap());
+        assert_eq!(view.len().unwrap(), 2);
+        let decoded = view
+            .values()
+            .unwrap()
+            .map(|v| v.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(decoded, [ValueRef::Int32(1), ValueRef::Int32(2)]);
+
+        // A page claiming more levels than it holds is an error, borrowed or not
+        let mut short = Vec::new();
+        Page::write(&[1, 1], 5, Codec::Uncompressed, &mut short).unwrap();
+        for chunk in [Cow::Borrowed(&short[..]), Cow::Owned(short.clone())] {
+            let view = ColumnView::new(&leaves[1], chunk);
+            assert!(view.len().is_err());
+        }
+    }
+}
@@ -1,123 +1,1714 @@
-use itertools::Itertools;
-use std::fs::File;
-use std::io::{BufRead, BufWriter, Read, Seek, SeekFrom, Write};
-use std::path::Path;
-
-use crate::file::footer::{self, Footer};
-use crate::file::rowgroup::column::Column;
-use crate::file::rowgroup::RowGroup;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+use crate::file::bloom::DEFAULT_FPP;
+
+use crate::file::codec::Codec;
+use crate::file::config::{CREATED_BY, WriterConfig, WriterOptions};
+use crate::file::footer::{BlobLocation, Footer};
+use crate::file::format::{FORMAT_VERSION, FeatureFlags, MAGIC, Trailer};
+use crate::file::ingest::{
+    self, BadRecordPolicy, BadRecords, CsvOptions, INFER_RECORDS, IngestError, IngestReport,
+    TextEncoding,
+};
+use crate::file::reader::{PlankReader, RecordBatch};
+use crate::file::rowgroup::column::{
+    Column,
+    page::{DEFAULT_PAGE_SIZE, Encoding},
+};
+use crate::file::rowgroup::{EncodedRowGroup, RowGroup, shred};
+use crate::file::sort::{SortColumn, SortOrder};
+use crate::file::stats::ColumnStats;
 use crate::serde::Serialize;
+use crate::types::json;
+use crate::types::record::{self, PlankRecord};
 use crate::types::{data::PlankData, fields::PlankField, types::PlankType};
 
-pub struct PlankWriter {
+/// Writes a plank file. `PlankWriter<T>`, made with `create`, also writes records of `T` as
+/// rows.
+pub struct PlankWriter<T = ()> {
     file: BufWriter<File>,
+    codec: Codec,
+    page_size: usize,
+    options: WriterOptions,
+    /// Columns to build bloom filters for, with their false positive rate
+    bloom_filters: Vec<(String, f64)>,
+    sort_order: Vec<SortColumn>,
+    metadata: BTreeMap<String, Vec<u8>>,
+    csv_options: CsvOptions,
+    bad_records: BadRecordPolicy,
+    /// The schema of the rows given to `write_row`, set by `with_schema`
+    schema: Option<Vec<PlankField>>,
+    /// Rows serialized by a writer without a schema, until they give one
+    sample: Option<json::Sample>,
+    /// The file being written row by row, set up with the first row
+    state: Option<WriteState>,
+    /// The existing file rows are added to, set by `open_append`
+    append: Option<Append>,
+    record: PhantomData<fn(&T)>,
+}
+
+/// An existing file rows are appended to. Its row groups are copied to a spill file next to
+/// it, followed by the new ones and the new footer, and the spill file replaces it in
+/// `finish`, so a writer stopped before leaves the file as it was.
+struct Append {
+    path: PathBuf,
+    spill: PathBuf,
+    /// Where the old footer starts, and so where the new row groups go
+    footer_offset: u64,
+    /// The footer of the file, taken up by the first row group written
+    footer: Option<Footer>,
+}
+
+/// The rows buffered for the next row group, and what the footer keeps of the row groups
+/// already written
+struct WriteState {
+    schema: Vec<PlankField>,
+    /// Leaves to build bloom filters for, with their false positive rate
+    bloom_filters: Vec<(usize, f64)>,
+    key_indexes: Vec<usize>,
+    sort_order: SortOrder,
+    columns: Vec<Column>,
+    buffered: u32,
+    row_count: u64,
+    offsets: Vec<u64>,
+    first_rows: Vec<u64>,
+    stats: Vec<Vec<ColumnStats>>,
+    filters: Vec<BlobLocation>,
+    page_indexes: Vec<BlobLocation>,
+    column_chunks: Vec<BlobLocation>,
 }
 
 impl PlankWriter {
     pub fn new<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
-        let mut file = File::create(path)?;
-        Ok(Self {
-            file: BufWriter::new(file),
-        })
+        Self::create_file(path)
     }
 
-    fn write_rowgroup(&mut self, rg: &RowGroup) -> std::io::Result<u32> {
-        let rg_bytes = rg.to_bytes()?;
-        self.file
-            .write_all(&(rg_bytes.len() as u32).to_le_bytes())?;
-        self.file.write_all(&rg_bytes);
-        self.file.stream_position()?.try_into().map_err(|_| {
-            std::io::Error::new(
+    /// Opens an existing file to add rows to it, given like those of a writer created with
+    /// `with_schema` or converted from CSV or NDJSON, and checked against the schema of the
+    /// file. New row groups are written where the footer of the file starts, then `finish`
+    /// writes a footer for all of them.
+    ///
+    /// The codec, page size, row group limits, bloom filters, sort order and metadata of the
+    /// file are kept unless set again. Rows go to a copy of the file that `finish` renames
+    /// over it, so until then, or after a crash, it reads as it was. Files with footer
+    /// sections of a newer writer are refused, as their footer could not be kept whole.
+    pub fn open_append<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Self::append_file(path.as_ref())
+    }
+
+    /// Creates a writer for rows of `schema`, given with `write_row`, `write_rows` or
+    /// `write_batch`. Rows are buffered into row groups, and `finish` writes the last one and
+    /// the footer. A file dropped before `finish` has no footer and cannot be read.
+    pub fn with_schema<P: AsRef<Path>>(path: P, schema: Vec<PlankField>) -> std::io::Result<Self> {
+        shred::leaves(&schema)?;
+        let mut writer = Self::new(path)?;
+        writer.schema = Some(schema);
+        Ok(writer)
+    }
+}
+
+impl<T: PlankRecord> PlankWriter<T> {
+    /// Creates a writer for records of `T`, stored with the schema `T` derives. Records are
+    /// buffered into row groups like rows given to `write_row`.
+    pub fn create<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let schema = T::schema();
+        shred::leaves(&schema)?;
+        let mut writer = Self::create_file(path)?;
+        writer.schema = Some(schema);
+        Ok(writer)
+    }
+
+    /// Opens an existing file to add records of `T` to it, see `open_append`. The schema of
+    /// the file must be the one `T` derives.
+    pub fn open_append_records<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let writer = Self::append_file(path.as_ref())?;
+        record::check_schema(&T::schema(), writer.schema.as_deref().unwrap_or_default())?;
+        Ok(writer)
+    }
+
+    /// Buffers a record as a row, see `write_row`
+    pub fn write(&mut self, record: &T) -> std::io::Result<()> {
+        let mut state = self.take_state()?;
+        let result = self.buffer_row(&mut state, record.to_row());
+        self.state = Some(state);
+        result
+    }
+
+    /// Buffers every record of `records`, see `write`
+    pub fn write_records<'r>(
+        &mut self,
+        records: impl IntoIterator<Item = &'r T>,
+    ) -> std::io::Result<()>
+    where
+        T: 'r,
+    {
+        records
+            .into_iter()
+            .try_for_each(|record| self.write(record))
+    }
+}
+
+impl<T> PlankWriter<T> {
+    fn create_file<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(&MAGIC)?;
+        Ok(Self::from_file(file))
+    }
+
+    /// Sets up appending to the file at `path`, writing to its spill file
+    fn append_file(path: &Path) -> std::io::Result<Self> {
+        let footer_offset = Trailer::read(&mut File::open(path)?)?.footer_offset;
+        let footer = PlankReader::open(path)?.into_footer();
+        if !footer.unknown_sections.is_empty() {
+            return Err(std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
-                "offset does not fit into u32",
-            )
+                format!(
+                    "cannot append to {}, its footer has sections {:?} of a newer writer",
+                    path.display(),
+                    footer.unknown_sections
+                ),
+            ));
+        }
+
+        let mut spill = path.as_os_str().to_owned();
+        spill.push(".append");
+        let spill = PathBuf::from(spill);
+        std::fs::copy(path, &spill)?;
+        let mut file = OpenOptions::new().read(true).write(true).open(&spill)?;
+        file.set_len(footer_offset)?;
+        file.seek(SeekFrom::End(0))?;
+        let mut writer = Self::from_file(BufWriter::new(file));
+
+        if let Some(config) = footer.writer_config() {
+            writer.codec = config.codec;
+            writer.page_size = config.page_size as usize;
+            writer.options.row_group_rows = config.row_group_rows as usize;
+            if let Some(bytes) = config.row_group_bytes {
+                writer.options.row_group_bytes = bytes as usize;
+            }
+            if let Some(bytes) = config.max_memory {
+                writer.options.max_memory = bytes as usize;
+            }
+        }
+        // Filters keep the rate they were built with, the default when it is not recorded
+        let rates = footer
+            .writer_config()
+            .map(|config| config.bloom_filters.as_slice())
+            .unwrap_or_default();
+        let leaves = shred::leaves(&footer.schema)?;
+        writer.bloom_filters = footer
+            .bloom_filters
+            .iter()
+            .map(|filter| filter.leaf as usize)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .filter_map(|leaf| {
+                let column = leaves.get(leaf)?.path();
+                let fpp = rates
+                    .iter()
+                    .find(|(c, _)| c == column)
+                    .map_or(DEFAULT_FPP, |(_, fpp)| *fpp);
+                Some((column.to_string(), fpp))
+            })
+            .collect();
+        writer.sort_order = footer.sort_order.columns.clone();
+        writer.metadata = footer.metadata.clone();
+        writer.schema = Some(footer.schema.clone());
+        writer.append = Some(Append {
+            path: path.to_path_buf(),
+            spill,
+            footer_offset,
+            footer: Some(footer),
+        });
+        Ok(writer)
+    }
+
+    fn from_file(file: BufWriter<File>) -> Self {
+        Self {
+            file,
+            codec: Codec::default(),
+            page_size: DEFAULT_PAGE_SIZE,
+            options: WriterOptions::default(),
+            bloom_filters: Vec::new(),
+            sort_order: Vec::new(),
+            metadata: BTreeMap::new(),
+            csv_options: CsvOptions::default(),
+            bad_records: BadRecordPolicy::default(),
+            schema: None,
+            sample: None,
+            state: None,
+            append: None,
+            record: PhantomData,
+        }
+    }
+
+    /// Sets the codec used for column pages. `Codec::Uncompressed` allows memory-mapped
+    /// readers to scan values without copying them.
+    pub fn with_codec(mut self, codec: Codec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// Sets the target byte size of a page. Column chunks are split into pages of about this
+    /// size, each compressed on its own, so readers can stream and skip them.
+    pub fn with_page_size(mut self, page_size: usize) -> Self {
+        self.page_size = page_size;
+        self
+    }
+
+    /// Sets the limits row groups are cut at, see `WriterOptions`
+    pub fn with_options(mut self, options: WriterOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Builds a bloom filter for every chunk of a column, so readers can rule out row groups
+    /// on point lookups. `column` is a top-level primitive column or the dotted path of a leaf.
+    /// `fpp` is the rate of false positives the filters are sized for, see `DEFAULT_FPP`.
+    pub fn with_bloom_filter(mut self, column: &str, fpp: f64) -> Self {
+        self.bloom_filters.retain(|(c, _)| c != column);
+        self.bloom_filters.push((column.to_string(), fpp));
+        self
+    }
+
+    /// Declares that rows come sorted by `columns`, top-level primitive columns compared in
+    /// turn. Rows are checked as they are written and the order is kept in the footer, so
+    /// readers can binary-search by key.
+    pub fn with_sort_order(mut self, columns: Vec<SortColumn>) -> Self {
+        self.sort_order = columns;
+        self
+    }
+
+    /// Stores a key/value pair in the footer, replacing any earlier value of `key`
+    pub fn with_metadata(mut self, key: &str, value: impl Into<Vec<u8>>) -> Self {
+        self.metadata.insert(key.to_string(), value.into());
+        self
+    }
+
+    /// Sets how `write_from_csv` and `write_from_csv_reader` read their input, see
+    /// `CsvOptions`
+    pub fn with_csv_options(mut self, options: CsvOptions) -> Self {
+        self.csv_options = options;
+        self
+    }
+
+    /// Sets what becomes of input records that cannot be written, see `BadRecordPolicy`
+    pub fn with_bad_records(mut self, policy: BadRecordPolicy) -> Self {
+        self.bad_records = policy;
+        self
+    }
+
+    /// Buffers a row, writing a row group once enough rows are buffered. The row must hold a
+    /// value of the right type for every column of the schema given to `with_schema`.
+    pub fn write_row(&mut self, row: &[PlankData]) -> std::io::Result<()> {
+        let mut state = self.take_state()?;
+        let result = self.buffer_row(&mut state, row.to_vec());
+        self.state = Some(state);
+        result
+    }
+
+    /// Buffers every row of `rows`, see `write_row`
+    pub fn write_rows<R: AsRef<[PlankData]>>(
+        &mut self,
+        rows: impl IntoIterator<Item = R>,
+    ) -> std::io::Result<()> {
+        rows.into_iter()
+            .try_for_each(|row| self.write_row(row.as_ref()))
+    }
+
+    /// Buffers any value serializing to a struct or map as a row, its fields matched to
+    /// columns by name. A writer created with `new` takes its schema from the values, typed
+    /// as in `schema_of`: values are kept until each field has been other than `None` or an
+    /// empty sequence, or 1024 of them are, leaving such fields strings. Values of a type
+    /// other than their column's are errors.
+    pub fn serialize_row<S: ::serde::Serialize>(&mut self, value: &S) -> std::io::Result<()> {
+        if self.schema.is_none() && self.state.is_none() {
+            let sample = self.sample.get_or_insert_with(json::Sample::default);
+            sample.push(value)?;
+            if sample.is_settled() {
+                self.state = Some(self.take_state()?);
+            }
+            return Ok(());
+        }
+        let mut state = self.take_state()?;
+        let result =
+            json::to_row(value, &state.schema).and_then(|row| self.buffer_row(&mut state, row));
+        self.state = Some(state);
+        result
+    }
+
+    /// Buffers every value of `values`, see `serialize_row`
+    pub fn serialize_rows<S: ::serde::Serialize>(
+        &mut self,
+        values: impl IntoIterator<Item = S>,
+    ) -> std::io::Result<()> {
+        values
+            .into_iter()
+            .try_for_each(|value| self.serialize_row(&value))
+    }
+
+    /// Buffers the rows of a batch, which must have the schema given to `with_schema`
+    pub fn write_batch(&mut self, batch: RecordBatch) -> std::io::Result<()> {
+        let mut state = self.take_state()?;
+        let result = if batch.schema != state.schema {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "batch schema does not match the schema of the file",
+            ))
+        } else {
+            (0..batch.row_count as usize).try_for_each(|i| {
+                let row = batch
+                    .columns
+                    .iter()
+                    .map(|column| column.get(i))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| {
+                        std::io::Error::new(
+                            std::io::ErrorKind::InvalidData,
+                            "batch column is too short",
+                        )
+                    })?;
+                self.buffer_row(&mut state, row)
+            })
+        };
+        self.state = Some(state);
+        result
+    }
+
+    /// Writes the buffered rows as a last row group, then the footer, and returns the footer
+    pub fn finish(mut self) -> std::io::Result<Footer> {
+        let mut state = self.take_state()?;
+        self.write_buffered(&mut state)?;
+        self.write_end(state)
+    }
+
+    /// Sets up writing rows of `schema` with the bloom filters and sort order configured
+    fn begin(&self, schema: Vec<PlankField>) -> std::io::Result<WriteState> {
+        let leaves = shred::leaves(&schema)?;
+        let bloom_filters = self
+            .bloom_filters
+            .iter()
+            .map(|(column, fpp)| {
+                let leaf = leaves
+                    .iter()
+                    .position(|leaf| leaf.path() == column)
+                    .ok_or_else(|| {
+                        std::io::Error::new(
+                            std::io::ErrorKind::InvalidInput,
+                            format!("no leaf column {} to build a bloom filter for", column),
+                        )
+                    })?;
+                Ok((leaf, *fpp))
+            })
+            .collect::<std::io::Result<Vec<_>>>()?;
+
+        let sort_order = SortOrder::new(self.sort_order.clone());
+        let key_indexes = sort_order.key_indexes(&schema)?;
+
+        Ok(WriteState {
+            columns: WriteState::empty_columns(&schema),
+            schema,
+            bloom_filters,
+            key_indexes,
+            sort_order,
+            buffered: 0,
+            row_count: 0,
+            offsets: Vec::new(),
+            first_rows: Vec::new(),
+            stats: Vec::new(),
+            filters: Vec::new(),
+            page_indexes: Vec::new(),
+            column_chunks: Vec::new(),
         })
     }
 
-    fn write_footer(&mut self, footer: &Footer) -> std::io::Result<()> {
-        let before: u32 = self.file.stream_position()?.try_into().map_err(|_| {
+    /// Takes the state of the file being written row by row, setting it up on first use
+    fn take_state(&mut self) -> std::io::Result<WriteState> {
+        if let Some(state) = self.state.take() {
+            return Ok(state);
+        }
+        let sample = self.sample.take();
+        if let Some(sample) = &sample {
+            self.schema = Some(sample.schema());
+        }
+        let schema = self.schema.clone().ok_or_else(|| {
             std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                "offset does not fit into u32",
+                std::io::ErrorKind::InvalidInput,
+                "writer has no schema, create it with PlankWriter::with_schema",
             )
         })?;
-        self.file.write_all(&footer.to_bytes()?)?;
-        self.file.write_all(&before.to_le_bytes())?;
+        let mut state = self.begin(schema)?;
+        if let Some(footer) = self.append.as_mut().and_then(|append| append.footer.take()) {
+            state.resume(footer)?;
+        }
+        if let Some(sample) = sample {
+            for row in sample.into_rows(&state.schema)? {
+                self.buffer_row(&mut state, row)?;
+            }
+        }
+        Ok(state)
+    }
+
+    /// The schema of rows already buffered, values kept by `serialize_row` settling theirs,
+    /// or else the one given to `with_schema` or read from the file appended to
+    fn known_schema(&mut self) -> std::io::Result<Option<Vec<PlankField>>> {
+        if self.sample.is_some() {
+            self.state = Some(self.take_state()?);
+        }
+        Ok(match &self.state {
+            Some(state) => Some(state.schema.clone()),
+            None => self.schema.clone(),
+        })
+    }
+
+    /// Takes the state to ingest rows of `schema` with, carrying on after rows already buffered
+    /// and the row groups of the file appended to
+    fn ingest_state(&mut self, schema: Vec<PlankField>) -> std::io::Result<WriteState> {
+        if self.state.is_none() {
+            self.schema = Some(schema);
+        }
+        self.take_state()
+    }
+
+    fn buffer_row(&mut self, state: &mut WriteState, row: Vec<PlankData>) -> std::io::Result<()> {
+        state.check_row(&row)?;
+        for (column, value) in state.columns.iter_mut().zip(row) {
+            column.push(value)?;
+        }
+        state.buffered += 1;
+        if self
+            .options
+            .is_full(&state.columns, state.buffered as usize)
+        {
+            self.write_buffered(state)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the buffered rows, if any, as a row group
+    fn write_buffered(&mut self, state: &mut WriteState) -> std::io::Result<()> {
+        if state.buffered == 0 {
+            return Ok(());
+        }
+        let columns =
+            std::mem::replace(&mut state.columns, WriteState::empty_columns(&state.schema));
+        let row_count = std::mem::take(&mut state.buffered);
+        self.write_row_group(state, columns, row_count)
+    }
+
+    /// Checks the rows against the sort order and writes them as the next row group, followed
+    /// by its bloom filters and page indexes. Only what the footer needs of it is kept.
+    fn write_row_group(
+        &mut self,
+        state: &mut WriteState,
+        columns: Vec<Column>,
+        row_count: u32,
+    ) -> std::io::Result<()> {
+        if !state.sort_order.is_empty() {
+            let bounds = state.sort_order.check_row_group(
+                &columns,
+                &state.key_indexes,
+                row_count as usize,
+                state
+                    .sort_order
+                    .row_groups
+                    .last()
+                    .map(|(_, last)| last.as_slice()),
+                state.row_count,
+            )?;
+            state.sort_order.row_groups.push(bounds);
+        }
+
+        let id = state.offsets.len() as u32;
+        let rg = RowGroup::new(id, columns, row_count)
+            .with_codec(self.codec)
+            .with_page_size(self.page_size)
+            .with_bloom_filters(state.bloom_filters.clone());
+        state.offsets.push(self.position()?);
+        state.first_rows.push(state.row_count);
+        state.row_count += row_count as u64;
+
+        let encoded = self.write_rowgroup(&rg)?;
+        state.stats.push(encoded.stats);
+        // Locations are kept in leaf order, readers binary-search them
+        let mut filters: Vec<_> = encoded
+            .bloom_filters
+            .into_iter()
+            .map(|(leaf, filter)| (id, leaf, filter))
+            .collect();
+        filters.sort_by_key(|(_, leaf, _)| *leaf);
+        state.filters.extend(self.write_blobs(filters)?);
+        state
+            .column_chunks
+            .extend(
+                encoded
+                    .chunks
+                    .iter()
+                    .enumerate()
+                    .map(|(leaf, chunk)| BlobLocation {
+                        row_group: id,
+                        leaf: leaf as u32,
+                        offset: chunk.start,
+                        size: chunk.end - chunk.start,
+                    }),
+            );
+        let page_indexes = encoded
+            .page_indexes
+            .into_iter()
+            .enumerate()
+            .map(|(leaf, index)| (id, leaf, index))
+            .collect();
+        state.page_indexes.extend(self.write_blobs(page_indexes)?);
         Ok(())
     }
 
-    pub fn write_from_csv<P: AsRef<Path>>(&mut self, input: P) -> std::io::Result<()> {
-        let mut reader = csv::Reader::from_path(input).unwrap();
-        let mut offsets = Vec::new();
-        let mut curr_offset = 0;
-
-        const ROWGROUP_SIZE: usize = 10;
-
-        let headers = reader.headers()?.clone();
-        let mut records = reader.records().peekable();
-
-        let schema = if let Some(Ok(first_record)) = records.peek() {
-            headers
-                .iter()
-                .enumerate()
-                .map(|(i, header)| {
-                    let plank_type = first_record
-                        .get(i)
-                        .map(PlankType::infer_type)
-                        .unwrap_or(PlankType::Str);
-                    PlankField::new(header, plank_type)
-                })
-                .collect()
+    /// Writes the footer and the trailer
+    fn write_end(&mut self, state: WriteState) -> std::io::Result<Footer> {
+        let mut features = FeatureFlags::PAGE_INDEXES;
+        let nested = state.schema.iter().any(|f| {
+            matches!(
+                f.field_type().non_null(),
+                PlankType::Struct(_) | PlankType::List(_) | PlankType::Map(_)
+            )
+        });
+        if nested {
+            features.insert(FeatureFlags::NESTED_COLUMNS);
+        }
+        if state.schema.iter().any(|f| f.field_type().has_map()) {
+            features.insert(FeatureFlags::MAP_COLUMNS);
+        }
+        let nullable = shred::leaves(&state.schema)?
+            .iter()
+            .any(|leaf| leaf.max_definition_level() > leaf.max_repetition_level());
+        if nullable {
+            features.insert(FeatureFlags::NULLABLE_COLUMNS);
+        }
+        if !state.filters.is_empty() {
+            features.insert(FeatureFlags::BLOOM_FILTERS);
+        }
+        if !state.sort_order.is_empty() {
+            features.insert(FeatureFlags::SORT_ORDER);
+        }
+
+        let col_count = state.schema.len() as u32;
+        let row_group_count = state.offsets.len() as u64;
+        let footer = Footer::new(
+            state.schema,
+            state.offsets,
+            state.row_count,
+            col_count,
+            row_group_count,
+        )
+        .with_stats(state.stats)
+        .with_bloom_filters(state.filters)
+        .with_features(features)
+        .with_page_indexes(state.page_indexes)
+        .with_column_chunks(state.column_chunks)
+        .with_sort_order(state.sort_order)
+        .with_metadata(self.metadata.clone())
+        .with_writer_config(WriterConfig {
+            row_group_rows: self.options.row_group_rows as u64,
+            row_group_bytes: Some(self.options.row_group_bytes as u64),
+            page_size: self.page_size as u64,
+            codec: self.codec,
+            encodings: vec![Encoding::Plain],
+            created_by: CREATED_BY.to_string(),
+            max_memory: Some(self.options.max_memory as u64),
+            bloom_filters: self.bloom_filters.clone(),
+        })
+        .with_first_rows(state.first_rows);
+        self.write_footer(&footer)?;
+        self.file.flush()?;
+        if let Some(append) = self.append.take() {
+            append.commit(self.file.get_mut())?;
+        }
+
+        Ok(footer)
+    }
+
+    /// Writes a row group and returns the statistics, bloom filters and page indexes of its
+    /// leaf column chunks, with where each chunk went
+    fn write_rowgroup(&mut self, rg: &RowGroup) -> std::io::Result<EncodedRowGroup> {
+        let mut encoded = rg.encode()?;
+        let rg_bytes = std::mem::take(&mut encoded.bytes);
+        let size = rg_bytes.len() as u64;
+        // Page and chunk offsets are counted from the row group bytes, after their length
+        let base = self.position()? + 8;
+        encoded
+            .page_indexes
+            .iter_mut()
+            .for_each(|index| index.shift(base));
+        encoded
+            .chunks
+            .iter_mut()
+            .for_each(|chunk| *chunk = chunk.start + base..chunk.end + base);
+
+        self.file.write_all(&size.to_le_bytes())?;
+        self.file.write_all(&rg_bytes)?;
+        Ok(encoded)
+    }
+
+    /// Writes blobs of leaf column chunks and returns where each one went
+    fn write_blobs<B: Serialize>(
+        &mut self,
+        blobs: Vec<(u32, usize, B)>,
+    ) -> std::io::Result<Vec<BlobLocation>> {
+        let mut locations = Vec::with_capacity(blobs.len());
+        for (row_group, leaf, blob) in blobs {
+            let offset = self.position()?;
+            let bytes = blob.to_bytes()?;
+            let size = bytes.len() as u64;
+            self.file.write_all(&bytes)?;
+            locations.push(BlobLocation {
+                row_group,
+                leaf: leaf as u32,
+                offset,
+                size,
+            });
+        }
+        Ok(locations)
+    }
+
+    /// Where the next byte written goes in the file
+    fn position(&mut self) -> std::io::Result<u64> {
+        self.file.stream_position()
+    }
+
+    /// Writes the footer, then the trailer pointing to it
+    fn write_footer(&mut self, footer: &Footer) -> std::io::Result<()> {
+        let footer_offset = self.position()?;
+        self.file.write_all(&footer.to_bytes()?)?;
+        Trailer {
+            footer_offset,
+            version: FORMAT_VERSION,
+            features: footer.features(),
+        }
+        .write(&mut self.file)
+    }
+
+    /// Converts the CSV at `input`, see `write_from_csv_reader`
+    pub fn write_from_csv<P: AsRef<Path>>(&mut self, input: P) -> std::io::Result<IngestReport> {
+        self.write_from_csv_reader(open_input(input.as_ref())?)
+    }
+
+    /// Converts a CSV read from `source`, such as stdin, read as set by `with_csv_options`.
+    /// Gzipped input is decompressed. Cells are parsed as the types of rows already buffered,
+    /// which are kept, of the schema given to `with_schema`, or as types inferred from the
+    /// first records. Records that cannot be
+    /// written are handled as set by `with_bad_records`, and the report counts them.
+    pub fn write_from_csv_reader<R: Read>(&mut self, source: R) -> std::io::Result<IngestReport> {
+        let options = self.csv_options.clone();
+        let mut reader = options.reader(source)?;
+        let headers = if options.has_headers {
+            Some(reader.headers()?.clone())
         } else {
-            Vec::new()
+            None
+        };
+        let mut records = reader.into_records();
+        let sample = options.sample(&mut records)?;
+        let schema = match self.known_schema()? {
+            Some(schema) => schema,
+            None => options.infer_schema(
+                headers.as_ref(),
+                &sample,
+                self.bad_records == BadRecordPolicy::Null,
+            ),
         };
+        let header = match &headers {
+            Some(headers) => options.write_record(headers),
+            None => Vec::new(),
+        };
+        let mut bad = BadRecords::new(self.bad_records.clone(), header);
 
-        let col_count = schema.len() as u32;
-        let mut row_count = 0u32;
+        // Every row group is written as soon as it is full, so only what the footer keeps
+        // of the row groups grows with the input
+        let mut state = self.ingest_state(schema)?;
+        for record in sample.into_iter().map(Ok).chain(records) {
+            let record = record?;
+            if let Some(row) = state.read_record(&record, &options, &mut bad)? {
+                self.buffer_row(&mut state, row)?;
+                bad.report.rows_written += 1;
+            }
+        }
+        self.write_buffered(&mut state)?;
+        self.write_end(state)?;
 
-        let mut row_groups = Vec::new();
-        let mut row_group_id = 0;
+        bad.finish()
+    }
 
-        for chunk in &records.chunks(ROWGROUP_SIZE) {
-            let mut row_group = vec![Vec::new(); schema.len()];
+    /// Converts the NDJSON at `input`, see `write_from_ndjson_reader`. Without a schema given to
+    /// `with_schema`, the whole input is read once to infer one from every record, then again
+    /// to write it.
+    pub fn write_from_ndjson<P: AsRef<Path>>(&mut self, input: P) -> std::io::Result<IngestReport> {
+        let input = input.as_ref();
+        let schema = match self.known_schema()? {
+            Some(schema) => schema,
+            None => ingest::scan_ndjson_schema(open_input(input)?)?,
+        };
+        self.write_ndjson(open_input(input)?, Some(schema))
+    }
+
+    /// Converts NDJSON read from `source`, one JSON object per line. Gzipped input is
+    /// decompressed and blank lines are skipped. Keys are the columns of the schema given to
+    /// `with_schema`, or of one inferred from the first 1024 records, where missing keys are
+    /// nulls and nested objects are structs or maps. When there are more records, integers are
+    /// inferred as `Int64` and keys first seen past them make their records bad. Records that
+    /// cannot be written are handled as set by `with_bad_records`, and the report counts them.
+    pub fn write_from_ndjson_reader<R: Read>(
+        &mut self,
+        source: R,
+    ) -> std::io::Result<IngestReport> {
+        let schema = self.known_schema()?;
+        self.write_ndjson(source, schema)
+    }
 
-            for row in chunk {
-                let row = row?;
-                for (i, field) in schema.iter().enumerate() {
-                    let item = &row[i];
-                    row_group[i].push(PlankData::parse(item, field.field_type())?);
+    fn write_ndjson<R: Read>(
+        &mut self,
+        source: R,
+        schema: Option<Vec<PlankField>>,
+    ) -> std::io::Result<IngestReport> {
+        let lines = BufReader::new(ingest::decode(source, TextEncoding::Utf8)?).lines();
+        let mut records = lines
+            .enumerate()
+            .filter(|(_, line)| !line.as_ref().is_ok_and(|l| l.trim().is_empty()))
+            .map(|(i, line)| {
+                let text = line?;
+                let object = parse_object(&text);
+                Ok((i as u64 + 1, text, object))
+            });
+        let sample = records
+            .by_ref()
+            .take(INFER_RECORDS)
+            .collect::<std::io::Result<Vec<_>>>()?;
+        let schema = match schema {
+            Some(schema) => schema,
+            None => ingest::infer_ndjson_schema(
+                sample
+                    .iter()
+                    .filter_map(|(_, _, object)| object.as_ref().ok()),
+                sample.len() < INFER_RECORDS,
+            ),
+        };
+
+        let mut state = self.ingest_state(schema)?;
+        let mut bad = BadRecords::new(self.bad_records.clone(), Vec::new());
+        for record in sample.into_iter().map(Ok).chain(records) {
+            let (line, text, object) = record?;
+            let object = match object {
+                Ok(object) => object,
+                Err(reason) => {
+                    let error = IngestError::Record { line, reason };
+                    bad.reject(error, &[text.as_bytes(), b"\n"].concat())?;
+                    continue;
                 }
-                row_count += 1;
+            };
+            if let Some(row) = state.read_object(&object, line, &text, &mut bad)? {
+                self.buffer_row(&mut state, row)?;
+                bad.report.rows_written += 1;
             }
-            let mut columns = Vec::new();
-            let mut row_group_row_count = 0;
-            for rg in row_group {
-                row_group_row_count = rg.len();
-                columns.push(Column::new(rg));
+        }
+        self.write_buffered(&mut state)?;
+        self.write_end(state)?;
+
+        bad.finish()
+    }
+}
+
+fn open_input(path: &Path) -> std::io::Result<File> {
+    File::open(path).map_err(|e| {
+        std::io::Error::new(e.kind(), format!("cannot open {}: {}", path.display(), e))
+    })
+}
+
+fn parse_object(text: &str) -> Result<serde_json::Map<String, serde_json::Value>, String> {
+    match serde_json::from_str(text) {
+        Ok(serde_json::Value::Object(object)) => Ok(object),
+        Ok(_) => Err("record is not a JSON object".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+impl Append {
+    /// Renames the spill file, synced first, over the file, unless the file was changed since
+    /// it was copied. The directory is synced after, so the rename outlasts a crash.
+    fn commit(self, spill: &mut File) -> std::io::Result<()> {
+        let trailer = Trailer::read(&mut File::open(&self.path)?)?;
+        if trailer.footer_offset != self.footer_offset {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{} changed while rows were appended", self.path.display()),
+            ));
+        }
+        spill.sync_all()?;
+        std::fs::rename(&self.spill, &self.path)?;
+        #[cfg(unix)]
+        {
+            let dir = match self.path.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => Path::new("."),
+            };
+            File::open(dir)?.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+impl WriteState {
+    /// Carries on after the row groups of an existing file
+    fn resume(&mut self, footer: Footer) -> std::io::Result<()> {
+        if self.sort_order.columns != footer.sort_order.columns {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "rows appended to a sorted file must keep its sort order",
+            ));
+        }
+        self.sort_order = footer.sort_order;
+        self.row_count = footer.row_count;
+        self.offsets = footer.offsets;
+        self.first_rows = footer.first_rows;
+        self.stats = footer.stats;
+        self.filters = footer.bloom_filters;
+        self.page_indexes = footer.page_indexes;
+        self.column_chunks = footer.column_chunks;
+        Ok(())
+    }
+
+    fn empty_columns(schema: &[PlankField]) -> Vec<Column> {
+        schema
+            .iter()
+            .map(|field| Column::new(field.field_type()))
+            .collect()
+    }
+
+    /// Reads the cells of a CSV record as a row, or `None` when `bad` leaves the record out
+    fn read_record(
+        &self,
+        record: &csv::StringRecord,
+        options: &CsvOptions,
+        bad: &mut BadRecords,
+    ) -> std::io::Result<Option<Vec<PlankData>>> {
+        let line = record.position().map_or(0, |p| p.line());
+        if record.len() != self.schema.len() {
+            let error = IngestError::Width {
+                line,
+                cells: record.len(),
+                columns: self.schema.len(),
+            };
+            bad.reject(error, &options.write_record(record))?;
+            return Ok(None);
+        }
+
+        let mut row = Vec::with_capacity(record.len());
+        for ((field, column), cell) in self.schema.iter().zip(&self.columns).zip(record) {
+            let value = if options.is_null(cell) {
+                Ok(PlankData::Null)
+            } else {
+                PlankData::parse(cell, field.field_type())
+            };
+            let reason = match value {
+                Ok(value) if column.accepts(&value) => {
+                    row.push(value);
+                    continue;
+                }
+                Ok(PlankData::Null) => "column is not nullable".to_string(),
+                Ok(_) => format!("not a valid {}", field.field_type()),
+                Err(e) => e.to_string(),
+            };
+            let error = IngestError::Cell {
+                line,
+                column: field.field_name().to_string(),
+                value: cell.to_string(),
+                reason,
+            };
+            match bad.cell(error, field, &options.write_record(record))? {
+                Some(value) => row.push(value),
+                None => return Ok(None),
             }
+        }
+        Ok(Some(row))
+    }
 
-            row_groups.push(RowGroup::new(row_group_id, columns, row_group_row_count as u32));
-            row_group_id += 1;
+    /// Reads the keys of an NDJSON object as a row, or `None` when `bad` leaves the record out
+    fn read_object(
+        &self,
+        object: &serde_json::Map<String, serde_json::Value>,
+        line: u64,
+        text: &str,
+        bad: &mut BadRecords,
+    ) -> std::io::Result<Option<Vec<PlankData>>> {
+        let raw = || [text.as_bytes(), b"\n"].concat();
+        let unknown = object
+            .iter()
+            .find(|(key, _)| !self.schema.iter().any(|f| f.field_name() == *key));
+        if let Some((key, value)) = unknown {
+            let error = IngestError::Cell {
+                line,
+                column: key.clone(),
+                value: value.to_string(),
+                reason: "not a column of the schema".to_string(),
+            };
+            bad.reject(error, &raw())?;
+            return Ok(None);
         }
 
-        for rg in &row_groups {
-            offsets.push(curr_offset);
-            curr_offset = self.write_rowgroup(rg)?
+        let mut row = Vec::with_capacity(self.schema.len());
+        for (field, column) in self.schema.iter().zip(&self.columns) {
+            let value = object
+                .get(field.field_name())
+                .unwrap_or(&serde_json::Value::Null);
+            let reason = match PlankData::from_json(value, field.field_type()) {
+                Ok(value) if column.accepts(&value) => {
+                    row.push(value);
+                    continue;
+                }
+                Ok(_) => format!("not a valid {}", field.field_type()),
+                Err(e) => e.to_string(),
+            };
+            let error = IngestError::Cell {
+                line,
+                column: field.field_name().to_string(),
+                value: value.to_string(),
+                reason,
+            };
+            match bad.cell(error, field, &raw())? {
+                Some(value) => row.push(value),
+                None => return Ok(None),
+            }
         }
+        Ok(Some(row))
+    }
 
-        // Add an extra offset pointing to the beginning of the footer
-        // This will be used to know the byte size of any rowgroup N (offsets[N + 1] - offsets[N])
-        // offsets.push(curr_offset);
+    /// Checks that a row holds a value of the right type for every column
+    fn check_row(&self, row: &[PlankData]) -> std::io::Result<()> {
+        if row.len() != self.schema.len() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "row has {} values but the schema has {} columns",
+                    row.len(),
+                    self.schema.len()
+                ),
+            ));
+        }
+        for ((field, column), value) in self.schema.iter().zip(&self.columns).zip(row) {
+            if !column.accepts(value) {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!(
+                        "value {} does not match column {} of type {}",
+                        value,
+                        field.field_name(),
+                        field.field_type()
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
 
-        let footer = Footer::new(
-            schema,
-            offsets,
-            row_count,
-            col_count,
-            row_groups.len() as u32,
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::bloom::{BloomFilter, DEFAULT_FPP};
+    use crate::file::ingest::TextEncoding;
+    use crate::file::predicate::Predicate;
+    use crate::file::reader::PlankReader;
+    use sha2::{Digest, Sha256};
+    use std::io::SeekFrom;
+
+    #[test]
+    fn test_offsets_past_4_gib() {
+        let dir = std::env::temp_dir().join(format!("plank-sparse-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let csv_path = dir.join("sparse.csv");
+        let plank_path = dir.join("sparse.plank");
+
+        let mut csv = String::from("id,name\n");
+        for i in 0..25 {
+            csv.push_str(&format!("{},name-{}\n", i, i));
+        }
+        std::fs::write(&csv_path, csv).unwrap();
+
+        let mut writer = PlankWriter::new(&plank_path)
+            .unwrap()
+            .with_options(WriterOptions::default().with_row_group_rows(10))
+            .with_bloom_filter("name", DEFAULT_FPP);
+        // A hole in front of the row groups takes no space on disk
+        writer.file.seek(SeekFrom::Start(5 << 30)).unwrap();
+        writer.write_from_csv(&csv_path).unwrap();
+        drop(writer);
+        assert!(std::fs::metadata(&plank_path).unwrap().len() > 5 << 30);
+
+        for mut reader in [
+            PlankReader::open(&plank_path).unwrap(),
+            PlankReader::open_mmap(&plank_path).unwrap(),
+        ] {
+            assert!(reader.footer().offsets[0] > u32::MAX as u64);
+            let rows = (&mut reader).into_iter().flat_map(|rg| rg.unwrap()).count();
+            assert_eq!(rows, 25);
+
+            let name = PlankData::Str("name-13".to_string());
+            assert_eq!(reader.row_groups_containing("name", &name).unwrap(), [1]);
+            let index = reader.read_page_index(2, "id").unwrap().unwrap();
+            assert!(index.pages[0].offset > u32::MAX as u64);
+            let predicate = Predicate::GreaterEq("id".to_string(), PlankData::Int32(20));
+            let ranges = reader.matching_row_ranges(2, &predicate).unwrap();
+            let runs = reader.read_row_ranges(2, "id", &ranges).unwrap();
+            assert_eq!(runs[0].1.len(), 5);
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// CSV rows made as they are read, noting how large the output file is every 1,000 rows
+    struct GrowingCsv {
+        output: PathBuf,
+        rows: u64,
+        next: u64,
+        pending: Vec<u8>,
+        sizes: Vec<(u64, u64)>,
+    }
+
+    impl Read for GrowingCsv {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.pending.is_empty() {
+                if self.next == self.rows {
+                    return Ok(0);
+                }
+                if self.next.is_multiple_of(1000) {
+                    let size = std::fs::metadata(&self.output)?.len();
+                    self.sizes.push((self.next, size));
+                }
+                if self.next == 0 {
+                    self.pending.extend_from_slice(b"id,name\n");
+                }
+                self.pending
+                    .extend_from_slice(format!("{},name-{}\n", self.next, self.next).as_bytes());
+                self.next += 1;
+            }
+            let n = buf.len().min(self.pending.len());
+            buf[..n].copy_from_slice(&self.pending[..n]);
+            self.pending.drain(..n);
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_write_from_csv_streams_row_groups() {
+        let dir = std::env::temp_dir().join(format!("plank-stream-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("stream.plank");
+        let mut csv = GrowingCsv {
+            output: path.clone(),
+            rows: 50_000,
+            next: 0,
+            pending: Vec::new(),
+            sizes: Vec::new(),
+        };
+
+        let mut writer = PlankWriter::new(&path)
+            .unwrap()
+            .with_options(WriterOptions::default().with_row_group_rows(1000))
+            .with_bloom_filter("name", DEFAULT_FPP);
+        writer.write_from_csv_reader(&mut csv).unwrap();
+        drop(writer);
+        let mut reader = PlankReader::open(&path).unwrap();
+        assert_eq!(reader.footer().row_group_count(), 50);
+        let offsets = reader.footer().offsets.clone();
+
+        // Every row group is on disk before the rows two groups later are read, short of
+        // what the output and the CSV reader buffer
+        for &(row, size) in &csv.sizes {
+            let Some(written) = (row / 1000).checked_sub(2) else {
+                continue;
+            };
+            assert!(
+                size + 8192 >= offsets[written as usize + 1],
+                "{} bytes on disk after {} rows",
+                size,
+                row
+            );
+        }
+
+        // The blobs written between row groups belong to their own row group
+        for id in 0..50 {
+            let name = PlankData::Str(format!("name-{}", id * 1000 + 7));
+            assert!(reader.might_contain(id, "name", &name).unwrap());
+            assert_eq!(reader.row_groups_containing("name", &name).unwrap(), [id]);
+
+            let index = reader.read_page_index(id, "id").unwrap().unwrap();
+            let end = offsets.get(id + 1).copied().unwrap_or(u64::MAX);
+            assert!(
+                index
+                    .pages
+                    .iter()
+                    .all(|page| (offsets[id]..end).contains(&page.offset))
+            );
+            assert_eq!(index.pages[0].min, Some(PlankData::Int32(id as i32 * 1000)));
+            assert_eq!(index.pages.last().unwrap().rows().end, 1000);
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_write_from_csv_reader_with_options() {
+        let dir = std::env::temp_dir().join(format!("plank-csv-options-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("dialect.plank");
+
+        // A gzipped, semicolon-separated CSV with a byte order mark and a comment line
+        let csv = "\u{feff}id; score ;name\n# a comment\n1; NA ;a\n2;7;'b;c'\n3;;NA\n";
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        gz.write_all(csv.as_bytes()).unwrap();
+        let mut writer = PlankWriter::new(&path).unwrap().with_csv_options(
+            CsvOptions::default()
+                .with_delimiter(b';')
+                .with_quote(b'\'')
+                .with_comment(b'#')
+                .with_trim(true)
+                .with_null_values(["", "NA"]),
         );
-        self.write_footer(&footer)?;
+        writer
+            .write_from_csv_reader(gz.finish().unwrap().as_slice())
+            .unwrap();
+        drop(writer);
 
-        Ok(())
+        let mut reader = PlankReader::open(&path).unwrap();
+        assert_eq!(
+            reader.schema(),
+            [
+                PlankField::new("id", PlankType::Int32.nullable()),
+                PlankField::new("score", PlankType::Int32.nullable()),
+                PlankField::new("name", PlankType::Str.nullable()),
+            ]
+        );
+        assert!(
+            reader
+                .footer()
+                .features()
+                .contains(FeatureFlags::NULLABLE_COLUMNS)
+        );
+        let rows = (&mut reader)
+            .into_iter()
+            .flat_map(|rg| rg.unwrap())
+            .map(|row| row.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(rows[0][1], PlankData::Null);
+        assert_eq!(rows[1][2], PlankData::Str("b;c".to_string()));
+        assert_eq!(rows[2][1..], [PlankData::Null, PlankData::Null]);
+        assert_eq!(reader.column_stats(0, "score").unwrap().null_count, 2);
+
+        // A Latin-1 TSV without headers, read as the types of a given schema
+        let schema = vec![
+            PlankField::new("id", PlankType::Int64),
+            PlankField::new("city", PlankType::Str),
+        ];
+        let mut writer = PlankWriter::with_schema(&path, schema.clone())
+            .unwrap()
+            .with_csv_options(
+                CsvOptions::default()
+                    .with_delimiter(b'\t')
+                    .with_headers(false)
+                    .with_encoding(TextEncoding::Latin1),
+            );
+        writer.write_from_csv_reader(&b"1\tK\xf6ln\n"[..]).unwrap();
+        drop(writer);
+
+        let mut reader = PlankReader::open(&path).unwrap();
+        assert_eq!(reader.schema(), schema);
+        let batch = reader.read_row_group(0).unwrap();
+        assert_eq!(batch.columns[1].str_value(0), Some("Köln"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_bad_records_policies() {
+        let dir = std::env::temp_dir().join(format!("plank-bad-records-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bad.plank");
+        let quarantine = dir.join("bad.csv");
+        let csv = "id,name\n1,a\nx,b\n3,c,extra\n4,d\n";
+        let convert = |policy: BadRecordPolicy| {
+            PlankWriter::new(&path)
+                .unwrap()
+                .with_bad_records(policy)
+                .write_from_csv_reader(csv.as_bytes())
+        };
+
+        let err = convert(BadRecordPolicy::Fail).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        let error = err
+            .get_ref()
+            .unwrap()
+            .downcast_ref::<IngestError>()
+            .unwrap();
+        assert!(
+            matches!(error, IngestError::Cell { line: 3, column, value, .. } if column == "id" && value == "x"),
+            "{}",
+            error
+        );
+
+        let report = convert(BadRecordPolicy::Skip).unwrap();
+        assert_eq!((report.rows_written, report.rows_skipped), (2, 2));
+
+        let report = convert(BadRecordPolicy::Quarantine(quarantine.clone())).unwrap();
+        assert_eq!(report.rows_quarantined, 2);
+        assert_eq!(
+            std::fs::read_to_string(&quarantine).unwrap(),
+            "id,name\nx,b\n3,c,extra\n"
+        );
+
+        // Nulls replace cells but cannot make up for a missing one
+        let err = convert(BadRecordPolicy::Null).unwrap_err();
+        assert!(err.to_string().starts_with("line 4:"), "{}", err);
+        let report = PlankWriter::new(&path)
+            .unwrap()
+            .with_bad_records(BadRecordPolicy::Null)
+            .write_from_csv_reader("id,name\n1,a\nx,b\n".as_bytes())
+            .unwrap();
+        assert_eq!((report.rows_written, report.cells_nulled), (2, 1));
+        let mut reader = PlankReader::open(&path).unwrap();
+        let batch = reader.read_row_group(0).unwrap();
+        assert_eq!(batch.columns[0].get(1), Some(PlankData::Null));
+
+        let err = PlankWriter::new(&path)
+            .unwrap()
+            .write_from_csv(dir.join("missing.csv"))
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_quarantine_keeps_the_csv_dialect() {
+        let dir = std::env::temp_dir().join(format!("plank-quarantine-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bad.plank");
+        let quarantine = dir.join("bad.csv");
+        let options = CsvOptions::default()
+            .with_delimiter(b';')
+            .with_quote(b'\'')
+            .with_escape(b'\\')
+            .with_comment(b'#');
+        let csv = "id;name\n1;plain\nx;'it\\'s; here'\ny;'back\\\\slash'\nz;'two\nlines'\n\
+                   #w;skipped\n'#q';hash\n'';\n4;ok\n";
+
+        let report = PlankWriter::new(&path)
+            .unwrap()
+            .with_csv_options(options.clone())
+            .with_bad_records(BadRecordPolicy::Quarantine(quarantine.clone()))
+            .write_from_csv_reader(csv.as_bytes())
+            .unwrap();
+        assert_eq!((report.rows_written, report.rows_quarantined), (2, 5));
+
+        // Read with the same options, the quarantine holds the bad records as they were
+        let records = |source: &[u8]| {
+            let mut reader = options.reader(source).unwrap();
+            let headers = reader.headers().unwrap().clone();
+            let records = reader
+                .into_records()
+                .map(Result::unwrap)
+                .filter(|r| r[0].parse::<i32>().is_err())
+                .collect::<Vec<_>>();
+            (headers, records)
+        };
+        let (headers, expected) = records(csv.as_bytes());
+        assert_eq!(
+            expected.iter().map(|r| &r[1]).collect::<Vec<_>>(),
+            ["it's; here", "back\\slash", "two\nlines", "hash", ""]
+        );
+        let written = std::fs::read(&quarantine).unwrap();
+        assert_eq!(records(&written), (headers, expected));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_write_from_ndjson() {
+        let dir = std::env::temp_dir().join(format!("plank-ndjson-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("records.plank");
+        let ndjson = r#"{"id": 1, "user": {"name": "a", "tags": ["x"]}, "score": 1.5}
+
+{"id": 3000000000, "user": {"name": "b"}, "active": true}
+not json
+{"id": null, "user": null}
+"#;
+
+        let report = PlankWriter::new(&path)
+            .unwrap()
+            .with_bad_records(BadRecordPolicy::Skip)
+            .write_from_ndjson_reader(ndjson.as_bytes())
+            .unwrap();
+        assert_eq!((report.rows_written, report.rows_skipped), (3, 1));
+
+        let mut reader = PlankReader::open(&path).unwrap();
+        let user = PlankType::Struct(vec![
+            PlankField::new("name", PlankType::Str.nullable()),
+            PlankField::new("tags", PlankType::List(Box::new(PlankType::Str)).nullable()),
+        ]);
+        assert_eq!(
+            reader.schema(),
+            [
+                PlankField::new("id", PlankType::Int64.nullable()),
+                PlankField::new("user", user.nullable()),
+                PlankField::new("score", PlankType::Str.nullable()),
+                PlankField::new("active", PlankType::Bool.nullable()),
+            ]
+        );
+        let rows = (&mut reader)
+            .into_iter()
+            .flat_map(|rg| rg.unwrap())
+            .map(|row| row.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(rows[0][2], PlankData::Str("1.5".to_string()));
+        assert_eq!(
+            rows[1][..2],
+            [
+                PlankData::Int64(3_000_000_000),
+                PlankData::Struct(vec![PlankData::Str("b".to_string()), PlankData::Null]),
+            ]
+        );
+        assert_eq!(rows[2], vec![PlankData::Null; 4]);
+
+        // Keys outside a given schema are bad records
+        let err = PlankWriter::with_schema(&path, vec![PlankField::new("id", PlankType::Int64)])
+            .unwrap()
+            .write_from_ndjson_reader(ndjson.as_bytes())
+            .unwrap_err();
+        let error = err
+            .get_ref()
+            .unwrap()
+            .downcast_ref::<IngestError>()
+            .unwrap();
+        assert!(
+            matches!(error, IngestError::Cell { line: 1, column, .. } if column == "user"),
+            "{}",
+            error
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_ndjson_past_the_inferred_records() {
+        let dir =
+            std::env::temp_dir().join(format!("plank-ndjson-inferred-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("long.ndjson");
+        let path = dir.join("long.plank");
+        let mut ndjson = (0..1100)
+            .map(|i| match i {
+                1050 => r#"{"n": 5000000000, "name": {"first": "b"}}"#.to_string(),
+                i => format!(
+                    r#"{{"n": {}, "name": "a", "attrs": {{"k{}": {}}}}}"#,
+                    i,
+                    i % 70,
+                    i
+                ),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        ndjson.push_str("\n{\"late\": true}\n");
+        std::fs::write(&input, &ndjson).unwrap();
+
+        // Read as a stream, the schema comes from the first records: integers are widened, a
+        // key seen later is bad and so is an object in a string column
+        let report = PlankWriter::new(&path)
+            .unwrap()
+            .with_bad_records(BadRecordPolicy::Skip)
+            .write_from_ndjson_reader(ndjson.as_bytes())
+            .unwrap();
+        assert_eq!((report.rows_written, report.rows_skipped), (1099, 2));
+        let mut reader = PlankReader::open(&path).unwrap();
+        assert_eq!(
+            reader.schema(),
+            [
+                PlankField::new("n", PlankType::Int64.nullable()),
+                PlankField::new("name", PlankType::Str.nullable()),
+                PlankField::new(
+                    "attrs",
+                    PlankType::map(PlankType::Int64.nullable()).nullable()
+                ),
+            ]
+        );
+        let row = (&mut reader)
+            .into_iter()
+            .flat_map(|rg| rg.unwrap())
+            .nth(3)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            row[2],
+            PlankData::List(vec![PlankData::Struct(vec![
+                PlankData::Str("k3".to_string()),
+                PlankData::Int64(3)
+            ])])
+        );
+
+        // Read from a file, every record is inferred from
+        let report = PlankWriter::new(&path)
+            .unwrap()
+            .with_bad_records(BadRecordPolicy::Skip)
+            .write_from_ndjson(&input)
+            .unwrap();
+        assert_eq!((report.rows_written, report.rows_skipped), (1100, 1));
+        let reader = PlankReader::open(&path).unwrap();
+        assert_eq!(
+            reader.schema(),
+            [
+                PlankField::new("n", PlankType::Int64.nullable()),
+                PlankField::new("name", PlankType::Str.nullable()),
+                PlankField::new(
+                    "attrs",
+                    PlankType::map(PlankType::Int32.nullable()).nullable()
+                ),
+                PlankField::new("late", PlankType::Bool.nullable()),
+            ]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_write_rows_then_finish() {
+        let dir = std::env::temp_dir().join(format!("plank-rows-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("rows.plank");
+        let schema = vec![
+            PlankField::new("id", PlankType::Int64),
+            PlankField::new("name", PlankType::Str),
+        ];
+        let row = |i: i64| vec![PlankData::Int64(i), PlankData::Str(format!("name-{}", i))];
+
+        let mut writer = PlankWriter::with_schema(&path, schema.clone())
+            .unwrap()
+            .with_options(WriterOptions::default().with_row_group_rows(10))
+            .with_sort_order(vec![SortColumn::asc("id")]);
+        writer.write_rows((0..15).map(row)).unwrap();
+        assert!(writer.write_row(&[PlankData::Int64(15)]).is_err());
+        assert!(
+            writer
+                .write_row(&[PlankData::Int32(15), PlankData::Int32(0)])
+                .is_err()
+        );
+        // Rejected rows leave nothing behind
+        writer.write_row(&row(15)).unwrap();
+        let footer = writer.finish().unwrap();
+        assert_eq!(footer.row_count(), 16);
+        assert_eq!(footer.row_group_count(), 2);
+
+        let mut reader = PlankReader::open(&path).unwrap();
+        assert_eq!(reader.schema(), schema);
+        let batch = reader.read_row_group(1).unwrap();
+        assert_eq!(batch.columns[0].get(5), Some(PlankData::Int64(15)));
+
+        // A batch read back is written again as is
+        let copy = dir.join("rows-copy.plank");
+        let mut writer = PlankWriter::with_schema(&copy, schema).unwrap();
+        writer.write_batch(batch).unwrap();
+        assert!(writer.write_row(&row(0)).is_ok());
+        assert_eq!(writer.finish().unwrap().row_count(), 7);
+
+        // Rows out of the declared order are rejected when their row group is written
+        let mut writer =
+            PlankWriter::with_schema(&copy, vec![PlankField::new("id", PlankType::Int64)])
+                .unwrap()
+                .with_sort_order(vec![SortColumn::asc("id")]);
+        writer
+            .write_rows((0..5).rev().map(|i| [PlankData::Int64(i)]))
+            .unwrap();
+        assert!(writer.finish().is_err());
+        assert!(PlankWriter::new(&copy).unwrap().write_row(&row(0)).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_open_append() {
+        let dir = std::env::temp_dir().join(format!("plank-append-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("append.plank");
+        let schema = vec![
+            PlankField::new("id", PlankType::Int64),
+            PlankField::new("name", PlankType::Str),
+        ];
+        let row = |i: i64| vec![PlankData::Int64(i), PlankData::Str(format!("name-{}", i))];
+        let read_all = |path: &Path| {
+            let mut reader = PlankReader::open(path).unwrap();
+            let mut rows = Vec::new();
+            for row_group in &mut reader {
+                for row in row_group.unwrap() {
+                    rows.push(row.unwrap());
+                }
+            }
+            rows
+        };
+
+        let mut writer = PlankWriter::with_schema(&path, schema)
+            .unwrap()
+            .with_options(
+                WriterOptions::default()
+                    .with_row_group_rows(10)
+                    .with_max_memory(1 << 20),
+            )
+            .with_sort_order(vec![SortColumn::asc("id")])
+            .with_bloom_filter("name", 0.000_001)
+            .with_metadata("job", "hourly");
+        writer.write_rows((0..15).map(row)).unwrap();
+        writer.finish().unwrap();
+        let before = std::fs::read(&path).unwrap();
+
+        // A writer dropped before finish leaves the file as it was
+        let mut writer = PlankWriter::open_append(&path).unwrap();
+        writer.write_rows((15..40).map(row)).unwrap();
+        drop(writer);
+        assert_eq!(std::fs::read(&path).unwrap(), before);
+
+        // Rows of another schema or out of the sort order are refused
+        let mut writer = PlankWriter::open_append(&path).unwrap();
+        assert!(writer.write_row(&[PlankData::Int32(15)]).is_err());
+        writer.write_row(&row(3)).unwrap();
+        assert!(writer.finish().is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), before);
+
+        let mut writer = PlankWriter::open_append(&path).unwrap();
+        writer.write_rows((15..40).map(row)).unwrap();
+        let footer = writer.finish().unwrap();
+        assert_eq!(footer.row_count(), 40);
+        assert_eq!(footer.row_group_count(), 5);
+        assert!(!dir.join("append.plank.append").exists());
+
+        assert_eq!(read_all(&path), (0..40).map(row).collect::<Vec<_>>());
+        let mut reader = PlankReader::open(&path).unwrap();
+        assert_eq!(reader.metadata()["job"], b"hourly");
+        assert_eq!(reader.row_group_first_row(3), Some(25));
+        assert_eq!(
+            reader.find_row(&[PlankData::Int64(33)]).unwrap(),
+            Some(row(33))
+        );
+        assert!(
+            reader
+                .might_contain(4, "name", &PlankData::Str("name-35".to_string()))
+                .unwrap()
+        );
+
+        // Appended row groups are cut and filtered as the file was written
+        let config = reader.writer_config().unwrap();
+        assert_eq!(config.max_memory, Some(1 << 20));
+        assert_eq!(config.bloom_filters, [("name".to_string(), 0.000_001)]);
+        let filter_sizes = reader
+            .footer()
+            .bloom_filters
+            .iter()
+            .map(|filter| filter.size)
+            .collect::<Vec<_>>();
+        assert_eq!(filter_sizes[0], filter_sizes[3]);
+        assert!(
+            filter_sizes[0] > BloomFilter::new(10, DEFAULT_FPP).to_bytes().unwrap().len() as u64
+        );
+
+        // A footer section of a newer writer would be lost, so such files are refused
+        let mut bytes = std::fs::read(&path).unwrap();
+        let footer_offset = Trailer::read(&mut File::open(&path).unwrap())
+            .unwrap()
+            .footer_offset as usize;
+        let trailer = bytes.split_off(bytes.len() - Trailer::SIZE as usize);
+        bytes.truncate(bytes.len() - 32);
+        let count = u32::from_le_bytes(bytes[footer_offset..footer_offset + 4].try_into().unwrap());
+        bytes[footer_offset..footer_offset + 4].copy_from_slice(&(count + 1).to_le_bytes());
+        bytes.extend_from_slice(&1000u32.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        let checksum = Sha256::digest(&bytes[footer_offset..]);
+        bytes.extend_from_slice(&checksum);
+        bytes.extend_from_slice(&trailer);
+        std::fs::write(&path, &bytes).unwrap();
+        assert_eq!(PlankReader::open(&path).unwrap().footer().row_count(), 40);
+        let error = PlankWriter::open_append(&path).err().unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(std::fs::read(&path).unwrap(), bytes);
+        assert!(!dir.join("append.plank.append").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_append_csv_and_ndjson() {
+        let dir = std::env::temp_dir().join(format!("plank-append-ingest-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("ingest.plank");
+        let schema = vec![
+            PlankField::new("id", PlankType::Int64),
+            PlankField::new("name", PlankType::Str),
+        ];
+        let row = |i: i64| vec![PlankData::Int64(i), PlankData::Str(format!("name-{}", i))];
+        let mut writer = PlankWriter::with_schema(&path, schema.clone()).unwrap();
+        writer.write_rows((0..3).map(row)).unwrap();
+        writer.finish().unwrap();
+
+        let mut writer = PlankWriter::open_append(&path).unwrap();
+        let report = writer
+            .write_from_csv_reader("id,name\n3,name-3\n4,name-4\n".as_bytes())
+            .unwrap();
+        assert_eq!(report.rows_written, 2);
+        let mut writer = PlankWriter::open_append(&path).unwrap();
+        let report = writer
+            .write_from_ndjson_reader(r#"{"id": 5, "name": "name-5"}"#.as_bytes())
+            .unwrap();
+        assert_eq!(report.rows_written, 1);
+
+        let mut reader = PlankReader::open(&path).unwrap();
+        assert_eq!(reader.schema(), schema);
+        assert_eq!(reader.footer().row_count(), 6);
+        assert_eq!(reader.footer().row_group_count(), 3);
+        let mut rows = Vec::new();
+        for row_group in &mut reader {
+            for row in row_group.unwrap() {
+                rows.push(row.unwrap());
+            }
+        }
+        assert_eq!(rows, (0..6).map(row).collect::<Vec<_>>());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_write_rows_then_csv() {
+        let dir = std::env::temp_dir().join(format!("plank-rows-csv-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("rows-csv.plank");
+        let schema = vec![
+            PlankField::new("id", PlankType::Int64),
+            PlankField::new("name", PlankType::Str),
+        ];
+        let row = |i: i64| vec![PlankData::Int64(i), PlankData::Str(format!("name-{}", i))];
+
+        // Rows still buffered are written with the converted ones
+        let mut writer = PlankWriter::with_schema(&path, schema).unwrap();
+        writer.write_rows((0..2).map(row)).unwrap();
+        let report = writer
+            .write_from_csv_reader("id,name\n2,name-2\n".as_bytes())
+            .unwrap();
+        assert_eq!(report.rows_written, 1);
+        let mut reader = PlankReader::open(&path).unwrap();
+        assert_eq!(reader.footer().row_count(), 3);
+        let mut rows = Vec::new();
+        for row_group in &mut reader {
+            for row in row_group.unwrap() {
+                rows.push(row.unwrap());
+            }
+        }
+        assert_eq!(rows, (0..3).map(row).collect::<Vec<_>>());
+
+        // Values kept to infer a schema from settle it before the conversion
+        let mut writer = PlankWriter::new(&path).unwrap();
+        writer
+            .serialize_row(&serde_json::json!({"id": 0, "name": "name-0"}))
+            .unwrap();
+        writer
+            .write_from_csv_reader("id,name\n1,name-1\n".as_bytes())
+            .unwrap();
+        assert_eq!(PlankReader::open(&path).unwrap().footer().row_count(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
@@ -1,3 +1,5 @@
+extern crate self as plank;
+
 mod file;
 // mod query;
 mod serde;
@@ -5,7 +7,38 @@ mod types;
 
 mod bindings;
 
-pub use crate::file::reader::PlankReader;
+pub use crate::file::bloom::{BloomFilter, DEFAULT_FPP};
+pub use crate::file::codec::Codec;
+pub use crate::file::config::{
+    CREATED_BY, DEFAULT_MAX_ROW_GROUP_MEMORY, DEFAULT_ROW_GROUP_BYTES, DEFAULT_ROW_GROUP_ROWS,
+    WriterConfig, WriterOptions,
+};
+pub use crate::file::footer::Footer;
+pub use crate::file::format::{FORMAT_VERSION, FeatureFlags, FormatVersion, MAGIC};
+pub use crate::file::ingest::{
+    BadRecordPolicy, CsvOptions, IngestError, IngestReport, TextEncoding,
+};
+pub use crate::file::page_index::{PageIndex, PageIndexEntry};
+pub use crate::file::predicate::Predicate;
+pub use crate::file::reader::{MatchingRowGroups, PlankReader, RecordBatch, Records};
+pub use crate::file::rowgroup::column::{
+    bitmap::Bitmap,
+    page::{Encoding, Page, PageHeader, Pages},
+    Column,
+};
+pub use crate::file::rowgroup::shred::Leaf;
+pub use crate::file::sort::{SortColumn, SortOrder};
+pub use crate::file::stats::ColumnStats;
+pub use crate::file::view::{ColumnValues, ColumnView, RecordBatchView, ValueRef, Values};
 pub use crate::file::writer::PlankWriter;
 pub use crate::types::{types::PlankType, data::PlankData, fields::PlankField};
+pub use crate::types::json::schema_of;
+pub use crate::types::record::{PlankRecord, PlankValue};
+pub use plank_derive::PlankRecord;
+
+/// Used by the code `#[derive(PlankRecord)]` generates, not a public API
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::types::record::{mismatch, row_values, take_field};
+}
 
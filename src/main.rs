@@ -1,13 +1,4 @@
-mod file;
-// mod query;
-mod serde;
-mod types;
-
-mod bindings;
-
-use crate::file::reader::PlankReader;
-use crate::file::writer::PlankWriter;
-use crate::types::data::PlankData;
+use plank::{PlankReader, PlankWriter};
 
 fn main() {
     {
@@ -5,6 +5,26 @@ pub trait Serialize {
 pub trait Deserialize<'a>: Sized {
     type Schema;
     // type Query;
-    fn from_bytes(bytes: &[u8], schema: &'a Self::Schema) -> std::io::Result<Self>;
+    /// Decodes a value from the start of `bytes` and returns it together with the number of
+    /// bytes it occupied, so callers can advance past it without encoding it again.
+    fn from_bytes(bytes: &[u8], schema: &'a Self::Schema) -> std::io::Result<(Self, usize)>;
     // fn from_bytes(bytes: &[u8], schema: &'a Vec<PlankField>, query: &RowGroupQuery) -> std::io::Result<Self>;
 }
+
+/// Reads a little-endian `u32` at `pos`, failing instead of panicking on short input
+pub(crate) fn read_u32(bytes: &[u8], pos: usize) -> std::io::Result<u32> {
+    bytes
+        .get(pos..pos + 4)
+        .and_then(|b| b.try_into().ok())
+        .map(u32::from_le_bytes)
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "expected u32"))
+}
+
+/// Reads a little-endian `u64` at `pos`, failing instead of panicking on short input
+pub(crate) fn read_u64(bytes: &[u8], pos: usize) -> std::io::Result<u64> {
+    bytes
+        .get(pos..pos + 8)
+        .and_then(|b| b.try_into().ok())
+        .map(u64::from_le_bytes)
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "expected u64"))
+}
@@ -1,3 +1,6 @@
+#[allow(clippy::module_inception)]
 pub mod types;
 pub mod fields;
 pub mod data;
+pub mod record;
+pub mod json;
@@ -1,7 +1,6 @@
-use crate::serde::{Deserialize, Serialize};
-use crate::types::types::PlankType;
+use crate::serde::{Deserialize, Serialize, read_u32, read_u64};
+use crate::types::types::{JsonType, PlankType};
 use std::fmt;
-use std::str::FromStr;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum PlankData {
@@ -11,6 +10,8 @@ pub enum PlankData {
     Bool(bool),
     List(Vec<PlankData>),
     Struct(Vec<PlankData>),
+    /// No value, held by nullable columns
+    Null,
 }
 
 impl PlankData {
@@ -29,54 +30,140 @@ impl PlankData {
     }
 
     fn parse_extended_value(s: &str) -> std::io::Result<PlankData> {
-        let s = serde_json::from_str(s)
+        let value = serde_json::from_str(s)
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
-        match s {
-            serde_json::Value::Number(n) => {
-                // TODO: Add i32 support
-                if let Some(n) = n.as_i64() {
-                    if let Ok(n) = i32::try_from(n) {
-                        return Ok(PlankData::Int32(n));
-                    }
-                    Ok(PlankData::Int64(n))
-                } else {
-                    Err(std::io::Error::new(
-                        std::io::ErrorKind::InvalidData,
-                        "unsupported number",
-                    ))
-                }
-            }
-            serde_json::Value::Bool(b) => Ok(PlankData::Bool(b)),
-            serde_json::Value::String(s) => Ok(PlankData::Str(s)),
-            serde_json::Value::Object(o) => {
-                let fields = o
-                    .iter()
-                    .map(|(_, v)| Self::parse_extended_value(&v.to_string()))
-                    .collect::<std::io::Result<Vec<_>>>()?;
+        Self::from_json(&value, &JsonType::of(&value).into_type())
+    }
 
-                Ok(PlankData::Struct(fields))
+    /// Parses `s` as a value of `data_type`, failing with `InvalidData` if it is not one
+    pub fn parse(s: &str, data_type: &PlankType) -> std::io::Result<Self> {
+        let invalid = |e: &dyn fmt::Display| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("not a valid {}: {}", data_type, e),
+            )
+        };
+        match data_type {
+            PlankType::Int32 => s
+                .parse::<i32>()
+                .map(PlankData::Int32)
+                .map_err(|e| invalid(&e)),
+            PlankType::Int64 => s
+                .parse::<i64>()
+                .map(PlankData::Int64)
+                .map_err(|e| invalid(&e)),
+            PlankType::Bool => s
+                .parse::<bool>()
+                .map(PlankData::Bool)
+                .map_err(|e| invalid(&e)),
+            PlankType::Struct(_) | PlankType::List(_) | PlankType::Map(_) => {
+                serde_json::from_str(s)
+                    .map_err(|e| invalid(&e))
+                    .and_then(|value| Self::from_json(&value, data_type))
             }
-            serde_json::Value::Array(a) => {
-                let items = a
+            PlankType::Nullable(inner) => Self::parse(s, inner),
+            _ => Ok(PlankData::Str(String::from(s))),
+        }
+    }
+
+    /// Converts a JSON value to a value of `data_type`. Object keys are matched to struct
+    /// fields by name, and missing keys and JSON nulls are nulls. Objects are also maps, and
+    /// strings also hold numbers as their JSON text, there being no floating point type.
+    pub fn from_json(value: &serde_json::Value, data_type: &PlankType) -> std::io::Result<Self> {
+        use serde_json::Value;
+
+        let invalid = |reason: String| std::io::Error::new(std::io::ErrorKind::InvalidData, reason);
+        match (data_type, value) {
+            (PlankType::Nullable(_), Value::Null) => Ok(PlankData::Null),
+            (PlankType::Nullable(inner), value) => Self::from_json(value, inner),
+            (_, Value::Null) => Err(invalid("column is not nullable".to_string())),
+            (PlankType::Str, Value::String(s)) => Ok(PlankData::Str(s.clone())),
+            (PlankType::Str, Value::Number(n)) => Ok(PlankData::Str(n.to_string())),
+            (PlankType::Int32, Value::Number(n)) => n
+                .as_i64()
+                .and_then(|n| i32::try_from(n).ok())
+                .map(PlankData::Int32)
+                .ok_or_else(|| invalid(format!("not a valid {}", data_type))),
+            (PlankType::Int64, Value::Number(n)) => n
+                .as_i64()
+                .map(PlankData::Int64)
+                .ok_or_else(|| invalid(format!("not a valid {}", data_type))),
+            (PlankType::Bool, Value::Bool(b)) => Ok(PlankData::Bool(*b)),
+            (PlankType::List(item_type), Value::Array(items)) => items
+                .iter()
+                .map(|item| Self::from_json(item, item_type))
+                .collect::<std::io::Result<Vec<_>>>()
+                .map(PlankData::List),
+            (PlankType::Map(value_type), Value::Object(object)) => object
+                .iter()
+                .map(|(key, value)| {
+                    Ok(PlankData::Struct(vec![
+                        PlankData::Str(key.clone()),
+                        Self::from_json(value, value_type)?,
+                    ]))
+                })
+                .collect::<std::io::Result<Vec<_>>>()
+                .map(PlankData::List),
+            (PlankType::Struct(fields), Value::Object(object)) => {
+                if let Some(key) = object
+                    .keys()
+                    .find(|key| !fields.iter().any(|f| f.field_name() == *key))
+                {
+                    return Err(invalid(format!("key {} is not a field of the struct", key)));
+                }
+                fields
                     .iter()
-                    .map(|v| Self::parse_extended_value(&v.to_string()))
-                    .collect::<std::io::Result<Vec<_>>>()?;
-                Ok(PlankData::List(items))
+                    .map(|f| {
+                        let value = object.get(f.field_name()).unwrap_or(&Value::Null);
+                        Self::from_json(value, f.field_type())
+                    })
+                    .collect::<std::io::Result<Vec<_>>>()
+                    .map(PlankData::Struct)
             }
-            _ => Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                "unsupported data type",
-            )),
+            _ => Err(invalid(format!("not a valid {}", data_type))),
         }
     }
 
-    pub fn parse(s: &str, data_type: &PlankType) -> std::io::Result<Self> {
-        match data_type {
-            PlankType::Int32 => Ok(PlankData::Int32(s.parse::<i32>().unwrap())),
-            PlankType::Int64 => Ok(PlankData::Int64(s.parse::<i64>().unwrap())),
-            PlankType::Bool => Ok(PlankData::Bool(s.parse::<bool>().unwrap())),
-            PlankType::Struct(_) | PlankType::List(_) => Self::parse_extended_value(s),
-            _ => Ok(PlankData::Str(String::from(s))),
+    /// Converts the value to JSON, struct fields named after those of `data_type` and maps
+    /// as objects
+    pub fn to_json(&self, data_type: &PlankType) -> serde_json::Value {
+        use serde_json::Value;
+
+        if let (PlankData::List(entries), Some(value_type)) = (self, data_type.map_value()) {
+            return Value::Object(
+                entries
+                    .iter()
+                    .filter_map(|entry| match entry {
+                        PlankData::Struct(kv) => match kv.as_slice() {
+                            [PlankData::Str(key), value] => {
+                                Some((key.clone(), value.to_json(value_type)))
+                            }
+                            _ => None,
+                        },
+                        _ => None,
+                    })
+                    .collect(),
+            );
+        }
+        match (self, data_type.non_null()) {
+            (PlankData::Null, _) => Value::Null,
+            (PlankData::Str(s), _) => Value::String(s.clone()),
+            (PlankData::Int32(n), _) => Value::from(*n),
+            (PlankData::Int64(n), _) => Value::from(*n),
+            (PlankData::Bool(b), _) => Value::Bool(*b),
+            (PlankData::List(items), PlankType::List(item_type)) => {
+                Value::Array(items.iter().map(|item| item.to_json(item_type)).collect())
+            }
+            (PlankData::Struct(values), PlankType::Struct(fields)) => Value::Object(
+                fields
+                    .iter()
+                    .zip(values)
+                    .map(|(f, value)| (f.field_name().clone(), value.to_json(f.field_type())))
+                    .collect(),
+            ),
+            (PlankData::List(items) | PlankData::Struct(items), _) => {
+                Value::Array(items.iter().map(|item| item.to_json(data_type)).collect())
+            }
         }
     }
 
@@ -111,6 +198,7 @@ impl fmt::Display for PlankData {
             Self::Int32(n) => write!(f, "{}", n),
             Self::Int64(n) => write!(f, "{}", n),
             Self::Bool(b) => write!(f, "{}", b),
+            Self::Null => write!(f, "null"),
             Self::Struct(fields) => {
                 write!(f, "{{")?;
                 for (i, field) in fields.iter().enumerate() {
@@ -181,75 +269,76 @@ impl Serialize for PlankData {
                 }
                 Ok(v)
             }
+            PlankData::Null => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "nulls are stored as definition levels",
+            )),
         }
     }
 }
 
 impl<'a> Deserialize<'a> for PlankData {
     type Schema = PlankType;
-    fn from_bytes(bytes: &[u8], schema: &'a Self::Schema) -> std::io::Result<Self> {
+    fn from_bytes(bytes: &[u8], schema: &'a Self::Schema) -> std::io::Result<(Self, usize)> {
         // let value_type = schema.field_type();
         match schema {
             PlankType::Str => {
-                let size = u32::from_le_bytes(bytes[..4].try_into().map_err(|_| {
-                    std::io::Error::new(std::io::ErrorKind::InvalidData, "expected u32")
-                })?);
-                let field_value = std::str::from_utf8(&bytes[4..4 + size as usize])
-                    .map_err(|_| {
+                let size = read_u32(bytes, 0)? as usize;
+                let field_value = bytes
+                    .get(4..4 + size)
+                    .and_then(|b| std::str::from_utf8(b).ok())
+                    .ok_or_else(|| {
                         std::io::Error::new(
                             std::io::ErrorKind::InvalidData,
                             format!("expected to read {} bytes", size),
                         )
                     })?
                     .to_string();
-                Ok(PlankData::Str(field_value))
+                Ok((PlankData::Str(field_value), 4 + size))
             }
-            PlankType::Int32 => {
-                let n = i32::from_le_bytes(bytes[..4].try_into().map_err(|_| {
-                    std::io::Error::new(std::io::ErrorKind::InvalidData, "expected u32")
-                })?);
-                Ok(PlankData::Int32(n))
-            }
-            PlankType::Int64 => {
-                let n = i64::from_le_bytes(bytes[..8].try_into().map_err(|_| {
-                    std::io::Error::new(std::io::ErrorKind::InvalidData, "expected u64")
-                })?);
-                Ok(PlankData::Int64(n))
-            }
-            PlankType::Bool => match bytes[0] {
-                0 => Ok(PlankData::Bool(false)),
-                1 => Ok(PlankData::Bool(true)),
+            PlankType::Int32 => Ok((PlankData::Int32(read_u32(bytes, 0)? as i32), 4)),
+            PlankType::Int64 => Ok((PlankData::Int64(read_u64(bytes, 0)? as i64), 8)),
+            PlankType::Bool => match bytes.first() {
+                Some(0) => Ok((PlankData::Bool(false), 1)),
+                Some(1) => Ok((PlankData::Bool(true), 1)),
                 _ => Err(std::io::Error::new(
                     std::io::ErrorKind::InvalidData,
                     "expected bool",
                 )),
             },
             PlankType::Struct(fields) => {
-                let size = u32::from_le_bytes(bytes[..4].try_into().map_err(|_| {
-                    std::io::Error::new(std::io::ErrorKind::InvalidData, "expected struct size")
-                })?) as usize;
-                let mut v = Vec::new();
+                let size = read_u32(bytes, 0)? as usize;
+                if size != fields.len() {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("expected {} struct fields, found {}", fields.len(), size),
+                    ));
+                }
+                let mut v = Vec::with_capacity(size);
                 let mut pos = 4;
-                for i in 0..size {
-                    let data = PlankData::from_bytes(&bytes[pos..], &fields[i].field_type())?;
-                    pos += data.to_bytes()?.len();
+                for field in fields {
+                    let (data, consumed) =
+                        PlankData::from_bytes(&bytes[pos..], field.field_type())?;
+                    pos += consumed;
                     v.push(data);
                 }
-                Ok(PlankData::Struct(v))
+                Ok((PlankData::Struct(v), pos))
             }
             PlankType::List(list_type) => {
-                let size = u32::from_le_bytes(bytes[..4].try_into().map_err(|_| {
-                    std::io::Error::new(std::io::ErrorKind::InvalidData, "expected struct size")
-                })?) as usize;
+                let size = read_u32(bytes, 0)? as usize;
                 let mut v = Vec::new();
                 let mut pos = 4;
                 for _ in 0..size {
-                    let data = PlankData::from_bytes(&bytes[pos..], list_type.as_ref())?;
-                    pos += data.to_bytes()?.len();
+                    let (data, consumed) =
+                        PlankData::from_bytes(&bytes[pos..], list_type.as_ref())?;
+                    pos += consumed;
                     v.push(data);
                 }
-                Ok(PlankData::List(v))
+                Ok((PlankData::List(v), pos))
             }
+            // Values of a nullable type are stored as their inner type
+            PlankType::Nullable(inner) => PlankData::from_bytes(bytes, inner),
+            PlankType::Map(value) => PlankData::from_bytes(bytes, &PlankType::map_entries(value)),
         }
     }
 }
@@ -264,9 +353,10 @@ mod tests {
         let data = PlankData::Int32(1);
 
         let serialized = data.to_bytes().unwrap();
-        let deserialized = PlankData::from_bytes(&serialized, &PlankType::Int32).unwrap();
+        let (deserialized, size) = PlankData::from_bytes(&serialized, &PlankType::Int32).unwrap();
 
         assert_eq!(data, deserialized);
+        assert_eq!(size, serialized.len());
     }
 
     #[test]
@@ -277,7 +367,7 @@ mod tests {
         ]);
 
         let serialized = data.to_bytes().unwrap();
-        let deserialized = PlankData::from_bytes(
+        let (deserialized, size) = PlankData::from_bytes(
             &serialized,
             &PlankType::Struct(vec![
                 PlankField::new("col1", PlankType::Int32),
@@ -287,6 +377,7 @@ mod tests {
         .unwrap();
 
         assert_eq!(data, deserialized);
+        assert_eq!(size, serialized.len());
     }
 
     #[test]
@@ -294,11 +385,12 @@ mod tests {
         let data = PlankData::List(vec![PlankData::Int32(1), PlankData::Int32(2)]);
 
         let serialized = data.to_bytes().unwrap();
-        let deserialized =
+        let (deserialized, size) =
             PlankData::from_bytes(&serialized, &PlankType::List(Box::new(PlankType::Int32)))
                 .unwrap();
 
         assert_eq!(data, deserialized);
+        assert_eq!(size, serialized.len());
     }
 
     #[test]
@@ -338,6 +430,67 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_rejects_invalid_value() {
+        assert_eq!(
+            PlankData::parse("7", &PlankType::Int64.nullable()).unwrap(),
+            PlankData::Int64(7)
+        );
+        let err = PlankData::parse("seven", &PlankType::Int32).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("Int32"), "{}", err);
+        assert!(PlankData::parse("yes", &PlankType::Bool).is_err());
+    }
+
+    #[test]
+    fn test_parse_matches_struct_keys() {
+        let person = PlankType::Struct(vec![
+            PlankField::new("name", PlankType::Str),
+            PlankField::new("age", PlankType::Int32.nullable()),
+        ]);
+        assert_eq!(
+            PlankData::parse(r#"{"age": 10, "name": "me"}"#, &person).unwrap(),
+            PlankData::Struct(vec![PlankData::Str("me".to_string()), PlankData::Int32(10)])
+        );
+        assert_eq!(
+            PlankData::parse(r#"{"name": "me"}"#, &person).unwrap(),
+            PlankData::Struct(vec![PlankData::Str("me".to_string()), PlankData::Null])
+        );
+        let err = PlankData::parse(r#"{"name": {"first": "me"}}"#, &person).unwrap_err();
+        assert_eq!(err.to_string(), "not a valid Str");
+
+        let tags = PlankType::map(PlankType::Int32);
+        let value = PlankData::parse(r#"{"a": 1, "b": 2}"#, &tags).unwrap();
+        assert_eq!(value.to_json(&tags), serde_json::json!({"a": 1, "b": 2}));
+    }
+
+    #[test]
+    fn test_key_value_lists_are_not_maps() {
+        let pairs = PlankType::List(Box::new(PlankType::Struct(vec![
+            PlankField::new("key", PlankType::Str),
+            PlankField::new("value", PlankType::Int32),
+        ])));
+        assert_eq!(pairs.map_value(), None);
+        let json = serde_json::json!([{"key": "a", "value": 1}, {"key": "a", "value": 2}]);
+        let value = PlankData::from_json(&json, &pairs).unwrap();
+        assert_eq!(value.to_json(&pairs), json);
+        assert!(PlankData::parse(r#"{"a": 1}"#, &pairs).is_err());
+
+        // A map holds the same values, but reads back as an object
+        let map = PlankType::map(PlankType::Int32);
+        let (read, _) = PlankType::from_bytes(&map.to_bytes().unwrap(), &()).unwrap();
+        assert_eq!(read, map);
+        let value = PlankData::parse(r#"{"a": 1}"#, &map).unwrap();
+        assert_eq!(
+            value,
+            PlankData::List(vec![PlankData::Struct(vec![
+                PlankData::Str("a".to_string()),
+                PlankData::Int32(1)
+            ])])
+        );
+        assert_eq!(value.to_json(&map), serde_json::json!({"a": 1}));
+    }
+
     #[test]
     fn test_get_struct_field() {
         let s = PlankData::Struct(vec![PlankData::Str("me".to_string()), PlankData::Int32(10)]);
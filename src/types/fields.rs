@@ -1,7 +1,5 @@
-use crate::serde::{Deserialize, Serialize};
+use crate::serde::{Deserialize, Serialize, read_u32};
 use crate::types::types::PlankType;
-use std::fmt;
-use std::str::FromStr;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PlankField {
@@ -63,14 +61,13 @@ impl Serialize for PlankField {
 
 impl<'a> Deserialize<'a> for PlankField {
     type Schema = ();
-    fn from_bytes(bytes: &[u8], schema: &'a Self::Schema) -> std::io::Result<Self> {
-        let size =
-            u32::from_le_bytes(bytes[..4].try_into().map_err(|_| {
-                std::io::Error::new(std::io::ErrorKind::InvalidData, "expected u32")
-            })?) as usize;
-
-        let field_name = std::str::from_utf8(&bytes[4..4 + size as usize])
-            .map_err(|_| {
+    fn from_bytes(bytes: &[u8], schema: &'a Self::Schema) -> std::io::Result<(Self, usize)> {
+        let size = read_u32(bytes, 0)? as usize;
+
+        let field_name = bytes
+            .get(4..4 + size)
+            .and_then(|b| std::str::from_utf8(b).ok())
+            .ok_or_else(|| {
                 std::io::Error::new(
                     std::io::ErrorKind::InvalidData,
                     format!("expected to read {} bytes", size),
@@ -78,17 +75,15 @@ impl<'a> Deserialize<'a> for PlankField {
             })?
             .to_string();
 
-        let field_type = PlankType::from_bytes(
-            bytes[4 + size..].try_into().map_err(|_| {
-                std::io::Error::new(std::io::ErrorKind::InvalidData, "expected type")
-            })?,
-            schema,
-        )?;
+        let (field_type, type_size) = PlankType::from_bytes(&bytes[4 + size..], schema)?;
 
-        Ok(PlankField {
-            name: field_name,
-            field_type,
-        })
+        Ok((
+            PlankField {
+                name: field_name,
+                field_type,
+            },
+            4 + size + type_size,
+        ))
     }
 }
 
@@ -101,7 +96,9 @@ mod tests {
         let field = PlankField::new("name", PlankType::Str);
 
         let serialized = field.to_bytes().unwrap();
-        let deserialized = PlankField::from_bytes(&serialized, &()).unwrap();
+        let (deserialized, size) = PlankField::from_bytes(&serialized, &()).unwrap();
+
+        assert_eq!(size, serialized.len());
 
         assert_eq!(deserialized.name, field.name);
         assert_eq!(deserialized.field_type, field.field_type);
@@ -124,8 +121,8 @@ mod tests {
             PlankField::new(
                 "person",
                 PlankType::Struct(vec![
-                    PlankField::new("name", PlankType::Str),
-                    PlankField::new("age", PlankType::Int32)
+                    PlankField::new("name", PlankType::Str.nullable()),
+                    PlankField::new("age", PlankType::Int32.nullable())
                 ])
             )
         )
@@ -0,0 +1,774 @@
+use std::fmt;
+use std::io::{Error, ErrorKind};
+
+use ::serde::ser::{self, Impossible, Serialize};
+use serde_json::{Map, Value};
+
+use crate::file::ingest::INFER_RECORDS;
+use crate::types::{data::PlankData, fields::PlankField, record::mismatch, types::PlankType};
+
+/// The schema of rows serialized like `sample`, one column per field typed as the field
+/// serializes: integers up to 32 bits are `Int32` and larger ones `Int64`, `Option`s are
+/// nullable, sequences lists, structs structs and maps lists of `key` and `value` structs. A
+/// field that is `None` or an empty sequence in the sample has no type and is an error.
+pub fn schema_of<S: Serialize>(sample: &S) -> std::io::Result<Vec<PlankField>> {
+    let (fields, _) = record_row(sample)?;
+    if let Some((name, _)) = fields.iter().find(|(_, shape)| !shape.is_known()) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "field {} is None or empty in the sample, its type is unknown",
+                name
+            ),
+        ));
+    }
+    Ok(fields
+        .into_iter()
+        .map(|(name, shape)| PlankField::new(&name, shape.into_type()))
+        .collect())
+}
+
+/// Rows serialized by a writer without a schema, kept until their fields have shown a type,
+/// then written with the schema they give
+#[derive(Default)]
+pub(crate) struct Sample {
+    fields: Vec<(String, Shape)>,
+    rows: Vec<RecordedRow>,
+}
+
+impl Sample {
+    pub(crate) fn push<S: Serialize>(&mut self, value: &S) -> std::io::Result<()> {
+        let (fields, values) = record_row(value)?;
+        self.fields = if self.rows.is_empty() {
+            fields.clone()
+        } else {
+            Shape::merge_fields(std::mem::take(&mut self.fields), fields.clone())
+                .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?
+        };
+        self.rows.push((fields, values));
+        Ok(())
+    }
+
+    /// Whether every field has a type, or so many rows are kept that the fields still
+    /// without one, only ever `None` or empty, are taken as strings
+    pub(crate) fn is_settled(&self) -> bool {
+        self.rows.len() >= INFER_RECORDS || self.fields.iter().all(|(_, s)| s.is_known())
+    }
+
+    pub(crate) fn schema(&self) -> Vec<PlankField> {
+        self.fields
+            .iter()
+            .map(|(name, shape)| PlankField::new(name, shape.clone().into_type()))
+            .collect()
+    }
+
+    pub(crate) fn into_rows(self, schema: &[PlankField]) -> std::io::Result<Vec<Vec<PlankData>>> {
+        self.rows
+            .into_iter()
+            .map(|(fields, values)| conform_fields(&fields, values, schema, true))
+            .collect()
+    }
+}
+
+/// Converts a serialized value to a row of `schema`, its fields matched to columns by name
+pub(crate) fn to_row<S: Serialize>(
+    value: &S,
+    schema: &[PlankField],
+) -> std::io::Result<Vec<PlankData>> {
+    let (fields, values) = record_row(value)?;
+    conform_fields(&fields, values, schema, true)
+}
+
+/// Matches serialized fields to the fields of a struct, or to the columns of the file
+fn conform_fields(
+    shapes: &[(String, Shape)],
+    mut values: Vec<PlankData>,
+    fields: &[PlankField],
+    columns: bool,
+) -> std::io::Result<Vec<PlankData>> {
+    let noun = if columns { "column" } else { "field" };
+    if let Some((key, _)) = shapes
+        .iter()
+        .find(|(key, _)| !fields.iter().any(|f| f.field_name() == key))
+    {
+        let of = if columns { "the file" } else { "the struct" };
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("field {} is not a {} of {}", key, noun, of),
+        ));
+    }
+    fields
+        .iter()
+        .map(
+            |f| match shapes.iter().position(|(key, _)| key == f.field_name()) {
+                None if f.field_type().non_null() == f.field_type() => Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("{} {} is missing and not nullable", noun, f.field_name()),
+                )),
+                None => Ok(PlankData::Null),
+                Some(i) => {
+                    let value = std::mem::replace(&mut values[i], PlankData::Null);
+                    conform(value, &shapes[i].1, f.field_type()).map_err(|e| {
+                        Error::new(e.kind(), format!("{} {}: {}", noun, f.field_name(), e))
+                    })
+                }
+            },
+        )
+        .collect()
+}
+
+/// Checks a serialized value is one of `data_type`, widening `Int32`s to `Int64`s
+fn conform(data: PlankData, shape: &Shape, data_type: &PlankType) -> std::io::Result<PlankData> {
+    match (data, shape, data_type.non_null()) {
+        (PlankData::Null, _, t) if t == data_type => {
+            Err(Error::new(ErrorKind::InvalidData, "column is not nullable"))
+        }
+        (PlankData::Null, _, _) => Ok(PlankData::Null),
+        (data, Shape::Nullable(shape), _) => conform(data, shape, data_type),
+        (PlankData::Int32(n), _, PlankType::Int64) => Ok(PlankData::Int64(n.into())),
+        (data @ PlankData::Int32(_), _, PlankType::Int32)
+        | (data @ PlankData::Int64(_), _, PlankType::Int64)
+        | (data @ PlankData::Bool(_), _, PlankType::Bool)
+        | (data @ PlankData::Str(_), _, PlankType::Str) => Ok(data),
+        (PlankData::List(items), Shape::List(item), PlankType::List(item_type)) => items
+            .into_iter()
+            .map(|value| conform(value, item, item_type))
+            .collect::<std::io::Result<Vec<_>>>()
+            .map(PlankData::List),
+        (PlankData::List(entries), Shape::Map(value), PlankType::Map(value_type)) => entries
+            .into_iter()
+            .map(|entry| match entry {
+                PlankData::Struct(mut kv) if kv.len() == 2 => {
+                    let data = kv.pop().unwrap_or(PlankData::Null);
+                    kv.push(conform(data, value, value_type)?);
+                    Ok(PlankData::Struct(kv))
+                }
+                entry => Err(mismatch(data_type, &entry)),
+            })
+            .collect::<std::io::Result<Vec<_>>>()
+            .map(PlankData::List),
+        (PlankData::Struct(values), Shape::Struct(shapes), PlankType::Struct(fields)) => {
+            conform_fields(shapes, values, fields, false).map(PlankData::Struct)
+        }
+        (data, _, _) => Err(mismatch(data_type, &data)),
+    }
+}
+
+/// Deserializes a row of `schema` into `D`, columns matched to fields by name
+pub(crate) fn from_row<D: ::serde::de::DeserializeOwned>(
+    schema: &[PlankField],
+    row: Vec<PlankData>,
+) -> std::io::Result<D> {
+    let object = schema
+        .iter()
+        .zip(&row)
+        .map(|(f, value)| (f.field_name().clone(), value.to_json(f.field_type())))
+        .collect::<Map<_, _>>();
+    serde_json::from_value(Value::Object(object)).map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("row does not fit {}: {}", std::any::type_name::<D>(), e),
+        )
+    })
+}
+
+/// The type of a serialized value, as far as the value shows it
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Shape {
+    /// The type of a `None`, or of the items of an empty sequence
+    Unknown,
+    Int32,
+    Int64,
+    Bool,
+    Str,
+    List(Box<Shape>),
+    Struct(Vec<(String, Shape)>),
+    Nullable(Box<Shape>),
+    /// The shape of the values of a map
+    Map(Box<Shape>),
+}
+
+impl Shape {
+    fn nullable(self) -> Self {
+        match self {
+            Shape::Nullable(_) => self,
+            _ => Shape::Nullable(Box::new(self)),
+        }
+    }
+
+    fn is_known(&self) -> bool {
+        match self {
+            Shape::Unknown => false,
+            Shape::List(item) | Shape::Nullable(item) | Shape::Map(item) => item.is_known(),
+            Shape::Struct(fields) => fields.iter().all(|(_, s)| s.is_known()),
+            _ => true,
+        }
+    }
+
+    fn merge(self, other: Self) -> Result<Self, String> {
+        Ok(match (self, other) {
+            (Shape::Unknown, s) | (s, Shape::Unknown) => s,
+            (Shape::Nullable(a), Shape::Nullable(b)) => a.merge(*b)?.nullable(),
+            (Shape::Nullable(a), b) | (b, Shape::Nullable(a)) => a.merge(b)?.nullable(),
+            (Shape::Int32, Shape::Int64) | (Shape::Int64, Shape::Int32) => Shape::Int64,
+            (Shape::List(a), Shape::List(b)) => Shape::List(Box::new(a.merge(*b)?)),
+            (Shape::Map(a), Shape::Map(b)) => Shape::Map(Box::new(a.merge(*b)?)),
+            (Shape::Struct(a), Shape::Struct(b)) => Shape::Struct(Self::merge_fields(a, b)?),
+            (a, b) if a == b => a,
+            (a, b) => return Err(format!("values of {} and {}", a, b)),
+        })
+    }
+
+    /// Merges the fields of two values, those missing from either becoming nullable
+    fn merge_fields(
+        fields: Vec<(String, Shape)>,
+        mut other: Vec<(String, Shape)>,
+    ) -> Result<Vec<(String, Shape)>, String> {
+        let mut merged = Vec::with_capacity(fields.len());
+        for (name, shape) in fields {
+            let shape = match other.iter().position(|(n, _)| *n == name) {
+                Some(i) => shape
+                    .merge(other.remove(i).1)
+                    .map_err(|e| format!("field {}: {}", name, e))?,
+                None => shape.nullable(),
+            };
+            merged.push((name, shape));
+        }
+        merged.extend(other.into_iter().map(|(name, s)| (name, s.nullable())));
+        Ok(merged)
+    }
+
+    /// The type of the shape, strings where it is unknown
+    fn into_type(self) -> PlankType {
+        match self {
+            Shape::Unknown | Shape::Str => PlankType::Str,
+            Shape::Int32 => PlankType::Int32,
+            Shape::Int64 => PlankType::Int64,
+            Shape::Bool => PlankType::Bool,
+            Shape::List(item) => PlankType::List(Box::new(item.into_type())),
+            Shape::Struct(fields) => PlankType::Struct(
+                fields
+                    .into_iter()
+                    .map(|(name, s)| PlankField::new(&name, s.into_type()))
+                    .collect(),
+            ),
+            Shape::Nullable(inner) => inner.into_type().nullable(),
+            Shape::Map(value) => PlankType::map(value.into_type()),
+        }
+    }
+}
+
+impl fmt::Display for Shape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Shape::Unknown => write!(f, "unknown type"),
+            shape => write!(f, "{}", shape.clone().into_type()),
+        }
+    }
+}
+
+/// Serializes a row into the shape and the value of each of its fields
+fn record_row<S: Serialize>(value: &S) -> std::io::Result<RecordedRow> {
+    match value.serialize(Recorder { row: true }) {
+        Ok((Shape::Struct(fields), PlankData::Struct(values))) => Ok((fields, values)),
+        Ok(_) => Err(not_a_row()),
+        Err(e) => Err(Error::new(ErrorKind::InvalidInput, e.0)),
+    }
+}
+
+fn not_a_row() -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        "rows are serialized from structs or maps",
+    )
+}
+
+#[derive(Debug)]
+struct RecordError(String);
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for RecordError {}
+
+impl ser::Error for RecordError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        RecordError(msg.to_string())
+    }
+}
+
+/// A serializer recording the shape of a value along with it. A row is a struct or a map,
+/// whose keys are then its fields; maps below it are lists of `key` and `value` structs.
+struct Recorder {
+    row: bool,
+}
+
+type Recorded = (Shape, PlankData);
+
+/// The fields of a serialized row, with their values
+type RecordedRow = (Vec<(String, Shape)>, Vec<PlankData>);
+
+impl Recorder {
+    fn value(self, shape: Shape, data: PlankData) -> Result<Recorded, RecordError> {
+        self.check_value()?;
+        Ok((shape, data))
+    }
+
+    fn check_value(&self) -> Result<(), RecordError> {
+        match self.row {
+            true => Err(RecordError(not_a_row().to_string())),
+            false => Ok(()),
+        }
+    }
+
+    fn unsupported(what: &str) -> RecordError {
+        RecordError(format!("{} have no column type", what))
+    }
+}
+
+impl ser::Serializer for Recorder {
+    type Ok = Recorded;
+    type Error = RecordError;
+    type SerializeSeq = SeqRecorder;
+    type SerializeTuple = SeqRecorder;
+    type SerializeTupleStruct = SeqRecorder;
+    type SerializeTupleVariant = Impossible<Recorded, RecordError>;
+    type SerializeMap = MapRecorder;
+    type SerializeStruct = StructRecorder;
+    type SerializeStructVariant = Impossible<Recorded, RecordError>;
+
+    fn serialize_bool(self, v: bool) -> Result<Recorded, RecordError> {
+        self.value(Shape::Bool, PlankData::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Recorded, RecordError> {
+        self.serialize_i32(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Recorded, RecordError> {
+        self.serialize_i32(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Recorded, RecordError> {
+        self.value(Shape::Int32, PlankData::Int32(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Recorded, RecordError> {
+        self.value(Shape::Int64, PlankData::Int64(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Recorded, RecordError> {
+        self.serialize_i32(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Recorded, RecordError> {
+        self.serialize_i32(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Recorded, RecordError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Recorded, RecordError> {
+        let v =
+            i64::try_from(v).map_err(|_| RecordError(format!("{} does not fit an Int64", v)))?;
+        self.serialize_i64(v)
+    }
+
+    fn serialize_f32(self, _: f32) -> Result<Recorded, RecordError> {
+        Err(Self::unsupported("floating point numbers"))
+    }
+
+    fn serialize_f64(self, _: f64) -> Result<Recorded, RecordError> {
+        Err(Self::unsupported("floating point numbers"))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Recorded, RecordError> {
+        self.value(Shape::Str, PlankData::Str(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Recorded, RecordError> {
+        self.value(Shape::Str, PlankData::Str(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Recorded, RecordError> {
+        let bytes = v.iter().map(|&b| PlankData::Int32(b.into())).collect();
+        self.value(Shape::List(Box::new(Shape::Int32)), PlankData::List(bytes))
+    }
+
+    fn serialize_none(self) -> Result<Recorded, RecordError> {
+        self.value(Shape::Unknown.nullable(), PlankData::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Recorded, RecordError> {
+        let (shape, data) = value.serialize(Recorder { row: false })?;
+        self.value(shape.nullable(), data)
+    }
+
+    fn serialize_unit(self) -> Result<Recorded, RecordError> {
+        self.serialize_none()
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<Recorded, RecordError> {
+        self.serialize_none()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+    ) -> Result<Recorded, RecordError> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<Recorded, RecordError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<Recorded, RecordError> {
+        Err(Self::unsupported("enum variants holding values"))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqRecorder, RecordError> {
+        self.check_value()?;
+        Ok(SeqRecorder {
+            item: Shape::Unknown,
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqRecorder, RecordError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        len: usize,
+    ) -> Result<SeqRecorder, RecordError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, RecordError> {
+        Err(Self::unsupported("enum variants holding values"))
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<MapRecorder, RecordError> {
+        Ok(MapRecorder {
+            row: self.row,
+            key: None,
+            value: Shape::Unknown,
+            fields: Vec::new(),
+            values: Vec::new(),
+        })
+    }
+
+    fn serialize_struct(self, _: &'static str, len: usize) -> Result<StructRecorder, RecordError> {
+        Ok(StructRecorder {
+            fields: Vec::with_capacity(len),
+            values: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, RecordError> {
+        Err(Self::unsupported("enum variants holding values"))
+    }
+}
+
+struct SeqRecorder {
+    item: Shape,
+    items: Vec<PlankData>,
+}
+
+impl SeqRecorder {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), RecordError> {
+        let (shape, data) = value.serialize(Recorder { row: false })?;
+        self.item = std::mem::replace(&mut self.item, Shape::Unknown)
+            .merge(shape)
+            .map_err(|e| RecordError(format!("sequence holds {}", e)))?;
+        self.items.push(data);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Recorded, RecordError> {
+        Ok((
+            Shape::List(Box::new(self.item)),
+            PlankData::List(self.items),
+        ))
+    }
+}
+
+impl ser::SerializeSeq for SeqRecorder {
+    type Ok = Recorded;
+    type Error = RecordError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), RecordError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Recorded, RecordError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SeqRecorder {
+    type Ok = Recorded;
+    type Error = RecordError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), RecordError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Recorded, RecordError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqRecorder {
+    type Ok = Recorded;
+    type Error = RecordError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), RecordError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Recorded, RecordError> {
+        self.finish()
+    }
+}
+
+struct MapRecorder {
+    row: bool,
+    key: Option<String>,
+    /// The shape of the values of a map below the row
+    value: Shape,
+    fields: Vec<(String, Shape)>,
+    values: Vec<PlankData>,
+}
+
+impl ser::SerializeMap for MapRecorder {
+    type Ok = Recorded;
+    type Error = RecordError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), RecordError> {
+        self.key = Some(match key.serialize(Recorder { row: false })?.1 {
+            PlankData::Str(key) => key,
+            PlankData::Int32(key) => key.to_string(),
+            PlankData::Int64(key) => key.to_string(),
+            _ => {
+                return Err(RecordError(
+                    "map keys must be strings or integers".to_string(),
+                ));
+            }
+        });
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), RecordError> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| RecordError("map value without a key".to_string()))?;
+        let (shape, data) = value.serialize(Recorder { row: false })?;
+        if self.row {
+            self.fields.push((key, shape));
+            self.values.push(data);
+        } else {
+            self.value = std::mem::replace(&mut self.value, Shape::Unknown)
+                .merge(shape)
+                .map_err(|e| RecordError(format!("map holds {}", e)))?;
+            self.values
+                .push(PlankData::Struct(vec![PlankData::Str(key), data]));
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<Recorded, RecordError> {
+        if self.row {
+            return Ok((Shape::Struct(self.fields), PlankData::Struct(self.values)));
+        }
+        Ok((
+            Shape::Map(Box::new(self.value)),
+            PlankData::List(self.values),
+        ))
+    }
+}
+
+struct StructRecorder {
+    fields: Vec<(String, Shape)>,
+    values: Vec<PlankData>,
+}
+
+impl ser::SerializeStruct for StructRecorder {
+    type Ok = Recorded;
+    type Error = RecordError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), RecordError> {
+        let (shape, data) = value.serialize(Recorder { row: false })?;
+        self.fields.push((key.to_string(), shape));
+        self.values.push(data);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Recorded, RecordError> {
+        Ok((Shape::Struct(self.fields), PlankData::Struct(self.values)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Serialize};
+
+    use crate::file::format::FeatureFlags;
+    use crate::{PlankField, PlankReader, PlankType, PlankWriter, schema_of};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Address {
+        city: String,
+        zip: Option<i32>,
+    }
+
+    /// Shaped like a map entry, but a struct of its own
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Pair {
+        key: String,
+        value: i64,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Customer {
+        id: i64,
+        address: Address,
+        tags: Vec<String>,
+        scores: BTreeMap<String, i64>,
+        pairs: Vec<Pair>,
+        note: Option<String>,
+    }
+
+    #[test]
+    fn test_serialize_and_deserialize_rows() {
+        let dir = std::env::temp_dir().join(format!("plank-json-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("customers.plank");
+        let customers: Vec<Customer> = (0..3)
+            .map(|i| Customer {
+                id: i,
+                address: Address {
+                    city: format!("city {}", i),
+                    zip: (i != 1).then_some(1000 + i as i32),
+                },
+                tags: vec![format!("tag {}", i); i as usize + 1],
+                scores: (0..i).map(|s| (format!("s{}", s), s * 10)).collect(),
+                pairs: vec![Pair {
+                    key: "k".to_string(),
+                    value: i,
+                }],
+                note: (i == 2).then(|| "late".to_string()),
+            })
+            .collect();
+
+        // The first value has no scores and no note, their types are only known from later ones
+        let err = schema_of(&customers[0]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "field scores is None or empty in the sample, its type is unknown"
+        );
+        let mut writer = PlankWriter::new(&path).unwrap();
+        writer.serialize_rows(&customers).unwrap();
+        let footer = writer.finish().unwrap();
+        let address = PlankType::Struct(vec![
+            PlankField::new("city", PlankType::Str),
+            PlankField::new("zip", PlankType::Int32.nullable()),
+        ]);
+        assert_eq!(
+            footer.schema,
+            [
+                PlankField::new("id", PlankType::Int64),
+                PlankField::new("address", address),
+                PlankField::new("tags", PlankType::List(Box::new(PlankType::Str))),
+                PlankField::new("scores", PlankType::map(PlankType::Int64)),
+                PlankField::new(
+                    "pairs",
+                    PlankType::List(Box::new(PlankType::Struct(vec![
+                        PlankField::new("key", PlankType::Str),
+                        PlankField::new("value", PlankType::Int64),
+                    ])))
+                ),
+                PlankField::new("note", PlankType::Str.nullable()),
+            ]
+        );
+        assert_eq!(schema_of(&customers[2]).unwrap(), footer.schema);
+        assert!(footer.features().contains(FeatureFlags::MAP_COLUMNS));
+
+        let mut reader = PlankReader::open(&path).unwrap();
+        let read = reader
+            .deserialize_rows::<Customer>()
+            .collect::<std::io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(read, customers);
+
+        // A struct that does not fit the file is refused with the field at fault
+        #[derive(Debug, Deserialize)]
+        struct Narrow {
+            #[allow(dead_code)]
+            id: bool,
+        }
+        let err = reader
+            .deserialize_rows::<Narrow>()
+            .next()
+            .unwrap()
+            .unwrap_err();
+        assert!(err.to_string().contains("expected a boolean"), "{}", err);
+
+        let schema = vec![PlankField::new("id", PlankType::Int64)];
+        let mut writer = PlankWriter::with_schema(&path, schema).unwrap();
+        let err = writer.serialize_row(&customers[0]).unwrap_err();
+        assert_eq!(err.to_string(), "field address is not a column of the file");
+
+        // Values are not converted to the type of their column
+        #[derive(Serialize)]
+        struct Row {
+            id: i64,
+        }
+        let schema = vec![PlankField::new("id", PlankType::Str)];
+        let mut writer = PlankWriter::with_schema(&path, schema).unwrap();
+        let err = writer.serialize_row(&Row { id: 7 }).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "column id: expected a value of Str, found 7"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
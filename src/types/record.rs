@@ -0,0 +1,251 @@
+use std::io::{Error, ErrorKind};
+
+use crate::types::{data::PlankData, fields::PlankField, types::PlankType};
+
+/// A Rust value stored in a single column. Implemented for the primitive types, `Vec<T>`
+/// as lists, `Option<T>` as nullable columns and by `#[derive(PlankRecord)]` for structs.
+pub trait PlankValue: Sized {
+    fn plank_type() -> PlankType;
+
+    fn to_plank(&self) -> PlankData;
+
+    fn from_plank(data: PlankData) -> std::io::Result<Self>;
+}
+
+/// A Rust struct stored as a row, one column per field. Usually derived with
+/// `#[derive(PlankRecord)]`.
+pub trait PlankRecord: Sized {
+    fn schema() -> Vec<PlankField>;
+
+    fn to_row(&self) -> Vec<PlankData>;
+
+    fn from_row(row: Vec<PlankData>) -> std::io::Result<Self>;
+}
+
+macro_rules! primitive_value {
+    ($rust:ty, $variant:ident) => {
+        impl PlankValue for $rust {
+            fn plank_type() -> PlankType {
+                PlankType::$variant
+            }
+
+            fn to_plank(&self) -> PlankData {
+                PlankData::$variant(self.clone())
+            }
+
+            fn from_plank(data: PlankData) -> std::io::Result<Self> {
+                match data {
+                    PlankData::$variant(value) => Ok(value),
+                    other => Err(mismatch(&Self::plank_type(), &other)),
+                }
+            }
+        }
+    };
+}
+
+primitive_value!(i32, Int32);
+primitive_value!(i64, Int64);
+primitive_value!(bool, Bool);
+primitive_value!(String, Str);
+
+impl<T: PlankValue> PlankValue for Vec<T> {
+    fn plank_type() -> PlankType {
+        PlankType::List(Box::new(T::plank_type()))
+    }
+
+    fn to_plank(&self) -> PlankData {
+        PlankData::List(self.iter().map(T::to_plank).collect())
+    }
+
+    fn from_plank(data: PlankData) -> std::io::Result<Self> {
+        match data {
+            PlankData::List(items) => items.into_iter().map(T::from_plank).collect(),
+            other => Err(mismatch(&Self::plank_type(), &other)),
+        }
+    }
+}
+
+impl<T: PlankValue> PlankValue for Option<T> {
+    fn plank_type() -> PlankType {
+        T::plank_type().nullable()
+    }
+
+    fn to_plank(&self) -> PlankData {
+        match self {
+            Some(value) => value.to_plank(),
+            None => PlankData::Null,
+        }
+    }
+
+    fn from_plank(data: PlankData) -> std::io::Result<Self> {
+        match data {
+            PlankData::Null => Ok(None),
+            value => T::from_plank(value).map(Some),
+        }
+    }
+}
+
+/// Checks the schema of a file is the one records are read as, naming the first field
+/// that differs
+pub(crate) fn check_schema(record: &[PlankField], file: &[PlankField]) -> std::io::Result<()> {
+    for (i, (expected, found)) in record.iter().zip(file).enumerate() {
+        if expected.field_name() != found.field_name() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "record field {} is {}, the file has column {} there",
+                    i,
+                    expected.field_name(),
+                    found.field_name()
+                ),
+            ));
+        }
+        if expected.field_type() != found.field_type() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "record field {} is {}, the file column is {}",
+                    expected.field_name(),
+                    expected.field_type(),
+                    found.field_type()
+                ),
+            ));
+        }
+    }
+    if record.len() != file.len() {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "record has {} fields, the file has {} columns",
+                record.len(),
+                file.len()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// The values of a row, checked to be as many as the fields of the record read from it
+pub fn row_values(
+    row: Vec<PlankData>,
+    width: usize,
+) -> std::io::Result<std::vec::IntoIter<PlankData>> {
+    if row.len() != width {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("row has {} values, the record {} fields", row.len(), width),
+        ));
+    }
+    Ok(row.into_iter())
+}
+
+/// Reads the next value of a row as the field `name`
+pub fn take_field<T: PlankValue>(
+    values: &mut std::vec::IntoIter<PlankData>,
+    name: &str,
+) -> std::io::Result<T> {
+    let value = values.next().ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("row has no value for field {}", name),
+        )
+    })?;
+    T::from_plank(value).map_err(|e| Error::new(e.kind(), format!("field {}: {}", name, e)))
+}
+
+pub fn mismatch(expected: &PlankType, found: &PlankData) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("expected a value of {}, found {}", expected, found),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{PlankField, PlankReader, PlankRecord, PlankType, PlankWriter};
+
+    #[derive(Debug, Clone, PartialEq, PlankRecord)]
+    struct Address {
+        city: String,
+        zip: Option<i32>,
+    }
+
+    #[derive(Debug, Clone, PartialEq, PlankRecord)]
+    struct Customer {
+        id: i64,
+        active: bool,
+        address: Address,
+        tags: Vec<String>,
+        r#type: Option<String>,
+    }
+
+    #[derive(Debug, PlankRecord)]
+    struct Other {
+        id: i32,
+    }
+
+    #[derive(Debug, Clone, PartialEq, PlankRecord)]
+    struct Tagged<T, U: Clone> {
+        value: T,
+        tags: Vec<U>,
+    }
+
+    #[test]
+    fn test_derived_records_round_trip() {
+        let dir = std::env::temp_dir().join(format!("plank-record-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("customers.plank");
+        let customers: Vec<Customer> = (0..3)
+            .map(|i| Customer {
+                id: i,
+                active: i % 2 == 0,
+                address: Address {
+                    city: format!("city {}", i),
+                    zip: (i > 0).then_some(1000 + i as i32),
+                },
+                tags: (0..i).map(|t| format!("tag {}", t)).collect(),
+                r#type: (i == 1).then(|| "retail".to_string()),
+            })
+            .collect();
+
+        let mut writer = PlankWriter::<Customer>::create(&path).unwrap();
+        writer.write_records(&customers).unwrap();
+        writer.finish().unwrap();
+
+        let mut reader = PlankReader::<Customer>::open_records(&path).unwrap();
+        assert_eq!(reader.schema(), Customer::schema());
+        assert_eq!(reader.schema()[4].field_name(), "type");
+        let read = reader
+            .records()
+            .collect::<std::io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(read, customers);
+
+        // A record of another shape is refused before any row is read
+        let err = PlankReader::<Other>::open_records(&path).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "record field id is Int32, the file column is Int64"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_generic_records() {
+        assert_eq!(
+            Tagged::<i64, Option<String>>::schema(),
+            [
+                PlankField::new("value", PlankType::Int64),
+                PlankField::new("tags", PlankType::List(Box::new(PlankType::Str.nullable()))),
+            ]
+        );
+        let record = Tagged {
+            value: Address {
+                city: "here".to_string(),
+                zip: None,
+            },
+            tags: vec![true, false],
+        };
+        assert_eq!(Tagged::from_row(record.to_row()).unwrap(), record);
+    }
+}
@@ -1,7 +1,6 @@
-use crate::serde::{Deserialize, Serialize};
+use crate::serde::{Deserialize, Serialize, read_u32};
 use crate::types::fields::PlankField;
 use std::fmt;
-use std::str::FromStr;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum PlankType {
@@ -12,6 +11,11 @@ pub enum PlankType {
     Bool,
     List(Box<PlankType>),
     Struct(Vec<PlankField>),
+    /// Values of the inner type or nulls
+    Nullable(Box<PlankType>),
+    /// String keys with values of the inner type, stored as a list of `key` and `value`
+    /// structs
+    Map(Box<PlankType>),
 }
 
 impl PlankType {
@@ -24,6 +28,23 @@ impl PlankType {
             Self::Bool => 1,
             Self::Struct(fields) => 1 + 4 + fields.iter().map(|f| f.encoded_size()).sum::<usize>(),
             Self::List(list_type) => 1 + list_type.encoded_size(),
+            Self::Nullable(inner) | Self::Map(inner) => 1 + inner.encoded_size(),
+        }
+    }
+
+    /// Wraps the type so it also holds nulls, unless it already does
+    pub fn nullable(self) -> Self {
+        match self {
+            Self::Nullable(_) => self,
+            _ => Self::Nullable(Box::new(self)),
+        }
+    }
+
+    /// The type of the values, without the nullability
+    pub fn non_null(&self) -> &PlankType {
+        match self {
+            Self::Nullable(inner) => inner.non_null(),
+            _ => self,
         }
     }
 
@@ -43,55 +64,186 @@ impl PlankType {
         PlankType::Str
     }
 
+    /// Infers the type of a JSON value the way NDJSON keys are: objects are structs with
+    /// nullable fields, numbers with a fraction and nulls are strings
     pub fn infer_extended_type(s: &str) -> std::io::Result<PlankType> {
-        let s = serde_json::from_str(s)
+        let value = serde_json::from_str(s)
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
-        match s {
-            serde_json::Value::Number(n) => {
-                if let Some(n) = n.as_i64() {
-                    if let Ok(_) = i32::try_from(n) {
-                        return Ok(PlankType::Int32);
-                    }
-                    Ok(PlankType::Int64)
-                } else {
-                    Err(std::io::Error::new(
-                        std::io::ErrorKind::InvalidData,
-                        "unsupported number",
-                    ))
-                }
+        Ok(JsonType::of(&value).into_type())
+    }
+
+    /// A map with string keys and values of `value`
+    pub fn map(value: PlankType) -> Self {
+        Self::Map(Box::new(value))
+    }
+
+    /// The type of the values, if the type is a map
+    pub fn map_value(&self) -> Option<&PlankType> {
+        match self.non_null() {
+            Self::Map(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Whether the type is or holds a map
+    pub(crate) fn has_map(&self) -> bool {
+        match self {
+            Self::Map(_) => true,
+            Self::List(inner) | Self::Nullable(inner) => inner.has_map(),
+            Self::Struct(fields) => fields.iter().any(|f| f.field_type().has_map()),
+            _ => false,
+        }
+    }
+
+    /// The list of `key` and `value` structs a map with values of `value` is stored as
+    pub(crate) fn map_entries(value: &PlankType) -> Self {
+        Self::List(Box::new(Self::Struct(vec![
+            PlankField::new("key", Self::Str),
+            PlankField::new("value", value.clone()),
+        ])))
+    }
+}
+
+/// Objects with more distinct keys than this are maps rather than structs
+pub(crate) const MAP_KEYS: usize = 64;
+
+/// The type of JSON values, merged across the values seen so far
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum JsonType {
+    /// Only nulls or empty lists
+    Unknown,
+    Int32,
+    Int64,
+    Bool,
+    Str,
+    /// The type of the items, and whether any was null
+    List(Box<JsonType>, bool),
+    Struct(Vec<(String, JsonType)>),
+    /// The type of the values of objects with too many keys to be structs
+    Map(Box<JsonType>),
+}
+
+impl JsonType {
+    pub(crate) fn of(value: &serde_json::Value) -> Self {
+        use serde_json::Value;
+        match value {
+            Value::Null => JsonType::Unknown,
+            Value::Bool(_) => JsonType::Bool,
+            Value::Number(n) => match n.as_i64() {
+                Some(n) if i32::try_from(n).is_ok() => JsonType::Int32,
+                Some(_) => JsonType::Int64,
+                // There is no floating point type, such numbers are kept as text
+                None => JsonType::Str,
+            },
+            Value::String(_) => JsonType::Str,
+            Value::Array(items) => JsonType::List(
+                Box::new(
+                    items
+                        .iter()
+                        .map(Self::of)
+                        .fold(JsonType::Unknown, Self::merge),
+                ),
+                items.iter().any(Value::is_null),
+            ),
+            Value::Object(object) => Self::struct_or_map(Self::of_object(object)),
+        }
+    }
+
+    pub(crate) fn of_object(
+        object: &serde_json::Map<String, serde_json::Value>,
+    ) -> Vec<(String, Self)> {
+        object
+            .iter()
+            .map(|(key, value)| (key.clone(), Self::of(value)))
+            .collect()
+    }
+
+    fn struct_or_map(fields: Vec<(String, JsonType)>) -> Self {
+        if fields.len() > MAP_KEYS {
+            JsonType::Map(Box::new(JsonType::merge_values(JsonType::Unknown, fields)))
+        } else {
+            JsonType::Struct(fields)
+        }
+    }
+
+    fn merge_values(value: Self, fields: Vec<(String, JsonType)>) -> Self {
+        fields
+            .into_iter()
+            .fold(value, |value, (_, t)| value.merge(t))
+    }
+
+    fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (JsonType::Unknown, t) | (t, JsonType::Unknown) => t,
+            (JsonType::Int32, JsonType::Int64) | (JsonType::Int64, JsonType::Int32) => {
+                JsonType::Int64
             }
-            serde_json::Value::Bool(_) => Ok(PlankType::Bool),
-            serde_json::Value::String(_) => Ok(PlankType::Str),
-            serde_json::Value::Object(o) => {
-                let fields = o
-                    .iter()
-                    .map(|(k, v)| {
-                        Ok(PlankField::new(
-                            k,
-                            Self::infer_extended_type(&v.to_string())?,
-                        ))
-                    })
-                    .collect::<std::io::Result<Vec<PlankField>>>()?;
-
-                Ok(PlankType::Struct(fields))
+            (JsonType::List(a, a_nulls), JsonType::List(b, b_nulls)) => {
+                JsonType::List(Box::new(a.merge(*b)), a_nulls || b_nulls)
             }
-            serde_json::Value::Array(a) => {
-                let items = a
-                    .iter()
-                    .map(|v| Self::infer_extended_type(&v.to_string()))
-                    .collect::<std::io::Result<Vec<_>>>()?;
-
-                // if !(items.is_empty() || items.iter().all(|e| items[0] == e)) {
-                //     Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "list should be homogeneous"))
-                // }
-
-                // Need a way to infer type if the list is empty
-                Ok(PlankType::List(Box::new(items[0].clone())))
+            (JsonType::Struct(a), JsonType::Struct(b)) => {
+                Self::struct_or_map(Self::merge_fields(a, b))
             }
-            _ => Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                "unsupported data type",
-            )),
+            (JsonType::Map(a), JsonType::Map(b)) => JsonType::Map(Box::new(a.merge(*b))),
+            (JsonType::Map(value), JsonType::Struct(fields))
+            | (JsonType::Struct(fields), JsonType::Map(value)) => {
+                JsonType::Map(Box::new(Self::merge_values(*value, fields)))
+            }
+            (a, b) if a == b => a,
+            _ => JsonType::Str,
+        }
+    }
+
+    pub(crate) fn merge_fields(
+        mut fields: Vec<(String, JsonType)>,
+        other: Vec<(String, JsonType)>,
+    ) -> Vec<(String, JsonType)> {
+        for (name, t) in other {
+            match fields.iter_mut().find(|(n, _)| *n == name) {
+                Some((_, field)) => {
+                    *field = std::mem::replace(field, JsonType::Unknown).merge(t);
+                }
+                None => fields.push((name, t)),
+            }
+        }
+        fields
+    }
+
+    /// Makes integers `Int64`, for values yet to be seen that may not fit an `Int32`
+    pub(crate) fn widen(self) -> Self {
+        match self {
+            JsonType::Int32 => JsonType::Int64,
+            JsonType::List(item, nulls) => JsonType::List(Box::new(item.widen()), nulls),
+            JsonType::Struct(fields) => JsonType::Struct(
+                fields
+                    .into_iter()
+                    .map(|(name, t)| (name, t.widen()))
+                    .collect(),
+            ),
+            JsonType::Map(value) => JsonType::Map(Box::new(value.widen())),
+            t => t,
+        }
+    }
+
+    pub(crate) fn into_type(self) -> PlankType {
+        match self {
+            JsonType::Unknown | JsonType::Str => PlankType::Str,
+            JsonType::Int32 => PlankType::Int32,
+            JsonType::Int64 => PlankType::Int64,
+            JsonType::Bool => PlankType::Bool,
+            JsonType::List(item, nulls) => {
+                let item = item.into_type();
+                PlankType::List(Box::new(if nulls { item.nullable() } else { item }))
+            }
+            // A struct needs a field to be stored, an always empty object is kept as text
+            JsonType::Struct(fields) if fields.is_empty() => PlankType::Str,
+            JsonType::Struct(fields) => PlankType::Struct(
+                fields
+                    .into_iter()
+                    .map(|(name, t)| PlankField::new(&name, t.into_type().nullable()))
+                    .collect(),
+            ),
+            JsonType::Map(value) => PlankType::map(value.into_type().nullable()),
         }
     }
 }
@@ -105,6 +257,8 @@ impl fmt::Display for PlankType {
             Self::Bool => write!(f, "Bool"),
             Self::Struct(_) => write!(f, "Struct"),
             Self::List(_) => write!(f, "List"),
+            Self::Nullable(inner) => write!(f, "Nullable<{}>", inner),
+            Self::Map(value) => write!(f, "Map<{}>", value),
         }
     }
 }
@@ -118,6 +272,8 @@ impl Serialize for PlankType {
             Self::Bool => 4,
             Self::Struct(_) => 5,
             Self::List(_) => 6,
+            Self::Nullable(_) => 7,
+            Self::Map(_) => 8,
         };
         let mut v = id.to_le_bytes().to_vec();
 
@@ -126,8 +282,8 @@ impl Serialize for PlankType {
             for field in fields {
                 v.extend_from_slice(&field.to_bytes()?);
             }
-        } else if let Self::List(list_type) = self {
-            v.extend_from_slice(&list_type.to_bytes()?);
+        } else if let Self::List(inner) | Self::Nullable(inner) | Self::Map(inner) = self {
+            v.extend_from_slice(&inner.to_bytes()?);
         }
 
         Ok(v)
@@ -136,34 +292,39 @@ impl Serialize for PlankType {
 
 impl<'a> Deserialize<'a> for PlankType {
     type Schema = ();
-    fn from_bytes(bytes: &[u8], schema: &'a Self::Schema) -> std::io::Result<Self> {
-        let id = bytes[0]
-            .try_into()
-            .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "expected u8"))?;
+    fn from_bytes(bytes: &[u8], schema: &'a Self::Schema) -> std::io::Result<(Self, usize)> {
+        let id = *bytes
+            .first()
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "expected u8"))?;
         match id {
-            1 => Ok(Self::Str),
-            2 => Ok(Self::Int32),
-            3 => Ok(Self::Int64),
-            4 => Ok(Self::Bool),
+            1 => Ok((Self::Str, 1)),
+            2 => Ok((Self::Int32, 1)),
+            3 => Ok((Self::Int64, 1)),
+            4 => Ok((Self::Bool, 1)),
             5 => {
                 let mut v = Vec::new();
-                let fields_size = u32::from_le_bytes(bytes[1..5].try_into().map_err(|_| {
-                    std::io::Error::new(std::io::ErrorKind::InvalidData, "expected u32")
-                })?) as usize;
+                let fields_size = read_u32(bytes, 1)? as usize;
                 let mut pos = 5;
                 for _ in 0..fields_size {
-                    let t = PlankField::from_bytes(&bytes[pos..], schema)?;
-                    // let t = PlankType::from_bytes(&bytes[pos..])?;
-                    pos += t.encoded_size();
+                    let (t, size) = PlankField::from_bytes(&bytes[pos..], schema)?;
+                    pos += size;
                     v.push(t);
                 }
 
-                Ok(Self::Struct(v))
+                Ok((Self::Struct(v), pos))
+            }
+            6 => {
+                let (item_type, size) = PlankType::from_bytes(&bytes[1..], &())?;
+                Ok((Self::List(Box::new(item_type)), 1 + size))
+            }
+            7 => {
+                let (inner, size) = PlankType::from_bytes(&bytes[1..], &())?;
+                Ok((Self::Nullable(Box::new(inner)), 1 + size))
+            }
+            8 => {
+                let (value, size) = PlankType::from_bytes(&bytes[1..], &())?;
+                Ok((Self::Map(Box::new(value)), 1 + size))
             }
-            6 => Ok(Self::List(Box::new(PlankType::from_bytes(
-                &bytes[1..],
-                &(),
-            )?))),
             _ => Err(std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
                 format!("unknown type id {}", id),
@@ -182,7 +343,9 @@ mod tests {
         let int_type = PlankType::Int32;
 
         let serialized = int_type.to_bytes().unwrap();
-        let deserialized = PlankType::from_bytes(&serialized, &()).unwrap();
+        let (deserialized, size) = PlankType::from_bytes(&serialized, &()).unwrap();
+
+        assert_eq!(size, serialized.len());
 
         assert_eq!(deserialized, int_type);
     }
@@ -195,11 +358,26 @@ mod tests {
         ]);
 
         let serialized = struct_type.to_bytes().unwrap();
-        let deserialized = PlankType::from_bytes(&serialized, &()).unwrap();
+        let (deserialized, size) = PlankType::from_bytes(&serialized, &()).unwrap();
+
+        assert_eq!(size, serialized.len());
 
         assert_eq!(deserialized, struct_type);
     }
 
+    #[test]
+    fn test_roundtrip_planktype_nullable() {
+        let nullable = PlankType::List(Box::new(PlankType::Int64.nullable())).nullable();
+
+        let serialized = nullable.to_bytes().unwrap();
+        let (deserialized, size) = PlankType::from_bytes(&serialized, &()).unwrap();
+
+        assert_eq!(size, serialized.len());
+        assert_eq!(size, nullable.encoded_size());
+        assert_eq!(deserialized, nullable);
+        assert_eq!(deserialized.clone().nullable(), deserialized);
+    }
+
     #[test]
     fn test_encoded_size_planktype_struct() {
         let t = PlankType::Struct(vec![
@@ -220,9 +398,43 @@ mod tests {
         assert_eq!(
             PlankType::infer_type(r#"{"name": "me", "age": 10}"#),
             PlankType::Struct(vec![
-                PlankField::new("name", PlankType::Str),
-                PlankField::new("age", PlankType::Int32)
+                PlankField::new("name", PlankType::Str.nullable()),
+                PlankField::new("age", PlankType::Int32.nullable())
             ])
-        )
+        );
+        assert_eq!(
+            PlankType::infer_type("[]"),
+            PlankType::List(Box::new(PlankType::Str))
+        );
+        assert_eq!(PlankType::infer_type("1.5"), PlankType::Str);
+    }
+
+    #[test]
+    fn test_infer_wide_objects_as_maps() {
+        let narrow = (0..MAP_KEYS)
+            .map(|i| format!(r#""k{}": {}"#, i, i))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let narrow = PlankType::infer_type(&format!("{{{}}}", narrow));
+        assert!(matches!(narrow, PlankType::Struct(ref fields) if fields.len() == MAP_KEYS));
+        assert_eq!(narrow.map_value(), None);
+
+        let wide = (0..=MAP_KEYS)
+            .map(|i| {
+                format!(
+                    r#""k{}": {}"#,
+                    i,
+                    if i == 0 {
+                        "null".to_string()
+                    } else {
+                        i.to_string()
+                    }
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let wide = PlankType::infer_type(&format!("{{{}}}", wide));
+        assert_eq!(wide, PlankType::map(PlankType::Int32.nullable()));
+        assert_eq!(wide.map_value(), Some(&PlankType::Int32.nullable()));
     }
 }